@@ -0,0 +1,33 @@
+/// The format of an audio output device.  Samples are always 32-bit float,
+/// interleaved by channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioSpec {
+    pub sample_rate: u32,
+    pub channels: u8,
+    /// Frames per callback; smaller is lower latency, but risks running dry
+    pub buffer_frames: u16,
+}
+
+impl Default for AudioSpec {
+    fn default() -> Self {
+        AudioSpec {
+            sample_rate: 48000,
+            channels: 2,
+            buffer_frames: 1024,
+        }
+    }
+}
+
+/// What the audio device pulls samples from.  This is called on the backend's
+/// audio thread, so it must not block for long.
+pub trait AudioSource: Send {
+    /// Fills `out` with interleaved samples in the output's channel count
+    fn fill(&mut self, out: &mut [f32], channels: u8);
+}
+
+/// An open audio device, which plays until it is dropped
+pub trait AudioOutput {
+    /// What the device actually gave us, which may differ from what was asked for
+    fn spec(&self) -> AudioSpec;
+    fn set_paused(&mut self, paused: bool);
+}
@@ -1,8 +1,10 @@
 use std::fmt::Display;
 
 use crate::{
+    audio::{AudioOutput, AudioSource, AudioSpec},
+    input::{CursorType, InputBackend},
     vs_error::VSError,
-    window::{WindowBuffers, WindowDepth, WindowExtent, WindowType},
+    window::{WindowBuffers, WindowDepth, WindowExtent, WindowSize, WindowType},
 };
 
 // Defines the api for the backend
@@ -17,6 +19,7 @@ pub trait Backend {
     fn display_modes(&self) -> Result<Vec<DisplayModeInfo>, VSError>;
     fn display_bounds(&self) -> Result<Vec<DisplayBoundsInfo>, VSError>;
 
+    #[allow(clippy::too_many_arguments)]
     fn create_window(
         &mut self,
         width: WindowExtent,
@@ -26,9 +29,23 @@ pub trait Backend {
         _buffer_count: WindowBuffers,
         _antialiass: bool,
         _vsyncnc: bool,
+        high_dpi: bool,
     ) -> Result<(), VSError>;
+    /// None until a window has been created
+    fn window_size(&self) -> Option<WindowSize>;
 
     fn show_cursor(&mut self, show: bool);
+    fn set_cursor(&mut self, cursor: CursorType);
+
+    /// The source of input events; there can only be one at a time
+    fn input_backend(&mut self) -> Result<Box<dyn InputBackend>, VSError>;
+
+    /// Opens the audio device, paused, pulling from `source` once it's unpaused
+    fn open_audio(
+        &mut self,
+        desired: AudioSpec,
+        source: Box<dyn AudioSource>,
+    ) -> Result<Box<dyn AudioOutput>, VSError>;
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -0,0 +1,457 @@
+use crate::{math::vector::Vector2D, window::WindowSize};
+
+// Both key enums share one table.  Scancodes name a physical key position (as
+// on a US layout), keycodes name what the key means on the user's layout.  The
+// numbers are USB HID usage ids, which is also what SDL uses for its scancodes.
+macro_rules! define_keys {
+    ( $( $key:ident = $code:literal, $name:literal; )* ) => {
+        /// A physical key position, named for its place on a US keyboard
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub enum Scancode {
+            $( $key, )*
+            Unknown,
+        }
+
+        /// A key as labelled on the user's keyboard layout
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub enum Keycode {
+            $( $key, )*
+            Unknown,
+        }
+
+        impl Scancode {
+            pub const ALL: &[Scancode] = &[ $( Scancode::$key, )* ];
+
+            pub fn from_usb_hid(code: u32) -> Self {
+                match code {
+                    $( $code => Scancode::$key, )*
+                    _ => Scancode::Unknown,
+                }
+            }
+
+            pub fn to_usb_hid(self) -> u32 {
+                match self {
+                    $( Scancode::$key => $code, )*
+                    Scancode::Unknown => 0,
+                }
+            }
+
+            /// For showing to players, e.g. in a controls menu
+            pub fn name(self) -> &'static str {
+                match self {
+                    $( Scancode::$key => $name, )*
+                    Scancode::Unknown => "Unknown",
+                }
+            }
+
+            /// Used when saving and loading
+            pub fn id(self) -> &'static str {
+                match self {
+                    $( Scancode::$key => stringify!($key), )*
+                    Scancode::Unknown => "Unknown",
+                }
+            }
+
+            pub fn from_id(id: &str) -> Option<Self> {
+                match id {
+                    $( stringify!($key) => Some(Scancode::$key), )*
+                    _ => None,
+                }
+            }
+        }
+
+        impl Keycode {
+            pub const ALL: &[Keycode] = &[ $( Keycode::$key, )* ];
+
+            /// The key which sits at this position on a US layout
+            pub fn from_scancode(scancode: Scancode) -> Self {
+                match scancode {
+                    $( Scancode::$key => Keycode::$key, )*
+                    Scancode::Unknown => Keycode::Unknown,
+                }
+            }
+
+            pub fn name(self) -> &'static str {
+                match self {
+                    $( Keycode::$key => $name, )*
+                    Keycode::Unknown => "Unknown",
+                }
+            }
+
+            pub fn id(self) -> &'static str {
+                match self {
+                    $( Keycode::$key => stringify!($key), )*
+                    Keycode::Unknown => "Unknown",
+                }
+            }
+
+            pub fn from_id(id: &str) -> Option<Self> {
+                match id {
+                    $( stringify!($key) => Some(Keycode::$key), )*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+define_keys! {
+    A = 4, "A"; B = 5, "B"; C = 6, "C"; D = 7, "D"; E = 8, "E"; F = 9, "F";
+    G = 10, "G"; H = 11, "H"; I = 12, "I"; J = 13, "J"; K = 14, "K"; L = 15, "L";
+    M = 16, "M"; N = 17, "N"; O = 18, "O"; P = 19, "P"; Q = 20, "Q"; R = 21, "R";
+    S = 22, "S"; T = 23, "T"; U = 24, "U"; V = 25, "V"; W = 26, "W"; X = 27, "X";
+    Y = 28, "Y"; Z = 29, "Z";
+    Num1 = 30, "1"; Num2 = 31, "2"; Num3 = 32, "3"; Num4 = 33, "4"; Num5 = 34, "5";
+    Num6 = 35, "6"; Num7 = 36, "7"; Num8 = 37, "8"; Num9 = 38, "9"; Num0 = 39, "0";
+    Return = 40, "Return"; Escape = 41, "Escape"; Backspace = 42, "Backspace";
+    Tab = 43, "Tab"; Space = 44, "Space"; Minus = 45, "-"; Equals = 46, "=";
+    LeftBracket = 47, "["; RightBracket = 48, "]"; Backslash = 49, "\\";
+    Semicolon = 51, ";"; Apostrophe = 52, "'"; Grave = 53, "`"; Comma = 54, ",";
+    Period = 55, "."; Slash = 56, "/"; CapsLock = 57, "Caps Lock";
+    F1 = 58, "F1"; F2 = 59, "F2"; F3 = 60, "F3"; F4 = 61, "F4"; F5 = 62, "F5";
+    F6 = 63, "F6"; F7 = 64, "F7"; F8 = 65, "F8"; F9 = 66, "F9"; F10 = 67, "F10";
+    F11 = 68, "F11"; F12 = 69, "F12";
+    PrintScreen = 70, "Print Screen"; ScrollLock = 71, "Scroll Lock"; Pause = 72, "Pause";
+    Insert = 73, "Insert"; Home = 74, "Home"; PageUp = 75, "Page Up"; Delete = 76, "Delete";
+    End = 77, "End"; PageDown = 78, "Page Down";
+    Right = 79, "Right"; Left = 80, "Left"; Down = 81, "Down"; Up = 82, "Up";
+    NumLock = 83, "Num Lock"; KpDivide = 84, "Keypad /"; KpMultiply = 85, "Keypad *";
+    KpMinus = 86, "Keypad -"; KpPlus = 87, "Keypad +"; KpEnter = 88, "Keypad Enter";
+    Kp1 = 89, "Keypad 1"; Kp2 = 90, "Keypad 2"; Kp3 = 91, "Keypad 3"; Kp4 = 92, "Keypad 4";
+    Kp5 = 93, "Keypad 5"; Kp6 = 94, "Keypad 6"; Kp7 = 95, "Keypad 7"; Kp8 = 96, "Keypad 8";
+    Kp9 = 97, "Keypad 9"; Kp0 = 98, "Keypad 0"; KpPeriod = 99, "Keypad .";
+    LCtrl = 224, "Left Ctrl"; LShift = 225, "Left Shift"; LAlt = 226, "Left Alt";
+    LGui = 227, "Left Super"; RCtrl = 228, "Right Ctrl"; RShift = 229, "Right Shift";
+    RAlt = 230, "Right Alt"; RGui = 231, "Right Super";
+}
+
+impl Keycode {
+    /// Keys which produce a character are identified by it on most platforms
+    pub fn from_char(c: char) -> Self {
+        match c.to_ascii_lowercase() {
+            'a'..='z' => Keycode::ALL[(c.to_ascii_lowercase() as u8 - b'a') as usize],
+            '1'..='9' => Keycode::ALL[26 + (c as u8 - b'1') as usize],
+            '0' => Keycode::Num0,
+            '\r' => Keycode::Return,
+            '\x1b' => Keycode::Escape,
+            '\x08' => Keycode::Backspace,
+            '\t' => Keycode::Tab,
+            ' ' => Keycode::Space,
+            '-' => Keycode::Minus,
+            '=' => Keycode::Equals,
+            '[' => Keycode::LeftBracket,
+            ']' => Keycode::RightBracket,
+            '\\' => Keycode::Backslash,
+            ';' => Keycode::Semicolon,
+            '\'' => Keycode::Apostrophe,
+            '`' => Keycode::Grave,
+            ',' => Keycode::Comma,
+            '.' => Keycode::Period,
+            '/' => Keycode::Slash,
+            '\x7f' => Keycode::Delete,
+            _ => Keycode::Unknown,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct KeyModifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub gui: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum MouseButton {
+    Left,
+    Middle,
+    Right,
+    X1,
+    X2,
+}
+
+impl MouseButton {
+    pub const ALL: &[MouseButton] = &[
+        MouseButton::Left,
+        MouseButton::Middle,
+        MouseButton::Right,
+        MouseButton::X1,
+        MouseButton::X2,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MouseButton::Left => "Left Mouse",
+            MouseButton::Middle => "Middle Mouse",
+            MouseButton::Right => "Right Mouse",
+            MouseButton::X1 => "Mouse 4",
+            MouseButton::X2 => "Mouse 5",
+        }
+    }
+}
+
+/// The backend's id for a connected gamepad; a gamepad which is unplugged and
+/// plugged back in gets a new id
+pub type GamepadId = u32;
+
+/// Named by position, on an Xbox-style layout
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum GamepadButton {
+    A,
+    B,
+    X,
+    Y,
+    Back,
+    Guide,
+    Start,
+    LeftStick,
+    RightStick,
+    LeftShoulder,
+    RightShoulder,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+impl GamepadButton {
+    pub const ALL: &[GamepadButton] = &[
+        GamepadButton::A,
+        GamepadButton::B,
+        GamepadButton::X,
+        GamepadButton::Y,
+        GamepadButton::Back,
+        GamepadButton::Guide,
+        GamepadButton::Start,
+        GamepadButton::LeftStick,
+        GamepadButton::RightStick,
+        GamepadButton::LeftShoulder,
+        GamepadButton::RightShoulder,
+        GamepadButton::DPadUp,
+        GamepadButton::DPadDown,
+        GamepadButton::DPadLeft,
+        GamepadButton::DPadRight,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GamepadButton::A => "A",
+            GamepadButton::B => "B",
+            GamepadButton::X => "X",
+            GamepadButton::Y => "Y",
+            GamepadButton::Back => "Back",
+            GamepadButton::Guide => "Guide",
+            GamepadButton::Start => "Start",
+            GamepadButton::LeftStick => "Left Stick Click",
+            GamepadButton::RightStick => "Right Stick Click",
+            GamepadButton::LeftShoulder => "Left Bumper",
+            GamepadButton::RightShoulder => "Right Bumper",
+            GamepadButton::DPadUp => "D-Pad Up",
+            GamepadButton::DPadDown => "D-Pad Down",
+            GamepadButton::DPadLeft => "D-Pad Left",
+            GamepadButton::DPadRight => "D-Pad Right",
+        }
+    }
+}
+
+/// Sticks run from -1 to 1, with positive y down; triggers run from 0 to 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum GamepadAxis {
+    LeftX,
+    LeftY,
+    RightX,
+    RightY,
+    TriggerLeft,
+    TriggerRight,
+}
+
+impl GamepadAxis {
+    pub const ALL: &[GamepadAxis] = &[
+        GamepadAxis::LeftX,
+        GamepadAxis::LeftY,
+        GamepadAxis::RightX,
+        GamepadAxis::RightY,
+        GamepadAxis::TriggerLeft,
+        GamepadAxis::TriggerRight,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GamepadAxis::LeftX => "Left Stick X",
+            GamepadAxis::LeftY => "Left Stick Y",
+            GamepadAxis::RightX => "Right Stick X",
+            GamepadAxis::RightY => "Right Stick Y",
+            GamepadAxis::TriggerLeft => "Left Trigger",
+            GamepadAxis::TriggerRight => "Right Trigger",
+        }
+    }
+
+    pub fn is_trigger(self) -> bool {
+        matches!(self, GamepadAxis::TriggerLeft | GamepadAxis::TriggerRight)
+    }
+}
+
+/// The backend's id for a finger on a touchscreen, for as long as it stays down
+pub type FingerId = i64;
+
+/// Trackpads scroll in many small, precise steps where a mouse wheel clicks
+/// in whole notches, so the two are scaled separately
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum WheelSource {
+    #[default]
+    Wheel,
+    Trackpad,
+}
+
+/// The mouse cursor images every backend provides
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum CursorType {
+    #[default]
+    Arrow,
+    IBeam,
+    Wait,
+    Crosshair,
+    WaitArrow,
+    SizeNWSE,
+    SizeNESW,
+    SizeWE,
+    SizeNS,
+    SizeAll,
+    No,
+    Hand,
+}
+
+impl CursorType {
+    pub const ALL: &[CursorType] = &[
+        CursorType::Arrow,
+        CursorType::IBeam,
+        CursorType::Wait,
+        CursorType::Crosshair,
+        CursorType::WaitArrow,
+        CursorType::SizeNWSE,
+        CursorType::SizeNESW,
+        CursorType::SizeWE,
+        CursorType::SizeNS,
+        CursorType::SizeAll,
+        CursorType::No,
+        CursorType::Hand,
+    ];
+}
+
+/// Everything the backend reports about the player and the window.  Positions
+/// are in window points, with (0,0) at the top left.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputEvent {
+    Quit,
+    WindowResized(WindowSize),
+    WindowFocus(bool),
+
+    KeyDown {
+        scancode: Scancode,
+        keycode: Keycode,
+        modifiers: KeyModifiers,
+        repeat: bool,
+    },
+    KeyUp {
+        scancode: Scancode,
+        keycode: Keycode,
+        modifiers: KeyModifiers,
+    },
+    /// Committed text, after any IME composition has finished
+    TextInput(String),
+    /// Text the IME is still composing; `cursor` and `selection` are in characters
+    TextEditing {
+        text: String,
+        cursor: i32,
+        selection: i32,
+    },
+
+    MouseMotion {
+        position: Vector2D,
+        delta: Vector2D,
+    },
+    MouseButtonDown {
+        button: MouseButton,
+        position: Vector2D,
+        clicks: u8,
+    },
+    MouseButtonUp {
+        button: MouseButton,
+        position: Vector2D,
+    },
+    MouseWheel {
+        delta: Vector2D,
+        source: WheelSource,
+    },
+
+    /// Pressure runs from 0 to 1, on screens which measure it; others report 1
+    TouchDown {
+        finger: FingerId,
+        position: Vector2D,
+        pressure: f32,
+    },
+    TouchMotion {
+        finger: FingerId,
+        position: Vector2D,
+        delta: Vector2D,
+        pressure: f32,
+    },
+    TouchUp {
+        finger: FingerId,
+        position: Vector2D,
+    },
+
+    GamepadAdded {
+        id: GamepadId,
+        name: String,
+    },
+    GamepadRemoved {
+        id: GamepadId,
+    },
+    GamepadButtonDown {
+        id: GamepadId,
+        button: GamepadButton,
+    },
+    GamepadButtonUp {
+        id: GamepadId,
+        button: GamepadButton,
+    },
+    /// Raw, before any dead zone is applied
+    GamepadAxisMotion {
+        id: GamepadId,
+        axis: GamepadAxis,
+        value: f32,
+    },
+}
+
+/// The backend's source of input events, and the controls which go with them
+pub trait InputBackend {
+    /// Every event which has arrived since the last poll
+    fn poll_events(&mut self) -> Vec<InputEvent>;
+
+    /// Starts delivering TextInput and TextEditing events, showing an IME if there is one
+    fn start_text_input(&mut self);
+    fn stop_text_input(&mut self);
+    /// Where the text being entered is on screen, in window points, so an IME can avoid covering it
+    fn set_text_input_rect(&mut self, position: Vector2D, size: Vector2D);
+
+    /// Strengths run from 0 to 1; a new rumble replaces any already playing
+    fn set_rumble(&mut self, gamepad: GamepadId, low_frequency: f32, high_frequency: f32, seconds: f32);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_tables() {
+        assert_eq!(Scancode::from_usb_hid(4), Scancode::A);
+        assert_eq!(Scancode::F12.to_usb_hid(), 69);
+        assert_eq!(Scancode::from_id("KpEnter"), Some(Scancode::KpEnter));
+        assert_eq!(Keycode::from_char('Q'), Keycode::Q);
+        assert_eq!(Keycode::from_char('7'), Keycode::Num7);
+        assert_eq!(Keycode::from_scancode(Scancode::Num0), Keycode::Num0);
+        assert_eq!(Keycode::LShift.name(), "Left Shift");
+    }
+}
@@ -1,5 +1,7 @@
+pub mod audio;
 pub mod backend;
 pub mod color;
+pub mod input;
 pub mod math;
 pub mod utils;
 pub mod vs_error;
@@ -11,6 +13,7 @@ use std::sync::Mutex;
 pub use crate::color::*;
 pub use crate::math::spline::*;
 pub use crate::math::spring::*;
+pub use crate::math::transform::*;
 pub use crate::math::vector::*;
 
 pub type SharedTimeVal = Arc<Mutex<Option<u64>>>;
@@ -1,5 +1,6 @@
 pub mod random;
 pub mod spline;
 pub mod spring;
+pub mod transform;
 pub mod vector;
 pub mod vs_math;
@@ -1,23 +1,69 @@
 use turborand::prelude::*;
 
-#[allow(dead_code)]
+use crate::math::vector::Vector2D;
+
 pub struct Random {
     rand: Rng,
+    seed: u64,
 }
 
 impl Random {
     pub fn new() -> Random {
-        Random { rand: Rng::new() }
+        Random::new_from_seed64(Rng::new().gen_u64())
     }
 
     pub fn new_from_seed(seed: u32) -> Random {
+        Random::new_from_seed64(seed as u64)
+    }
+
+    pub fn new_from_seed64(seed: u64) -> Random {
         Random {
-            rand: Rng::with_seed(seed as u64),
+            rand: Rng::with_seed(seed),
+            seed,
         }
     }
 
+    /// What the sequence started from; reseeding with it repeats the sequence
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn reseed(&mut self, seed: u64) {
+        self.rand.reseed(seed);
+        self.seed = seed;
+    }
+
     pub fn new_from_string(seed_string: &str) -> Random {
         let seed = seed_string.bytes().fold(0, |acc, b| acc + b as u32);
         Random::new_from_seed(seed)
     }
+
+    /// Uniform in min..max
+    pub fn get_float(&self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.rand.f32()
+    }
+
+    /// Uniform in min..=max
+    pub fn get_int(&self, min: i32, max: i32) -> i32 {
+        self.rand.i32(min..=max)
+    }
+
+    pub fn get_bool(&self) -> bool {
+        self.rand.bool()
+    }
+
+    /// In radians, 0..2π
+    pub fn get_angle(&self) -> f32 {
+        self.get_float(0.0, std::f32::consts::TAU)
+    }
+
+    pub fn get_unit_vector(&self) -> Vector2D {
+        Vector2D::from_angle(self.get_angle())
+    }
+}
+
+impl Default for Random {
+    fn default() -> Self {
+        Random::new()
+    }
 }
@@ -14,6 +14,25 @@ impl Spline1D {
     //     Self { start: 0.0, start_vel: 0.0, end: 0.0, end_vel: 0.0 }
     // }
 
+    pub fn constant(value: f32) -> Self {
+        Self {
+            start: value,
+            start_vel: 0.0,
+            end: value,
+            end_vel: 0.0,
+        }
+    }
+
+    /// A straight line from start to end
+    pub fn linear(start: f32, end: f32) -> Self {
+        Self {
+            start,
+            start_vel: end - start,
+            end,
+            end_vel: end - start,
+        }
+    }
+
     pub fn position_at_time(&self, t: f32) -> f32 {
         assert!(
             (0. ..=1.).contains(&t),
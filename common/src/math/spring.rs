@@ -12,6 +12,9 @@ use crate::{Vector2D, Vector3D};
 /// Damping values lower than this will yield a spring which oscillates, and
 /// values higher than this will yield a very sluggish spring.
 ///
+/// Each update removes 'damping * timestep' of the velocity, so the damping is
+/// a rate per second and the spring behaves the same at any small timestep.
+///
 /// Note that as all these springs are implemented using euler integrations,
 /// large differences between 'center' and 'position' values may make them
 /// behave eratically when timesteps are large.
@@ -28,18 +31,18 @@ pub struct Spring {
 }
 
 impl Spring {
-    fn new ( stiffness: f32, damping_factor: f32 ) -> Self {
+    pub fn new ( stiffness: f32, damping_factor: f32 ) -> Self {
         Self { stiffness, damping: damping_factor, ..Default::default() }
     }
 
-    fn new_damped ( stiffness: f32 ) -> Self {
+    pub fn new_damped ( stiffness: f32 ) -> Self {
         Self { stiffness, damping: 2.0 * stiffness.sqrt(), ..Default::default() }
     }
 
     pub fn update ( &mut self, timestep: f32 ) -> f32 {
         let delta = self.center - self.position;
 
-        self.velocity *= self.damping * timestep;
+        self.velocity -= self.velocity * self.damping * timestep;
         self.velocity += delta * self.stiffness * timestep;
 
         self.position += self.velocity * timestep;
@@ -61,7 +64,7 @@ pub struct Spring2D {
 }
 
 impl Spring2D {
-    fn new ( stiffness: Vector2D, damping_factor: f32 ) -> Self {
+    pub fn new ( stiffness: Vector2D, damping_factor: f32 ) -> Self {
         Self { stiffness, damping: damping_factor, ..Default::default() }
         // Self { stiffness, damping: VSvector2D{ x: damping_factor, y: damping_factor}, ..Default::default() }
     }
@@ -69,7 +72,7 @@ impl Spring2D {
     pub fn update ( &mut self, timestep: f32 ) -> &Vector2D {
         let delta = self.center - self.position;
 
-        self.velocity *= self.damping * timestep;
+        self.velocity -= self.velocity * self.damping * timestep;
         // self.velocity.x *= self.damping * timestep;
         // self.velocity.y *= self.damping * timestep;
 
@@ -104,7 +107,7 @@ impl Spring3D {
     pub fn update ( &mut self, timestep: f32 ) -> &Vector3D {
         let delta = self.center - self.position;
 
-        self.velocity *= self.damping * timestep;
+        self.velocity -= self.velocity * self.damping * timestep;
         // self.velocity.x *= self.damping * timestep;
         // self.velocity.y *= self.damping * timestep;
         // self.velocity.z *= self.damping * timestep;
@@ -119,3 +122,56 @@ impl Spring3D {
         &self.position
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // steps the spring towards 1.0 for 'seconds', returning the furthest it got
+    fn settle ( spring: &mut Spring, seconds: f32 ) -> f32 {
+        spring.center = 1.0;
+        let mut furthest = spring.position;
+        for _ in 0..( seconds * 120.0 ) as usize {
+            furthest = furthest.max( spring.update( 1.0 / 120.0 ) );
+        }
+        furthest
+    }
+
+    #[test]
+    fn critically_damped_spring_settles_without_overshoot() {
+        let mut spring = Spring::new_damped( 100.0 );
+        let furthest = settle( &mut spring, 0.5 );
+        assert!( furthest <= 1.0, "overshot to {furthest}" );
+        assert!( spring.position > 0.95 );
+        settle( &mut spring, 1.5 );
+        assert!( ( spring.position - 1.0 ).abs() < 1e-3 );
+    }
+
+    #[test]
+    fn lightly_damped_spring_overshoots_then_settles() {
+        // a tenth of critical damping overshoots by about 70%
+        let mut spring = Spring::new( 100.0, 2.0 );
+        let furthest = settle( &mut spring, 10.0 );
+        assert!( furthest > 1.6 && furthest < 1.85, "peaked at {furthest}" );
+        assert!( ( spring.position - 1.0 ).abs() < 1e-3 );
+    }
+
+    #[test]
+    fn vector_springs_move_like_scalar_springs() {
+        let mut spring = Spring::new( 100.0, 2.0 );
+        let mut spring_2d = Spring2D::new( Vector2D::new( 100.0, 100.0 ), 2.0 );
+        let mut spring_3d = Spring3D::new( Vector3D::new( 100.0, 100.0, 100.0 ), 2.0 );
+        spring.center = 1.0;
+        spring_2d.center = Vector2D::new( 1.0, -1.0 );
+        spring_3d.center = Vector3D::new( 1.0, -1.0, 1.0 );
+        for _ in 0..60 {
+            let position = spring.update( 1.0 / 120.0 );
+            let position_2d = *spring_2d.update( 1.0 / 120.0 );
+            let position_3d = *spring_3d.update( 1.0 / 120.0 );
+            assert!( ( position_2d.x - position ).abs() < 1e-5 );
+            assert!( ( position_2d.y + position ).abs() < 1e-5 );
+            assert!( ( position_3d.z - position ).abs() < 1e-5 );
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use crate::math::vector::{Affine2D, Vector2D};
+
+/// A 2D position, rotation (in radians) and scale.  Transforms are applied
+/// to points in the order scale, then rotate, then translate.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Transform2D {
+    pub position: Vector2D,
+    pub angle: f32,
+    pub scale: Vector2D,
+}
+
+impl Default for Transform2D {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Transform2D {
+    pub const IDENTITY: Transform2D = Transform2D {
+        position: Vector2D::ZERO,
+        angle: 0.0,
+        scale: Vector2D::ONE,
+    };
+
+    pub fn new(position: Vector2D, angle: f32, scale: Vector2D) -> Self {
+        assert!(!angle.is_nan());
+        Transform2D {
+            position,
+            angle,
+            scale,
+        }
+    }
+
+    pub fn from_position(position: Vector2D) -> Self {
+        Transform2D {
+            position,
+            ..Self::IDENTITY
+        }
+    }
+
+    pub fn from_position_angle(position: Vector2D, angle: f32) -> Self {
+        Transform2D::new(position, angle, Vector2D::ONE)
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == Self::IDENTITY
+    }
+
+    pub fn to_affine(&self) -> Affine2D {
+        Affine2D::from_scale_angle_translation(self.scale, self.angle, self.position)
+    }
+
+    /// Moves a point from this transform's local space into its parent space
+    pub fn apply_to(&self, point: Vector2D) -> Vector2D {
+        Vector2D::from_angle(self.angle).rotate(point * self.scale) + self.position
+    }
+
+    /// Rotates and scales a direction, ignoring the translation
+    pub fn apply_to_direction(&self, direction: Vector2D) -> Vector2D {
+        Vector2D::from_angle(self.angle).rotate(direction * self.scale)
+    }
+
+    /// Moves a point from parent space back into this transform's local space
+    pub fn inverse_apply_to(&self, point: Vector2D) -> Vector2D {
+        Vector2D::from_angle(-self.angle).rotate(point - self.position) / self.scale
+    }
+}
@@ -1,10 +1,11 @@
-use glam::{Vec3A, Vec2, IVec3, Vec4};
+use glam::{Affine2, Vec3A, Vec2, IVec3, Vec4};
 
 
 pub type Vector2D = Vec2;
 pub type Vector3D = Vec3A;
 pub type Vector4D = Vec4;
 pub type Position3D = IVec3;
+pub type Affine2D = Affine2;
 
 // #[derive(PartialEq, Debug, Clone, Copy, Default)]
 // pub struct VSvector2D {
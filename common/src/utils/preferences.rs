@@ -17,7 +17,6 @@ pub enum PreferenceValue {
 use PreferenceValue::*;
 
 
-#[allow(dead_code)]
 pub struct Preferences {
     preferences: BTreeMap<String, PreferenceValue>,
     filename: String,  // probably should be a path
@@ -34,6 +33,10 @@ impl Preferences {
         }
     }
 
+    pub fn get_filename ( &self ) -> &str {
+        &self.filename
+    }
+
     pub fn get_preference ( &self, label: &str ) -> Option<PreferenceValue> {
         self.preferences.get(label).copied()
     }
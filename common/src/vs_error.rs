@@ -9,7 +9,26 @@ pub enum VSError {
     Backend_NoResolutionFound,
     Backend_DisplayBoundsInfoMissing,
     Backend_NoWindow,
+    Backend_NoGLContext,
+    Backend_NoEventPump,
+    Backend_NoAudio,
 
     Core_DuplicateGameSystem,
     Core_SystemNotFound(GameSystemType),
+
+    Input_BindingsReadFailed,
+    Input_BindingsWriteFailed,
+    Input_RecordingReadFailed,
+    Input_RecordingWriteFailed,
+    Input_RecordingParseFailed,
+
+    Render_TextureNotFound,
+    Render_TextureDecodeFailed,
+    Render_NoFramebuffer,
+    Render_CaptureWriteFailed,
+
+    Sound_FileNotFound,
+    Sound_DecodeFailed,
+    Sound_UnsupportedFormat,
+    Sound_WriteFailed,
 }
@@ -8,3 +8,34 @@ pub enum WindowType {
     Fullscreen,
     FullscreenWindow,
 }
+
+/// A window's size in points, along with the size of its drawable area in pixels.
+/// These only differ when the window is high-dpi.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowSize {
+    pub width: WindowExtent,
+    pub height: WindowExtent,
+    pub width_pixels: WindowExtent,
+    pub height_pixels: WindowExtent,
+}
+
+impl WindowSize {
+    /// A window whose points and pixels are the same size
+    pub fn from_points(width: WindowExtent, height: WindowExtent) -> Self {
+        WindowSize {
+            width,
+            height,
+            width_pixels: width,
+            height_pixels: height,
+        }
+    }
+
+    /// Pixels per point
+    pub fn pixel_scale(&self) -> f32 {
+        if self.width == 0 {
+            1.0
+        } else {
+            self.width_pixels as f32 / self.width as f32
+        }
+    }
+}
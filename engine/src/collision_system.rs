@@ -0,0 +1,24 @@
+use physics::collision_system::CollisionSystem;
+
+use crate::core::{core_game::CoreGame, core_game_system::CoreGameSystem};
+
+impl CoreGameSystem for CollisionSystem {
+    fn is_active(&self) -> bool {
+        CollisionSystem::is_active(self)
+    }
+
+    fn set_active(&mut self, active: bool) {
+        CollisionSystem::set_active(self, active);
+    }
+
+    fn init(&mut self) {}
+
+    fn deinit(&mut self) {}
+
+    // before the game's update, so it sees this frame's contacts
+    fn update(&mut self, core: &mut CoreGame) {
+        CollisionSystem::update(self, core.get_time_step());
+    }
+
+    fn post_update(&mut self, _core: &mut CoreGame) {}
+}
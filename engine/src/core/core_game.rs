@@ -4,12 +4,14 @@ use crate::core::{
 };
 
 use common::GameSystemType;
-use log::info;
+use log::{error, info};
+use render::{RefScreen, debug_draw, display_list::DisplayList};
 use std::sync::{Arc, Mutex};
 
 pub trait GameCode {
-    fn update(&mut self, delta: f32);
-    fn draw_frame(&mut self);
+    /// `systems` gives access to input, sound and collision for this frame
+    fn update(&mut self, delta: f32, systems: &mut GameSystems);
+    fn draw_frame(&mut self, display_list: &mut DisplayList);
     fn should_exit(&self) -> bool;
 }
 
@@ -64,32 +66,64 @@ impl CoreGame {
         self.exit
     }
 
-    pub fn go(&mut self, systems: &mut GameSystems) {
+    /// This frame's, as measured by the TimerSystem
+    pub fn get_time_step(&self) -> f32 {
+        *self
+            .time_step
+            .lock()
+            .expect("unable to fetch timestep from mutex in core game")
+    }
+
+    /// Replaces this frame's measured time step, e.g. when replaying input
+    pub fn set_time_step(&self, time_step: f32) {
+        *self
+            .time_step
+            .lock()
+            .expect("unable to set timestep in mutex in core game") = time_step;
+    }
+
+    pub fn go(&mut self, systems: &mut GameSystems, screen: Option<&RefScreen>) {
         self.frames_rendered += 1;
         let system_order = self.game_system_order.clone();
 
         // update game systems
         for system in system_order.iter() {
-            systems.update_system(*system, self, PrePostUpdate::PreUpdate);
+            if let Err(e) = systems.update_system(*system, self, PrePostUpdate::PreUpdate) {
+                error!("unable to update game system in core game go: {:?}", e);
+            }
         }
 
         info!("in core game go");
 
-        self.code.update(
-            *self
-                .time_step
-                .lock()
-                .expect("unable to fetch timestep from mutex in core game update"),
-        );
+        let time_step = self.get_time_step();
+        self.code.update(time_step, systems);
 
         // ..
 
         // update game systems post update
         for system in system_order.iter() {
-            systems.update_system(*system, self, PrePostUpdate::PostUpdate);
+            if let Err(e) = systems.update_system(*system, self, PrePostUpdate::PostUpdate) {
+                error!("unable to update game system in core game go: {:?}", e);
+            }
         }
 
-        self.code.draw_frame();
+        match screen {
+            Some(screen) => {
+                let mut screen = screen.borrow_mut();
+                self.code.draw_frame(screen.get_fifo());
+                if let Some(timer) = systems.get_timer() {
+                    timer.end_gather_time();
+                }
+
+                screen.draw();
+                if let Some(timer) = systems.get_timer() {
+                    timer.end_draw_time();
+                    timer.set_gpu_time(screen.get_render_stats().gpu_time);
+                }
+            }
+            None => self.code.draw_frame(&mut DisplayList::new()),
+        }
+        debug_draw::advance(time_step);
 
         self.exit = self.code.should_exit();
     }
@@ -1,4 +1,7 @@
 use elsa::FrozenIndexMap;
+use input::input_system::InputSystem;
+use physics::collision_system::CollisionSystem;
+use sound::sound_system::SoundSystem;
 
 use common::{GameSystemType, vs_error::VSError};
 
@@ -42,6 +45,7 @@ impl GameSystems {
         // is bool right?
         if self.systems.get_index_of(&system_type).is_none() {
             self.systems.insert(system_type, system);
+            self.systems_order.push(system_type);
             Ok(())
         } else {
             Err(VSError::Core_DuplicateGameSystem)
@@ -81,11 +85,29 @@ impl GameSystems {
     }
 
     pub fn get_timer(&mut self) -> Option<&mut TimerSystem> {
-        if self.timer.is_some() {
-            self.timer.as_mut().unwrap().downcast_mut::<TimerSystem>()
-        } else {
-            None
-        }
+        self.timer
+            .as_mut()
+            .and_then(|timer| timer.downcast_mut::<TimerSystem>())
+    }
+
+    pub fn get_input(&mut self) -> Option<&mut InputSystem> {
+        self.get_system::<InputSystem>(GameSystemType::Input)
+    }
+
+    pub fn get_collision(&mut self) -> Option<&mut CollisionSystem> {
+        self.get_system::<CollisionSystem>(GameSystemType::Collision)
+    }
+
+    pub fn get_sound(&mut self) -> Option<&mut SoundSystem> {
+        self.get_system::<SoundSystem>(GameSystemType::Sound)
+    }
+
+    /// Any system, by the type it was inserted as
+    pub fn get_system<T: CoreGameSystem>(&mut self, system_type: GameSystemType) -> Option<&mut T> {
+        self.systems
+            .as_mut()
+            .get_mut(&system_type)
+            .and_then(|system| system.downcast_mut::<T>())
     }
 
     pub fn system_order(&self) -> Vec<GameSystemType> {
@@ -1,10 +1,18 @@
+use std::cell::RefCell;
+use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 
-use common::SharedTimeVal;
+use common::{GameSystemType, SharedTimeVal, input::Scancode};
+use input::input_system::InputSystem;
+use log::error;
+use physics::collision_system::CollisionSystem;
+use render::RefScreen;
+use sound::sound_system::SoundSystem;
 
 use crate::core::core_game::*;
 use crate::core::core_game_registry::{CoreGameRegistry, GameId};
 use crate::core::game_systems::GameSystems;
+use crate::frame_capture::{FrameCapture, RefFrameCapture};
 use crate::timer_system::TimerSystem;
 // use crate::utils::preferences::Preferences;
 
@@ -19,6 +27,8 @@ pub struct Core {
     // preferences: Preferences,
     temp_refresh_rate: Arc<Mutex<u16>>,
     launch_time: SharedTimeVal,
+    screen: Option<RefScreen>,
+    frame_capture: RefFrameCapture,
 
     exit: bool,
     allow_exit: bool,
@@ -36,6 +46,8 @@ impl Core {
             allow_exit: true,
             temp_refresh_rate: Arc::new(Mutex::new(60)),
             launch_time,
+            screen: None,
+            frame_capture: Rc::new(RefCell::new(FrameCapture::new())),
         }
     }
     pub fn init(&mut self) {
@@ -46,16 +58,73 @@ impl Core {
 
     fn create_game_systems(&mut self) {
         // let timer = new_RefTimerSystem(self.temp_refresh_rate.clone(), self.launch_time.clone());
-        self.game_systems.set_timer(Box::new(TimerSystem::new(
+        if let Err(e) = self.game_systems.set_timer(Box::new(TimerSystem::new(
             self.temp_refresh_rate.clone(),
             self.launch_time.clone(),
-        )));
+        ))) {
+            error!(
+                "unable to set timer system when creating game systems: {:?}",
+                e
+            );
+        }
         // .push(Rc::new(RefCell::new(GameSystem::Timer(timer))));
         // TODO: create these systems
         // self.game_systems.insert(GameSystem::Timer, TimerSystem::new());
-        // self.game_systems.insert(GameSystem::Input, InputSystem::new());
-        // self.game_systems.insert(GameSystem::Collision, CollisionSystem::new());
-        // self.game_systems.insert(GameSystem::Sound, SoundSystem::new());
+        if let Err(e) = self
+            .game_systems
+            .insert(GameSystemType::Input, Box::new(InputSystem::new()))
+        {
+            error!(
+                "unable to insert input system when creating game systems: {:?}",
+                e
+            );
+        }
+        if let Err(e) = self
+            .game_systems
+            .insert(GameSystemType::Collision, Box::new(CollisionSystem::new()))
+        {
+            error!(
+                "unable to insert collision system when creating game systems: {:?}",
+                e
+            );
+        }
+        if let Err(e) = self
+            .game_systems
+            .insert(GameSystemType::Sound, Box::new(SoundSystem::new()))
+        {
+            error!(
+                "unable to insert sound system when creating game systems: {:?}",
+                e
+            );
+        }
+    }
+
+    /// The screen each game's frame is drawn to, once the system has created it
+    pub fn set_screen(&mut self, screen: RefScreen) {
+        self.screen = Some(screen);
+    }
+
+    pub fn get_frame_capture(&self) -> RefFrameCapture {
+        Rc::clone(&self.frame_capture)
+    }
+
+    /// Window events and engine hotkeys, which every game gets
+    fn handle_window_input(&mut self) {
+        let Some(input) = self.game_systems.get_input() else {
+            return;
+        };
+
+        if input.take_quit_request() {
+            self.exit = true;
+        }
+        if input.was_key_pressed(Scancode::F12) {
+            self.frame_capture.borrow_mut().request_screenshot();
+        }
+        if let Some(size) = input.take_resize()
+            && let Some(screen) = &self.screen
+        {
+            screen.borrow_mut().notify_resized(size);
+        }
     }
 
     pub fn register_game(
@@ -112,8 +181,22 @@ impl Core {
                     .game_registry
                     .get_game(current_game)
                     .expect("unable to find next game, when switching to next game");
-                game.go(&mut self.game_systems);
+                if let Some(screen) = &self.screen {
+                    if let Some(input) = self.game_systems.get_input() {
+                        input.set_window_to_screen(screen.borrow().window_to_logical_transform());
+                    }
+                    self.frame_capture
+                        .borrow_mut()
+                        .begin_frame(&mut screen.borrow_mut(), self.game_systems.get_timer());
+                }
+                game.go(&mut self.game_systems, self.screen.as_ref());
+                if let Some(screen) = &self.screen {
+                    self.frame_capture
+                        .borrow_mut()
+                        .end_frame(&mut screen.borrow_mut());
+                }
                 self.exit = game.should_exit();
+                self.handle_window_input();
             }
         }
     }
@@ -0,0 +1,224 @@
+use std::{
+    cell::RefCell,
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::{error, info};
+use render::screen::Screen;
+
+use crate::timer_system::TimerSystem;
+
+pub type RefFrameCapture = Rc<RefCell<FrameCapture>>;
+
+const DEFAULT_CAPTURE_DIRECTORY: &str = "screenshots";
+
+/// Capturing every frame, with the game clock locked to a fixed step
+#[derive(Debug, Clone, PartialEq)]
+struct CaptureSequence {
+    prefix: String,
+    frame: u32,
+    time_step: f32,
+}
+
+/// Writes rendered frames out as PNGs, either one at a time on request, or as
+/// a numbered sequence of every frame for assembling into video.
+#[derive(Debug)]
+pub struct FrameCapture {
+    directory: PathBuf,
+    supersample: u8,
+    screenshot_requested: bool,
+    sequence: Option<CaptureSequence>,
+    capturing_frame: bool,
+    supersampling: bool, // the screen was switched to the capture resolution
+}
+
+impl Default for FrameCapture {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameCapture {
+    pub fn new() -> Self {
+        FrameCapture {
+            directory: PathBuf::from(DEFAULT_CAPTURE_DIRECTORY),
+            supersample: 1,
+            screenshot_requested: false,
+            sequence: None,
+            capturing_frame: false,
+            supersampling: false,
+        }
+    }
+
+    pub fn set_directory(&mut self, directory: &Path) {
+        self.directory = directory.to_path_buf();
+    }
+
+    pub fn get_directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Captured frames are rendered at `factor` times the window resolution, then scaled down
+    pub fn set_supersample(&mut self, factor: u8) {
+        self.supersample = factor.max(1);
+    }
+
+    /// Writes the next rendered frame to a timestamped PNG
+    pub fn request_screenshot(&mut self) {
+        self.screenshot_requested = true;
+    }
+
+    /// Writes every frame to `{prefix}_00000.png`, `{prefix}_00001.png` ... and
+    /// steps the game by exactly `1 / frames_per_second` each frame, however
+    /// long the frame actually took to render and save.
+    pub fn start_sequence(&mut self, prefix: &str, frames_per_second: u32) {
+        if frames_per_second == 0 {
+            error!("unable to start capture sequence {}: no frame rate", prefix);
+            return;
+        }
+        info!(
+            "Starting capture sequence {} at {} fps",
+            prefix, frames_per_second
+        );
+        self.sequence = Some(CaptureSequence {
+            prefix: prefix.to_string(),
+            frame: 0,
+            time_step: 1.0 / frames_per_second as f32,
+        });
+    }
+
+    pub fn stop_sequence(&mut self) {
+        if let Some(sequence) = self.sequence.take() {
+            info!(
+                "Stopped capture sequence {} after {} frames",
+                sequence.prefix, sequence.frame
+            );
+        }
+    }
+
+    pub fn is_capturing_sequence(&self) -> bool {
+        self.sequence.is_some()
+    }
+
+    /// Called before the game runs each frame
+    pub fn begin_frame(&mut self, screen: &mut Screen, timer: Option<&mut TimerSystem>) {
+        if let Some(timer) = timer {
+            timer.set_fixed_time_step(self.sequence.as_ref().map(|s| s.time_step));
+        }
+
+        self.capturing_frame = self.screenshot_requested || self.sequence.is_some();
+        if self.capturing_frame {
+            screen.set_supersample(self.supersample);
+            screen.request_capture();
+            self.supersampling = true;
+        } else if self.supersampling {
+            // the sequence was stopped between frames
+            screen.set_supersample(1);
+            self.supersampling = false;
+        }
+    }
+
+    /// Called once the screen has drawn the frame
+    pub fn end_frame(&mut self, screen: &mut Screen) {
+        if !self.capturing_frame {
+            return;
+        }
+        self.capturing_frame = false;
+
+        self.write_frame(screen);
+        if self.sequence.is_none() {
+            screen.set_supersample(1);
+            self.supersampling = false;
+        }
+    }
+
+    fn write_frame(&mut self, screen: &mut Screen) {
+        let frame = match screen.take_captured_frame() {
+            Some(Ok(frame)) => frame,
+            Some(Err(e)) => {
+                error!("unable to read back frame for capture: {:?}", e);
+                self.screenshot_requested = false;
+                self.stop_sequence();
+                return;
+            }
+            None => return,
+        };
+
+        if let Err(e) = fs::create_dir_all(&self.directory) {
+            error!(
+                "unable to create capture directory {}: {}",
+                self.directory.display(),
+                e
+            );
+            return;
+        }
+
+        if self.screenshot_requested {
+            self.screenshot_requested = false;
+            let path = self
+                .directory
+                .join(format!("screenshot_{}.png", timestamp(SystemTime::now())));
+            // the error has already been logged
+            let _ = frame.write_png(&path);
+        }
+
+        if let Some(sequence) = &mut self.sequence {
+            let path = self
+                .directory
+                .join(format!("{}_{:05}.png", sequence.prefix, sequence.frame));
+            sequence.frame += 1;
+            if frame.write_png(&path).is_err() {
+                self.stop_sequence();
+            }
+        }
+    }
+}
+
+/// UTC `YYYYMMDD-HHMMSS-mmm`, so screenshots sort by the time they were taken
+fn timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((seconds / 86400) as i64);
+    let second_of_day = seconds % 86400;
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}-{:03}",
+        year,
+        month,
+        day,
+        second_of_day / 3600,
+        (second_of_day / 60) % 60,
+        second_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+// days since 1970-01-01 to a proleptic gregorian date
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(timestamp(UNIX_EPOCH), "19700101-000000-000");
+        let time = UNIX_EPOCH + Duration::from_millis(1_709_210_096_250);
+        assert_eq!(timestamp(time), "20240229-123456-250");
+    }
+}
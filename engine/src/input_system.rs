@@ -0,0 +1,27 @@
+use input::input_system::InputSystem;
+
+use crate::core::{core_game::CoreGame, core_game_system::CoreGameSystem};
+
+impl CoreGameSystem for InputSystem {
+    fn is_active(&self) -> bool {
+        InputSystem::is_active(self)
+    }
+
+    fn set_active(&mut self, active: bool) {
+        InputSystem::set_active(self, active);
+    }
+
+    fn init(&mut self) {}
+
+    fn deinit(&mut self) {}
+
+    fn update(&mut self, core: &mut CoreGame) {
+        InputSystem::update(self, core.get_time_step());
+        // a replayed frame takes exactly as long as it did when it was recorded
+        if let Some(time_step) = self.get_playback_time_step() {
+            core.set_time_step(time_step);
+        }
+    }
+
+    fn post_update(&mut self, _core: &mut CoreGame) {}
+}
@@ -1,4 +1,8 @@
+pub mod collision_system;
 pub mod core;
+pub mod frame_capture;
+pub mod input_system;
+pub mod sound_system;
 pub mod system;
 pub mod system_preferences;
 pub mod timer_system;
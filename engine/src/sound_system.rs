@@ -0,0 +1,26 @@
+use sound::sound_system::SoundSystem;
+
+use crate::core::{core_game::CoreGame, core_game_system::CoreGameSystem};
+
+impl CoreGameSystem for SoundSystem {
+    fn is_active(&self) -> bool {
+        SoundSystem::is_active(self)
+    }
+
+    fn set_active(&mut self, active: bool) {
+        SoundSystem::set_active(self, active);
+    }
+
+    fn init(&mut self) {}
+
+    fn deinit(&mut self) {
+        self.stop_all();
+    }
+
+    // mixing happens on the backend's audio thread; this keeps music decoded ahead of it
+    fn update(&mut self, _core: &mut CoreGame) {
+        SoundSystem::update(self);
+    }
+
+    fn post_update(&mut self, _core: &mut CoreGame) {}
+}
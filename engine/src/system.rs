@@ -1,19 +1,30 @@
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
 use std::sync::{Arc, Mutex};
-use std::{collections::HashMap, mem::transmute};
 
+use common::GameSystemType;
+use common::audio::AudioSpec;
 use common::backend::Backend;
+use common::input::CursorType;
+use common::vs_error::VSError;
 use common::window::WindowType;
-use log::{error, info};
+use log::{error, info, warn};
 
+use input::input_system::WheelSettings;
+use input::recording::InputRecording;
+use render::RefScreen;
 use render::screen::Screen;
-use sdl2::{mouse::Cursor, mouse::SystemCursor};
+use render::texture::TextureManager;
+use render::virtual_resolution::VirtualResolution;
 use sdl2_backend::SDL2_Backend;
-use sdl2_sys::{SDL_CreateSystemCursor, SDL_Cursor};
 
 use crate::core::vs_core::Core;
+use crate::frame_capture::RefFrameCapture;
 use crate::system_preferences::SystemPreferences;
 use common::math::random::Random;
 use common::utils::log::start_timer_log;
+use common::utils::preferences::PreferenceNumber;
 
 // use super::timer_system::LaunchTime;
 
@@ -23,8 +34,7 @@ pub const VS_VERSION: &str = "r0.0.1";
 pub struct System {
     show_cursor: bool,
     show_cursor_overridden: bool,
-    cursors: HashMap<SystemCursor, Cursor>, // !! these need to be in backend? Or have VS structs
-    cursors2: HashMap<SystemCursor, *mut SDL_Cursor>,
+    cursor: CursorType, // the backend keeps the cursors themselves
     focused: bool,
     visible: bool,
     exit_game_key_enabled: bool,
@@ -32,7 +42,7 @@ pub struct System {
     min_buffers: u8,
     // orientation: Orientation,
     title: String,
-    screen: Option<Screen>,
+    screen: Option<RefScreen>,
     data_is_pristine: bool,
 
     // launch_time: LaunchTime,
@@ -42,6 +52,7 @@ pub struct System {
     backend: Box<dyn Backend>,
 
     pub random: Random,
+    pub texture_manager: TextureManager,
 
     pub core: Core,
 }
@@ -68,8 +79,7 @@ impl System {
         System {
             show_cursor: true,
             show_cursor_overridden: false,
-            cursors: HashMap::new(),
-            cursors2: HashMap::new(),
+            cursor: CursorType::Arrow,
             focused: true,
             visible: false,
             exit_game_key_enabled: true,
@@ -83,24 +93,14 @@ impl System {
             // sdl: Rc::new(sdl_context),
             backend: Box::new(SDL2_Backend::new()),
             random: Random::new(),
+            texture_manager: TextureManager::new(),
             core: Core::new(launch_time.clone()),
         }
     }
 
     pub fn init(&mut self) {
-        // cursors (do these need to be pre-set?)
-        for curs in [
-            SystemCursor::Arrow,
-            SystemCursor::IBeam,
-            SystemCursor::Wait,
-            SystemCursor::Hand,
-        ] {
-            // self.cursors.insert(curs, Cursor::from_system(curs).expect("unable to fetch sdl system cursor on system init"));
-            unsafe {
-                self.cursors2
-                    .insert(curs, SDL_CreateSystemCursor(transmute(curs as u32)));
-            }
-        }
+        // cursors are created by the backend as they are first used
+        self.backend.set_cursor(self.cursor);
 
         // resolution
         if let Err(e) = self
@@ -139,8 +139,6 @@ impl System {
         // self.sdl.mouse().show_cursor(self.show_cursor);
         self.backend.show_cursor(self.show_cursor);
 
-        // TODO: texture manager
-
         // window type
         let window_type = if self.system_preferences.get_fullscreen() {
             if self.system_preferences.get_fullscreen_window() {
@@ -160,7 +158,7 @@ impl System {
             } else {
                 1
             });
-        self.screen = Some(Screen::new(
+        let screen = Rc::new(RefCell::new(Screen::new(
             &mut self.backend, // need to pass backend here
             width,
             height,
@@ -170,7 +168,10 @@ impl System {
             self.system_preferences.get_vsync(),
             self.system_preferences.get_antialias(),
             self.system_preferences.get_high_dpi(),
-        ));
+            self.system_preferences.get_dynamic_batching(),
+        )));
+        self.core.set_screen(Rc::clone(&screen));
+        self.screen = Some(screen);
 
         // TODO: log system details
 
@@ -188,6 +189,150 @@ impl System {
 
         // core
         self.core.init();
+
+        // input
+        match self.backend.input_backend() {
+            Ok(source) => match self.core.game_systems.get_input() {
+                Some(input) => {
+                    input.set_source(source);
+                    input.set_wheel_settings(WheelSettings {
+                        smoothing: self.system_preferences.get_wheel_smoothing(),
+                        mouse_scale: self.system_preferences.get_mouse_wheel_scale_percent() as f32
+                            / 100.0,
+                        trackpad_scale: self.system_preferences.get_trackpad_wheel_scale_percent()
+                            as f32
+                            / 100.0,
+                    });
+                    // the error has already been logged, and the game's defaults still apply
+                    let _ = input.set_bindings_file(&self.system_preferences.get_bindings_path());
+                }
+                None => {
+                    error!("no input system to attach input source to, when initializing system")
+                }
+            },
+            Err(e) => error!(
+                "unable to get input source when initializing system: {:?}",
+                e
+            ),
+        }
+
+        // sound
+        match self.core.game_systems.get_sound() {
+            Some(sound) => {
+                sound
+                    .set_effects_volume(self.system_preferences.get_effect_volume() as f32 / 100.0);
+                sound.set_music_volume(self.system_preferences.get_music_volume() as f32 / 100.0);
+                match self
+                    .backend
+                    .open_audio(AudioSpec::default(), sound.audio_source())
+                {
+                    Ok(output) => sound.set_output(output),
+                    // the game still runs, just silently
+                    Err(e) => error!("unable to open audio when initializing system: {:?}", e),
+                }
+            }
+            None => error!("no sound system to attach audio output to, when initializing system"),
+        }
+    }
+
+    pub fn set_cursor(&mut self, cursor: CursorType) {
+        self.cursor = cursor;
+        self.backend.set_cursor(cursor);
+    }
+
+    pub fn get_cursor(&self) -> CursorType {
+        self.cursor
+    }
+
+    /// Fixes the logical size games draw at, fitted to the window by its aspect policy
+    pub fn set_virtual_resolution(&mut self, virtual_resolution: Option<VirtualResolution>) {
+        match &self.screen {
+            Some(screen) => screen
+                .borrow_mut()
+                .set_virtual_resolution(virtual_resolution),
+            None => error!("unable to set virtual resolution before the screen is created"),
+        }
+    }
+
+    /// Controls screenshots and frame sequence capture
+    pub fn frame_capture(&self) -> RefFrameCapture {
+        self.core.get_frame_capture()
+    }
+
+    /// Saves the next rendered frame as a timestamped PNG
+    pub fn capture_screenshot(&mut self) {
+        self.core
+            .get_frame_capture()
+            .borrow_mut()
+            .request_screenshot();
+    }
+
+    /// The effect volume preference, as a percentage
+    pub fn get_effect_volume(&self) -> PreferenceNumber {
+        self.system_preferences.get_effect_volume()
+    }
+
+    /// Sets the effect volume preference, and the effects bus with it
+    pub fn set_effect_volume(&mut self, percent: PreferenceNumber) {
+        let percent = self.system_preferences.set_effect_volume(percent);
+        if let Some(sound) = self.core.game_systems.get_sound() {
+            sound.set_effects_volume(percent as f32 / 100.0);
+        }
+    }
+
+    /// The music volume preference, as a percentage
+    pub fn get_music_volume(&self) -> PreferenceNumber {
+        self.system_preferences.get_music_volume()
+    }
+
+    /// Sets the music volume preference, and the music bus with it
+    pub fn set_music_volume(&mut self, percent: PreferenceNumber) {
+        let percent = self.system_preferences.set_music_volume(percent);
+        if let Some(sound) = self.core.game_systems.get_sound() {
+            sound.set_music_volume(percent as f32 / 100.0);
+        }
+    }
+
+    /// Records the player's input from the next frame on.  `random` is reseeded,
+    /// and the seed kept with the recording, so a replay sees the same numbers.
+    pub fn start_input_recording(&mut self) -> Result<(), VSError> {
+        let seed = Random::new().get_seed();
+        let input = self
+            .core
+            .game_systems
+            .get_input()
+            .ok_or(VSError::Core_SystemNotFound(GameSystemType::Input))?;
+        input.start_recording(seed);
+        self.random.reseed(seed);
+        Ok(())
+    }
+
+    pub fn stop_input_recording(&mut self, path: &Path) -> Result<(), VSError> {
+        let input = self
+            .core
+            .game_systems
+            .get_input()
+            .ok_or(VSError::Core_SystemNotFound(GameSystemType::Input))?;
+        match input.stop_recording() {
+            Some(recording) => recording.save(path),
+            None => {
+                warn!("no input recording to stop");
+                Ok(())
+            }
+        }
+    }
+
+    /// Replays a recording in place of the player's input, from the next frame on
+    pub fn start_input_playback(&mut self, path: &Path) -> Result<(), VSError> {
+        let recording = InputRecording::load(path)?;
+        let input = self
+            .core
+            .game_systems
+            .get_input()
+            .ok_or(VSError::Core_SystemNotFound(GameSystemType::Input))?;
+        self.random.reseed(recording.seed);
+        input.start_playback(recording);
+        Ok(())
     }
 
     pub fn deinit(&mut self) {
@@ -5,8 +5,8 @@ use core::fmt::Debug;
 use indexmap::IndexSet;
 use log::info;
 use log::warn;
+use std::path::PathBuf;
 
-#[allow(unused_imports)]
 use common::utils::preferences::PreferenceNumber;
 use common::utils::preferences::Preferences;
 
@@ -86,6 +86,12 @@ impl SystemPreferences {
             .expect("system unable to find vsync setting")
     }
 
+    pub fn get_dynamic_batching(&self) -> bool {
+        self.preferences
+            .get_boolean_preference(DYNAMIC_BATCHING)
+            .expect("system unable to find dynamic batching setting")
+    }
+
     pub fn get_antialias(&self) -> bool {
         self.preferences
             .get_boolean_preference(ANTIALIAS)
@@ -98,6 +104,53 @@ impl SystemPreferences {
             .expect("system unable to find high dpi setting")
     }
 
+    pub fn get_effect_volume(&self) -> PreferenceNumber {
+        self.preferences
+            .get_number_preference(EFFECT_VOLUME)
+            .expect("system unable to find effect volume setting")
+    }
+
+    /// Clamped to 0-100; returns the value set
+    pub fn set_effect_volume(&mut self, volume: PreferenceNumber) -> PreferenceNumber {
+        self.preferences
+            .set_number_preference(EFFECT_VOLUME, volume.min(100))
+    }
+
+    pub fn get_music_volume(&self) -> PreferenceNumber {
+        self.preferences
+            .get_number_preference(MUSIC_VOLUME)
+            .expect("system unable to find music volume setting")
+    }
+
+    /// Clamped to 0-100; returns the value set
+    pub fn set_music_volume(&mut self, volume: PreferenceNumber) -> PreferenceNumber {
+        self.preferences
+            .set_number_preference(MUSIC_VOLUME, volume.min(100))
+    }
+
+    pub fn get_wheel_smoothing(&self) -> bool {
+        self.preferences
+            .get_boolean_preference(WHEEL_SMOOTHING)
+            .expect("system unable to find wheel smoothing setting")
+    }
+
+    pub fn get_mouse_wheel_scale_percent(&self) -> PreferenceNumber {
+        self.preferences
+            .get_number_preference(MOUSE_WHEEL_SCALE_PERCENT)
+            .expect("system unable to find mouse wheel scale setting")
+    }
+
+    pub fn get_trackpad_wheel_scale_percent(&self) -> PreferenceNumber {
+        self.preferences
+            .get_number_preference(TRACKPAD_WHEEL_SCALE_PERCENT)
+            .expect("system unable to find trackpad wheel scale setting")
+    }
+
+    /// The player's input bindings are kept next to the preferences
+    pub fn get_bindings_path(&self) -> PathBuf {
+        PathBuf::from(format!("{}.bindings", self.preferences.get_filename()))
+    }
+
     pub fn get_resolution_extents(&self) -> (WindowExtent, WindowExtent) {
         let res = self
             .supported_resolutions
@@ -16,17 +16,18 @@ pub struct TimerSystem {
 
     start_cpu: u64,
     start_gather: u64,
-    // start_draw: u64,
-    // start_gpu: u64,
+    start_draw: u64,
+    start_gpu: u64,
     missed_frames: u32, // ?? u16
 
-    // cpu_time: u64,
-    // gather_time: u64,
-    // draw_time: u64,
-    // gpu_time: u64,
+    cpu_time: u64,
+    gather_time: u64,
+    draw_time: u64,
+    gpu_time: u64,
     first_frame: bool,
 
     time_step: SharedTimeStep,
+    fixed_time_step: Option<f32>,
 
     refresh_rate: Arc<Mutex<u16>>,
 }
@@ -46,8 +47,8 @@ impl CoreGameSystem for TimerSystem {
         self.init_time = microseconds;
         self.start_cpu = microseconds;
         self.start_gather = microseconds;
-        // self.start_draw = microseconds;
-        // self.start_gpu = microseconds;
+        self.start_draw = microseconds;
+        self.start_gpu = microseconds;
         self.missed_frames = 0;
         self.first_frame = true;
     }
@@ -98,7 +99,10 @@ impl CoreGameSystem for TimerSystem {
 
             let ts = round_time as f32 / 1000000_f32;
 
-            if self.first_frame {
+            if let Some(fixed) = self.fixed_time_step {
+                self.first_frame = false;
+                fixed
+            } else if self.first_frame {
                 self.first_frame = false;
                 MIN_TIME_PER_FRAME
             } else if ts > MAX_TIME_PER_FRAME {
@@ -118,9 +122,19 @@ impl CoreGameSystem for TimerSystem {
 
     fn post_update(&mut self, _core: &mut CoreGame) {
         self.start_gather = get_microseconds();
+        self.cpu_time = self.start_gather.saturating_sub(self.start_cpu);
     }
 }
 
+/// Where the time went in the last frame, in microseconds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FrameTiming {
+    pub cpu_time: u64,    // game and system updates
+    pub gather_time: u64, // game filling its display list
+    pub draw_time: u64,   // screen batching and submitting the display list
+    pub gpu_time: u64,    // as reported by the renderer
+}
+
 #[allow(dead_code)]
 impl TimerSystem {
     pub fn new(refresh_rate: Arc<Mutex<u16>>, launch_time: SharedTimeVal) -> Self {
@@ -143,6 +157,40 @@ impl TimerSystem {
         Arc::clone(&self.time_step)
     }
 
+    /// Forces every frame to advance by exactly `time_step`, regardless of real time taken
+    pub fn set_fixed_time_step(&mut self, time_step: Option<f32>) {
+        self.fixed_time_step = time_step;
+    }
+
+    /// Called once the game has finished drawing into its display list
+    pub fn end_gather_time(&mut self) {
+        self.start_draw = get_microseconds();
+        self.gather_time = self.start_draw.saturating_sub(self.start_gather);
+    }
+
+    /// Called once the screen has handed the frame to the renderer
+    pub fn end_draw_time(&mut self) {
+        self.start_gpu = get_microseconds();
+        self.draw_time = self.start_gpu.saturating_sub(self.start_draw);
+    }
+
+    pub fn set_gpu_time(&mut self, gpu_time: u64) {
+        self.gpu_time = gpu_time;
+    }
+
+    pub fn get_frame_timing(&self) -> FrameTiming {
+        FrameTiming {
+            cpu_time: self.cpu_time,
+            gather_time: self.gather_time,
+            draw_time: self.draw_time,
+            gpu_time: self.gpu_time,
+        }
+    }
+
+    pub fn get_missed_frames(&self) -> u32 {
+        self.missed_frames
+    }
+
     fn get_microseconds_since_init(&self) -> u64 {
         get_microseconds() - self.init_time
     }
@@ -166,3 +214,27 @@ fn get_microseconds() -> u64 {
         (counter * 1000000) / SDL_GetPerformanceFrequency()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use super::*;
+
+    #[test]
+    fn frame_timing_splits_gather_draw_and_gpu() {
+        let mut timer = TimerSystem::new(Arc::new(Mutex::new(60)), Arc::new(Mutex::new(None)));
+        timer.start_gather = get_microseconds();
+        thread::sleep(Duration::from_millis(2));
+        timer.end_gather_time();
+        thread::sleep(Duration::from_millis(4));
+        timer.end_draw_time();
+        timer.set_gpu_time(1234);
+
+        let timing = timer.get_frame_timing();
+        assert!(timing.gather_time >= 2000);
+        assert!(timing.draw_time >= 4000);
+        assert_eq!(timing.gpu_time, 1234);
+        assert_eq!(timing.cpu_time, 0);
+    }
+}
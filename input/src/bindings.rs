@@ -0,0 +1,346 @@
+//! Actions are named by the game, and bound to keys, buttons and axes.  Each
+//! game supplies a default table; players' changes are kept as overrides on
+//! top of it, and only the overrides are saved, so a later version of the
+//! game can change its defaults without clobbering anyone's remapping.
+//!
+//! The bindings file is plain text, one action per line:
+//!
+//! ```text
+//! fire = Key:Space, GamepadButton:A
+//! move_left = Key:A, GamepadAxis:LeftX-
+//! ```
+
+use std::{collections::BTreeMap, fmt, fs, path::Path};
+
+use common::{
+    input::{GamepadAxis, GamepadButton, MouseButton, Scancode},
+    vs_error::VSError,
+};
+use log::{error, info, warn};
+
+use crate::gestures::GestureKind;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum AxisDirection {
+    Positive,
+    Negative,
+}
+
+impl AxisDirection {
+    pub fn sign(self) -> f32 {
+        match self {
+            AxisDirection::Positive => 1.0,
+            AxisDirection::Negative => -1.0,
+        }
+    }
+
+    fn suffix(self) -> char {
+        match self {
+            AxisDirection::Positive => '+',
+            AxisDirection::Negative => '-',
+        }
+    }
+}
+
+/// One physical input an action can be bound to.  Axes are bound one
+/// direction at a time, so "left" and "right" can be separate actions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum InputBinding {
+    Key(Scancode),
+    Mouse(MouseButton),
+    Wheel(AxisDirection), // vertical, positive is away from the player
+    GamepadButton(GamepadButton),
+    GamepadAxis(GamepadAxis, AxisDirection),
+    Gesture(GestureKind),
+}
+
+impl InputBinding {
+    /// For showing to players, e.g. "Space" or "Left Stick X+"
+    pub fn display_name(&self) -> String {
+        match self {
+            InputBinding::Key(scancode) => scancode.name().to_string(),
+            InputBinding::Mouse(button) => button.name().to_string(),
+            InputBinding::Wheel(AxisDirection::Positive) => "Wheel Up".to_string(),
+            InputBinding::Wheel(AxisDirection::Negative) => "Wheel Down".to_string(),
+            InputBinding::GamepadButton(button) => format!("Pad {}", button.name()),
+            InputBinding::GamepadAxis(axis, _) if axis.is_trigger() => axis.name().to_string(),
+            InputBinding::GamepadAxis(axis, direction) => {
+                format!("{}{}", axis.name(), direction.suffix())
+            }
+            InputBinding::Gesture(kind) => kind.name().to_string(),
+        }
+    }
+
+    /// Parses the form written to the bindings file, e.g. "Key:Space"
+    pub fn from_id(id: &str) -> Option<Self> {
+        let (kind, value) = id.trim().split_once(':')?;
+        match kind {
+            "Key" => Scancode::from_id(value).map(InputBinding::Key),
+            "Mouse" => find_by_id(MouseButton::ALL, value).map(InputBinding::Mouse),
+            "Wheel" => {
+                let (rest, direction) = directed(value)?;
+                rest.is_empty().then_some(InputBinding::Wheel(direction))
+            }
+            "GamepadButton" => {
+                find_by_id(GamepadButton::ALL, value).map(InputBinding::GamepadButton)
+            }
+            "GamepadAxis" => {
+                let (axis, direction) = directed(value)?;
+                find_by_id(GamepadAxis::ALL, axis)
+                    .map(|axis| InputBinding::GamepadAxis(axis, direction))
+            }
+            "Gesture" => GestureKind::from_id(value).map(InputBinding::Gesture),
+            _ => None,
+        }
+    }
+}
+
+// written to the bindings file
+impl fmt::Display for InputBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputBinding::Key(scancode) => write!(f, "Key:{}", scancode.id()),
+            InputBinding::Mouse(button) => write!(f, "Mouse:{:?}", button),
+            InputBinding::Wheel(direction) => write!(f, "Wheel:{}", direction.suffix()),
+            InputBinding::GamepadButton(button) => write!(f, "GamepadButton:{:?}", button),
+            InputBinding::GamepadAxis(axis, direction) => {
+                write!(f, "GamepadAxis:{:?}{}", axis, direction.suffix())
+            }
+            InputBinding::Gesture(kind) => write!(f, "Gesture:{}", kind.id()),
+        }
+    }
+}
+
+// "LeftX+" is the left stick's x axis, pushed right
+fn directed(value: &str) -> Option<(&str, AxisDirection)> {
+    let direction = match value.chars().last()? {
+        '+' => AxisDirection::Positive,
+        '-' => AxisDirection::Negative,
+        _ => return None,
+    };
+    Some((&value[..value.len() - 1], direction))
+}
+
+fn find_by_id<T: fmt::Debug + Copy>(all: &[T], id: &str) -> Option<T> {
+    all.iter().find(|item| format!("{:?}", item) == id).copied()
+}
+
+/// An input bound to more than one action
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BindingConflict {
+    pub binding: InputBinding,
+    pub actions: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Bindings {
+    defaults: BTreeMap<String, Vec<InputBinding>>,
+    overrides: BTreeMap<String, Vec<InputBinding>>,
+}
+
+impl Bindings {
+    pub fn new() -> Self {
+        Bindings::default()
+    }
+
+    /// The game's own binding for an action, used until the player changes it
+    pub fn set_default(&mut self, action: &str, bindings: &[InputBinding]) {
+        self.defaults.insert(action.to_string(), bindings.to_vec());
+    }
+
+    /// The player's binding for an action; an empty list leaves it unbound
+    pub fn bind(&mut self, action: &str, bindings: &[InputBinding]) {
+        self.overrides.insert(action.to_string(), bindings.to_vec());
+    }
+
+    /// Adds to whatever the action is bound to now
+    pub fn add_binding(&mut self, action: &str, binding: InputBinding) {
+        let mut bindings = self.get_bindings(action).to_vec();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+            self.bind(action, &bindings);
+        }
+    }
+
+    /// Back to the game's default
+    pub fn reset(&mut self, action: &str) {
+        self.overrides.remove(action);
+    }
+
+    pub fn reset_all(&mut self) {
+        self.overrides.clear();
+    }
+
+    pub fn get_bindings(&self, action: &str) -> &[InputBinding] {
+        self.overrides
+            .get(action)
+            .or_else(|| self.defaults.get(action))
+            .map_or(&[], |bindings| bindings.as_slice())
+    }
+
+    pub fn is_overridden(&self, action: &str) -> bool {
+        self.overrides.contains_key(action)
+    }
+
+    /// Every action with a default or a player binding, in name order
+    pub fn actions(&self) -> Vec<&str> {
+        let mut actions: Vec<&str> = self
+            .defaults
+            .keys()
+            .chain(self.overrides.keys())
+            .map(|action| action.as_str())
+            .collect();
+        actions.sort_unstable();
+        actions.dedup();
+        actions
+    }
+
+    /// The actions other than `action` which `binding` would also trigger;
+    /// options menus use this to warn before a player makes the change
+    pub fn actions_using(&self, binding: InputBinding, except: &str) -> Vec<String> {
+        self.actions()
+            .into_iter()
+            .filter(|other| *other != except && self.get_bindings(other).contains(&binding))
+            .map(|other| other.to_string())
+            .collect()
+    }
+
+    pub fn conflicts(&self) -> Vec<BindingConflict> {
+        let mut users: BTreeMap<InputBinding, Vec<String>> = BTreeMap::new();
+        for action in self.actions() {
+            for binding in self.get_bindings(action) {
+                users.entry(*binding).or_default().push(action.to_string());
+            }
+        }
+        users
+            .into_iter()
+            .filter(|(_, actions)| actions.len() > 1)
+            .map(|(binding, actions)| BindingConflict { binding, actions })
+            .collect()
+    }
+
+    /// "Space / Pad A", or "Unbound"
+    pub fn display_name(&self, action: &str) -> String {
+        let bindings = self.get_bindings(action);
+        if bindings.is_empty() {
+            return "Unbound".to_string();
+        }
+        bindings
+            .iter()
+            .map(|binding| binding.display_name())
+            .collect::<Vec<_>>()
+            .join(" / ")
+    }
+
+    /// The player's overrides, in the bindings file format
+    pub fn overrides_to_text(&self) -> String {
+        let mut text = String::new();
+        for (action, bindings) in self.overrides.iter() {
+            let bindings: Vec<String> = bindings.iter().map(|b| b.to_string()).collect();
+            text.push_str(&format!("{} = {}\n", action, bindings.join(", ")));
+        }
+        text
+    }
+
+    /// Replaces the player's overrides.  Lines which can't be read are skipped.
+    pub fn overrides_from_text(&mut self, text: &str) {
+        self.overrides.clear();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((action, bindings)) = line.split_once('=') else {
+                warn!("skipping bindings line {}: no '='", number + 1);
+                continue;
+            };
+            let bindings: Vec<InputBinding> = bindings
+                .split(',')
+                .filter(|id| !id.trim().is_empty())
+                .filter_map(|id| {
+                    let binding = InputBinding::from_id(id);
+                    if binding.is_none() {
+                        warn!(
+                            "skipping unknown binding {} on line {}",
+                            id.trim(),
+                            number + 1
+                        );
+                    }
+                    binding
+                })
+                .collect();
+            self.overrides.insert(action.trim().to_string(), bindings);
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), VSError> {
+        fs::write(path, self.overrides_to_text()).map_err(|e| {
+            error!("unable to write bindings to {}: {}", path.display(), e);
+            VSError::Input_BindingsWriteFailed
+        })?;
+        info!("Saved bindings to {}", path.display());
+        Ok(())
+    }
+
+    /// A missing file is fine; it means the player hasn't changed anything
+    pub fn load(&mut self, path: &Path) -> Result<(), VSError> {
+        if !path.exists() {
+            self.overrides.clear();
+            return Ok(());
+        }
+        let text = fs::read_to_string(path).map_err(|e| {
+            error!("unable to read bindings from {}: {}", path.display(), e);
+            VSError::Input_BindingsReadFailed
+        })?;
+        self.overrides_from_text(&text);
+        info!("Loaded bindings from {}", path.display());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overrides_round_trip() {
+        let mut bindings = Bindings::new();
+        bindings.set_default("fire", &[InputBinding::Key(Scancode::Space)]);
+        bindings.set_default(
+            "left",
+            &[
+                InputBinding::Key(Scancode::A),
+                InputBinding::GamepadAxis(GamepadAxis::LeftX, AxisDirection::Negative),
+            ],
+        );
+        assert!(bindings.conflicts().is_empty());
+
+        bindings.bind(
+            "fire",
+            &[
+                InputBinding::Key(Scancode::A),
+                InputBinding::GamepadButton(GamepadButton::A),
+            ],
+        );
+        assert_eq!(
+            bindings.actions_using(InputBinding::Key(Scancode::A), "fire"),
+            vec!["left".to_string()]
+        );
+        assert_eq!(bindings.conflicts().len(), 1);
+        assert_eq!(bindings.display_name("left"), "A / Left Stick X-");
+
+        let text = bindings.overrides_to_text();
+        assert_eq!(text, "fire = Key:A, GamepadButton:A\n");
+
+        let mut loaded = Bindings::new();
+        loaded.set_default("fire", &[InputBinding::Key(Scancode::Space)]);
+        loaded.overrides_from_text(&format!("{}left = Wheel:+, Nonsense:1\n", text));
+        assert_eq!(loaded.get_bindings("fire"), bindings.get_bindings("fire"));
+        assert_eq!(
+            loaded.get_bindings("left"),
+            &[InputBinding::Wheel(AxisDirection::Positive)]
+        );
+        loaded.reset("fire");
+        assert_eq!(loaded.display_name("fire"), "Space");
+    }
+}
@@ -0,0 +1,55 @@
+/// What an input context keeps from the contexts beneath it on the stack
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Consumes {
+    /// Everything passes through, e.g. a heads-up display
+    #[default]
+    Nothing,
+    /// Just these actions, e.g. a pause menu taking "fire" and "pause"
+    Actions(Vec<String>),
+    /// Every action, e.g. a text field or a console
+    All,
+}
+
+/// A named layer of input handling, such as "gameplay", "pause_menu" or
+/// "console".  Contexts are pushed onto the `InputSystem`'s stack as they
+/// open; actions a context consumes no longer reach the contexts below it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputContext {
+    name: String,
+    consumes: Consumes,
+}
+
+impl InputContext {
+    pub fn new(name: &str) -> Self {
+        InputContext {
+            name: name.to_string(),
+            consumes: Consumes::Nothing,
+        }
+    }
+
+    pub fn consuming(mut self, actions: &[&str]) -> Self {
+        self.consumes = Consumes::Actions(actions.iter().map(|a| a.to_string()).collect());
+        self
+    }
+
+    pub fn consuming_all(mut self) -> Self {
+        self.consumes = Consumes::All;
+        self
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_consumes(&self) -> &Consumes {
+        &self.consumes
+    }
+
+    pub fn consumes(&self, action: &str) -> bool {
+        match &self.consumes {
+            Consumes::Nothing => false,
+            Consumes::Actions(actions) => actions.iter().any(|a| a == action),
+            Consumes::All => true,
+        }
+    }
+}
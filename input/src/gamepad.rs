@@ -0,0 +1,299 @@
+use std::collections::{HashMap, HashSet};
+
+use common::{
+    Vector2D,
+    input::{GamepadAxis, GamepadButton, GamepadId},
+};
+
+/// How a stick's dead zone is measured.  Axial treats each axis separately,
+/// which snaps to the cardinal directions; radial uses the stick's distance
+/// from centre, which keeps diagonals smooth.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DeadZoneShape {
+    Axial,
+    #[default]
+    Radial,
+}
+
+/// Maps a 0..1 deflection, after the dead zone, to a 0..1 output
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ResponseCurve {
+    #[default]
+    Linear,
+    Quadratic,
+    Cubic,
+    Power(f32),
+}
+
+impl ResponseCurve {
+    pub fn apply(self, value: f32) -> f32 {
+        let value = value.clamp(0.0, 1.0);
+        match self {
+            ResponseCurve::Linear => value,
+            ResponseCurve::Quadratic => value * value,
+            ResponseCurve::Cubic => value * value * value,
+            ResponseCurve::Power(exponent) => value.powf(exponent.max(0.0)),
+        }
+    }
+}
+
+/// Anything below `inner` reads as zero and anything past `outer` as full
+/// deflection, so worn sticks neither drift nor fall short of the edge.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StickSettings {
+    pub shape: DeadZoneShape,
+    pub inner: f32,
+    pub outer: f32,
+    pub curve: ResponseCurve,
+}
+
+impl Default for StickSettings {
+    fn default() -> Self {
+        StickSettings {
+            shape: DeadZoneShape::default(),
+            inner: 0.15,
+            outer: 0.95,
+            curve: ResponseCurve::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TriggerSettings {
+    pub inner: f32,
+    pub outer: f32,
+    pub curve: ResponseCurve,
+}
+
+impl Default for TriggerSettings {
+    fn default() -> Self {
+        TriggerSettings {
+            inner: 0.05,
+            outer: 0.95,
+            curve: ResponseCurve::default(),
+        }
+    }
+}
+
+fn rescale(value: f32, inner: f32, outer: f32, curve: ResponseCurve) -> f32 {
+    if value <= inner {
+        return 0.0;
+    }
+    let range = (outer - inner).max(f32::EPSILON);
+    curve.apply((value - inner) / range)
+}
+
+pub fn apply_stick(raw: Vector2D, settings: &StickSettings) -> Vector2D {
+    match settings.shape {
+        DeadZoneShape::Axial => {
+            let axis = |v: f32| {
+                v.signum() * rescale(v.abs(), settings.inner, settings.outer, settings.curve)
+            };
+            Vector2D::new(axis(raw.x), axis(raw.y))
+        }
+        DeadZoneShape::Radial => {
+            let length = raw.length();
+            let scaled = rescale(length, settings.inner, settings.outer, settings.curve);
+            if scaled == 0.0 {
+                Vector2D::ZERO
+            } else {
+                raw / length * scaled
+            }
+        }
+    }
+}
+
+pub fn apply_trigger(raw: f32, settings: &TriggerSettings) -> f32 {
+    rescale(raw, settings.inner, settings.outer, settings.curve)
+}
+
+/// One connected controller, and the player slot it fills, if any
+#[derive(Debug, Clone)]
+pub struct Gamepad {
+    id: GamepadId,
+    name: String,
+    player: Option<usize>,
+
+    down: HashSet<GamepadButton>,
+    pressed: HashSet<GamepadButton>,
+    released: HashSet<GamepadButton>,
+    axes: HashMap<GamepadAxis, f32>, // raw
+    previous_axes: HashMap<GamepadAxis, f32>,
+
+    stick_settings: StickSettings,
+    trigger_settings: TriggerSettings,
+}
+
+impl Gamepad {
+    pub fn new(id: GamepadId, name: &str) -> Self {
+        Gamepad {
+            id,
+            name: name.to_string(),
+            player: None,
+            down: HashSet::new(),
+            pressed: HashSet::new(),
+            released: HashSet::new(),
+            axes: HashMap::new(),
+            previous_axes: HashMap::new(),
+            stick_settings: StickSettings::default(),
+            trigger_settings: TriggerSettings::default(),
+        }
+    }
+
+    pub fn get_id(&self) -> GamepadId {
+        self.id
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// The player slot, counting from 0
+    pub fn get_player(&self) -> Option<usize> {
+        self.player
+    }
+
+    pub(crate) fn set_player(&mut self, player: Option<usize>) {
+        self.player = player;
+    }
+
+    pub fn get_stick_settings(&self) -> StickSettings {
+        self.stick_settings
+    }
+
+    pub fn set_stick_settings(&mut self, settings: StickSettings) {
+        self.stick_settings = settings;
+    }
+
+    pub fn get_trigger_settings(&self) -> TriggerSettings {
+        self.trigger_settings
+    }
+
+    pub fn set_trigger_settings(&mut self, settings: TriggerSettings) {
+        self.trigger_settings = settings;
+    }
+
+    pub(crate) fn begin_frame(&mut self) {
+        self.pressed.clear();
+        self.released.clear();
+        self.previous_axes.clone_from(&self.axes);
+    }
+
+    pub(crate) fn button_down(&mut self, button: GamepadButton) {
+        if self.down.insert(button) {
+            self.pressed.insert(button);
+        }
+    }
+
+    pub(crate) fn button_up(&mut self, button: GamepadButton) {
+        if self.down.remove(&button) {
+            self.released.insert(button);
+        }
+    }
+
+    pub(crate) fn set_axis(&mut self, axis: GamepadAxis, value: f32) {
+        self.axes.insert(axis, value);
+    }
+
+    pub(crate) fn release_all(&mut self) {
+        self.released.extend(self.down.drain());
+        self.axes.clear();
+    }
+
+    pub fn is_button_down(&self, button: GamepadButton) -> bool {
+        self.down.contains(&button)
+    }
+
+    pub fn was_button_pressed(&self, button: GamepadButton) -> bool {
+        self.pressed.contains(&button)
+    }
+
+    pub fn was_button_released(&self, button: GamepadButton) -> bool {
+        self.released.contains(&button)
+    }
+
+    /// As reported by the controller, with no dead zone
+    pub fn get_raw_axis(&self, axis: GamepadAxis) -> f32 {
+        self.axes.get(&axis).copied().unwrap_or(0.0)
+    }
+
+    /// With the dead zone and response curve applied
+    pub fn get_axis(&self, axis: GamepadAxis) -> f32 {
+        self.processed_axis(&self.axes, axis)
+    }
+
+    /// What `get_axis` returned last frame
+    pub fn get_previous_axis(&self, axis: GamepadAxis) -> f32 {
+        self.processed_axis(&self.previous_axes, axis)
+    }
+
+    pub fn get_left_stick(&self) -> Vector2D {
+        self.stick(&self.axes, GamepadAxis::LeftX, GamepadAxis::LeftY)
+    }
+
+    pub fn get_right_stick(&self) -> Vector2D {
+        self.stick(&self.axes, GamepadAxis::RightX, GamepadAxis::RightY)
+    }
+
+    pub fn get_left_trigger(&self) -> f32 {
+        self.get_axis(GamepadAxis::TriggerLeft)
+    }
+
+    pub fn get_right_trigger(&self) -> f32 {
+        self.get_axis(GamepadAxis::TriggerRight)
+    }
+
+    fn processed_axis(&self, axes: &HashMap<GamepadAxis, f32>, axis: GamepadAxis) -> f32 {
+        match axis {
+            GamepadAxis::LeftX => self.stick(axes, axis, GamepadAxis::LeftY).x,
+            GamepadAxis::LeftY => self.stick(axes, GamepadAxis::LeftX, axis).y,
+            GamepadAxis::RightX => self.stick(axes, axis, GamepadAxis::RightY).x,
+            GamepadAxis::RightY => self.stick(axes, GamepadAxis::RightX, axis).y,
+            GamepadAxis::TriggerLeft | GamepadAxis::TriggerRight => apply_trigger(
+                axes.get(&axis).copied().unwrap_or(0.0),
+                &self.trigger_settings,
+            ),
+        }
+    }
+
+    fn stick(&self, axes: &HashMap<GamepadAxis, f32>, x: GamepadAxis, y: GamepadAxis) -> Vector2D {
+        let raw = |axis| axes.get(&axis).copied().unwrap_or(0.0);
+        apply_stick(Vector2D::new(raw(x), raw(y)), &self.stick_settings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dead_zones() {
+        let settings = StickSettings {
+            inner: 0.2,
+            outer: 0.8,
+            ..Default::default()
+        };
+        assert_eq!(
+            apply_stick(Vector2D::new(0.1, 0.1), &settings),
+            Vector2D::ZERO
+        );
+
+        // radial keeps the direction, axial drops the small axis
+        let diagonal = apply_stick(Vector2D::new(0.5, 0.15), &settings);
+        assert!(diagonal.y > 0.0);
+        let axial = StickSettings {
+            shape: DeadZoneShape::Axial,
+            ..settings
+        };
+        let snapped = apply_stick(Vector2D::new(0.5, 0.15), &axial);
+        assert_eq!(snapped, Vector2D::new(0.5, 0.0));
+
+        assert_eq!(apply_stick(Vector2D::new(-0.9, 0.0), &axial).x, -1.0);
+        let curved = TriggerSettings {
+            inner: 0.0,
+            outer: 1.0,
+            curve: ResponseCurve::Quadratic,
+        };
+        assert_eq!(apply_trigger(0.5, &curved), 0.25);
+    }
+}
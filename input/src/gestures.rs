@@ -0,0 +1,393 @@
+//! Turns raw touches into gestures.  Taps, double-taps, long presses and
+//! swipes are single-finger gestures, reported on the frame they complete;
+//! pinch and rotate are two-finger gestures, reported as a change on every
+//! frame the fingers move.  Distances are in window points, times in seconds.
+
+use common::{Vector2D, input::FingerId};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SwipeDirection {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+impl SwipeDirection {
+    fn from_movement(movement: Vector2D) -> Self {
+        if movement.x.abs() >= movement.y.abs() {
+            if movement.x < 0.0 {
+                SwipeDirection::Left
+            } else {
+                SwipeDirection::Right
+            }
+        } else if movement.y < 0.0 {
+            SwipeDirection::Up
+        } else {
+            SwipeDirection::Down
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Gesture {
+    Tap {
+        position: Vector2D,
+    },
+    /// Reported as well as the second tap
+    DoubleTap {
+        position: Vector2D,
+    },
+    LongPress {
+        position: Vector2D,
+    },
+    Swipe {
+        start: Vector2D,
+        end: Vector2D,
+        direction: SwipeDirection,
+    },
+    /// `scale` is this frame's change in the fingers' spread; above 1 is apart
+    Pinch {
+        center: Vector2D,
+        scale: f32,
+    },
+    /// `angle` is this frame's turn in radians; positive is clockwise on screen
+    Rotate {
+        center: Vector2D,
+        angle: f32,
+    },
+}
+
+/// Gestures as something to bind an action to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum GestureKind {
+    Tap,
+    DoubleTap,
+    LongPress,
+    Swipe(SwipeDirection),
+    PinchIn,
+    PinchOut,
+    RotateClockwise,
+    RotateAnticlockwise,
+}
+
+impl GestureKind {
+    pub const ALL: &[GestureKind] = &[
+        GestureKind::Tap,
+        GestureKind::DoubleTap,
+        GestureKind::LongPress,
+        GestureKind::Swipe(SwipeDirection::Left),
+        GestureKind::Swipe(SwipeDirection::Right),
+        GestureKind::Swipe(SwipeDirection::Up),
+        GestureKind::Swipe(SwipeDirection::Down),
+        GestureKind::PinchIn,
+        GestureKind::PinchOut,
+        GestureKind::RotateClockwise,
+        GestureKind::RotateAnticlockwise,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GestureKind::Tap => "Tap",
+            GestureKind::DoubleTap => "Double Tap",
+            GestureKind::LongPress => "Long Press",
+            GestureKind::Swipe(SwipeDirection::Left) => "Swipe Left",
+            GestureKind::Swipe(SwipeDirection::Right) => "Swipe Right",
+            GestureKind::Swipe(SwipeDirection::Up) => "Swipe Up",
+            GestureKind::Swipe(SwipeDirection::Down) => "Swipe Down",
+            GestureKind::PinchIn => "Pinch In",
+            GestureKind::PinchOut => "Pinch Out",
+            GestureKind::RotateClockwise => "Rotate Clockwise",
+            GestureKind::RotateAnticlockwise => "Rotate Anticlockwise",
+        }
+    }
+
+    /// Used when saving and loading
+    pub fn id(self) -> &'static str {
+        match self {
+            GestureKind::Tap => "Tap",
+            GestureKind::DoubleTap => "DoubleTap",
+            GestureKind::LongPress => "LongPress",
+            GestureKind::Swipe(SwipeDirection::Left) => "SwipeLeft",
+            GestureKind::Swipe(SwipeDirection::Right) => "SwipeRight",
+            GestureKind::Swipe(SwipeDirection::Up) => "SwipeUp",
+            GestureKind::Swipe(SwipeDirection::Down) => "SwipeDown",
+            GestureKind::PinchIn => "PinchIn",
+            GestureKind::PinchOut => "PinchOut",
+            GestureKind::RotateClockwise => "RotateClockwise",
+            GestureKind::RotateAnticlockwise => "RotateAnticlockwise",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        GestureKind::ALL
+            .iter()
+            .find(|kind| kind.id() == id)
+            .copied()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GestureSettings {
+    pub tap_max_time: f32,
+    pub tap_max_distance: f32,
+    pub double_tap_max_interval: f32,
+    pub long_press_time: f32,
+    pub swipe_min_distance: f32,
+    pub swipe_max_time: f32,
+}
+
+impl Default for GestureSettings {
+    fn default() -> Self {
+        GestureSettings {
+            tap_max_time: 0.25,
+            tap_max_distance: 12.0,
+            double_tap_max_interval: 0.3,
+            long_press_time: 0.5,
+            swipe_min_distance: 60.0,
+            swipe_max_time: 0.5,
+        }
+    }
+}
+
+/// A finger currently on the screen
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Touch {
+    pub finger: FingerId,
+    pub position: Vector2D,
+    pub start_position: Vector2D,
+    pub pressure: f32,
+    pub held_for: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TouchTracking {
+    touch: Touch,
+    moved: bool,       // beyond the tap distance
+    multi_touch: bool, // another finger joined, so this isn't a single-finger gesture
+    long_pressed: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct GestureRecognizer {
+    settings: GestureSettings,
+    touches: Vec<TouchTracking>, // in the order they went down
+    gestures: Vec<Gesture>,      // this frame's
+    time: f32,
+    last_tap: Option<(f32, Vector2D)>,
+}
+
+impl GestureRecognizer {
+    pub fn new() -> Self {
+        GestureRecognizer::default()
+    }
+
+    pub fn get_settings(&self) -> GestureSettings {
+        self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: GestureSettings) {
+        self.settings = settings;
+    }
+
+    pub fn begin_frame(&mut self) {
+        self.gestures.clear();
+    }
+
+    /// Moves time on, which is what makes a held finger a long press
+    pub fn advance(&mut self, time_step: f32) {
+        self.time += time_step;
+        for tracking in self.touches.iter_mut() {
+            tracking.touch.held_for += time_step;
+            if !tracking.long_pressed
+                && !tracking.moved
+                && !tracking.multi_touch
+                && tracking.touch.held_for >= self.settings.long_press_time
+            {
+                tracking.long_pressed = true;
+                self.gestures.push(Gesture::LongPress {
+                    position: tracking.touch.position,
+                });
+            }
+        }
+    }
+
+    pub fn touch_down(&mut self, finger: FingerId, position: Vector2D, pressure: f32) {
+        self.touches.retain(|t| t.touch.finger != finger);
+        let multi_touch = !self.touches.is_empty();
+        for other in self.touches.iter_mut() {
+            other.multi_touch = true;
+        }
+        self.touches.push(TouchTracking {
+            touch: Touch {
+                finger,
+                position,
+                start_position: position,
+                pressure,
+                held_for: 0.0,
+            },
+            moved: false,
+            multi_touch,
+            long_pressed: false,
+        });
+    }
+
+    pub fn touch_move(&mut self, finger: FingerId, position: Vector2D, pressure: f32) {
+        let before = self.pair();
+        let Some(tracking) = self.touches.iter_mut().find(|t| t.touch.finger == finger) else {
+            return;
+        };
+        tracking.touch.position = position;
+        tracking.touch.pressure = pressure;
+        if (position - tracking.touch.start_position).length() > self.settings.tap_max_distance {
+            tracking.moved = true;
+        }
+
+        // the first two fingers down pinch and rotate
+        if let (Some((a, b)), Some((c, d))) = (before, self.pair()) {
+            let (old, new) = (b - a, d - c);
+            let center = (c + d) * 0.5;
+            if old.length() > 0.0 && new.length() > 0.0 {
+                let scale = new.length() / old.length();
+                if scale != 1.0 {
+                    self.gestures.push(Gesture::Pinch { center, scale });
+                }
+                let angle = old.angle_to(new);
+                if angle != 0.0 {
+                    self.gestures.push(Gesture::Rotate { center, angle });
+                }
+            }
+        }
+    }
+
+    pub fn touch_up(&mut self, finger: FingerId, position: Vector2D) {
+        let Some(index) = self.touches.iter().position(|t| t.touch.finger == finger) else {
+            return;
+        };
+        let tracking = self.touches.remove(index);
+        if tracking.multi_touch || tracking.long_pressed {
+            return;
+        }
+
+        let touch = tracking.touch;
+        let movement = position - touch.start_position;
+        if !tracking.moved && touch.held_for <= self.settings.tap_max_time {
+            self.gestures.push(Gesture::Tap { position });
+            let double = self.last_tap.is_some_and(|(time, last)| {
+                self.time - time <= self.settings.double_tap_max_interval
+                    && (position - last).length() <= self.settings.tap_max_distance
+            });
+            if double {
+                self.gestures.push(Gesture::DoubleTap { position });
+                self.last_tap = None;
+            } else {
+                self.last_tap = Some((self.time, position));
+            }
+        } else if movement.length() >= self.settings.swipe_min_distance
+            && touch.held_for <= self.settings.swipe_max_time
+        {
+            self.gestures.push(Gesture::Swipe {
+                start: touch.start_position,
+                end: position,
+                direction: SwipeDirection::from_movement(movement),
+            });
+        }
+    }
+
+    /// Lets go of every finger without recognising anything, e.g. when the window loses focus
+    pub fn cancel(&mut self) {
+        self.touches.clear();
+        self.last_tap = None;
+    }
+
+    pub fn touches(&self) -> impl Iterator<Item = &Touch> {
+        self.touches.iter().map(|t| &t.touch)
+    }
+
+    pub fn get_touch(&self, finger: FingerId) -> Option<&Touch> {
+        self.touches().find(|t| t.finger == finger)
+    }
+
+    pub fn gestures(&self) -> &[Gesture] {
+        &self.gestures
+    }
+
+    pub fn happened(&self, kind: GestureKind) -> bool {
+        self.get_value(kind) > 0.0
+    }
+
+    /// 1 for a gesture which completed this frame.  Pinches are how far the
+    /// spread grew or shrank as a fraction, and rotations are in radians.
+    pub fn get_value(&self, kind: GestureKind) -> f32 {
+        let mut value = 0.0;
+        for gesture in self.gestures.iter() {
+            value += match (kind, gesture) {
+                (GestureKind::Tap, Gesture::Tap { .. })
+                | (GestureKind::DoubleTap, Gesture::DoubleTap { .. })
+                | (GestureKind::LongPress, Gesture::LongPress { .. }) => 1.0,
+                (GestureKind::Swipe(want), Gesture::Swipe { direction, .. })
+                    if want == *direction =>
+                {
+                    1.0
+                }
+                (GestureKind::PinchOut, Gesture::Pinch { scale, .. }) => (scale - 1.0).max(0.0),
+                (GestureKind::PinchIn, Gesture::Pinch { scale, .. }) => (1.0 - scale).max(0.0),
+                (GestureKind::RotateClockwise, Gesture::Rotate { angle, .. }) => angle.max(0.0),
+                (GestureKind::RotateAnticlockwise, Gesture::Rotate { angle, .. }) => {
+                    (-angle).max(0.0)
+                }
+                _ => 0.0,
+            };
+        }
+        value
+    }
+
+    fn pair(&self) -> Option<(Vector2D, Vector2D)> {
+        match self.touches.as_slice() {
+            [a, b, ..] => Some((a.touch.position, b.touch.position)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn taps_swipes_and_pinches() {
+        let mut gestures = GestureRecognizer::new();
+        for _ in 0..2 {
+            gestures.touch_down(1, Vector2D::new(10.0, 10.0), 1.0);
+            gestures.advance(0.1);
+            gestures.touch_up(1, Vector2D::new(12.0, 10.0));
+        }
+        assert!(gestures.happened(GestureKind::Tap));
+        assert!(gestures.happened(GestureKind::DoubleTap));
+
+        gestures.begin_frame();
+        gestures.touch_down(2, Vector2D::new(100.0, 100.0), 1.0);
+        gestures.touch_move(2, Vector2D::new(100.0, 20.0), 1.0);
+        gestures.advance(0.2);
+        gestures.touch_up(2, Vector2D::new(100.0, 20.0));
+        assert_eq!(
+            gestures.gestures(),
+            &[Gesture::Swipe {
+                start: Vector2D::new(100.0, 100.0),
+                end: Vector2D::new(100.0, 20.0),
+                direction: SwipeDirection::Up,
+            }]
+        );
+
+        gestures.begin_frame();
+        gestures.touch_down(3, Vector2D::new(0.0, 0.0), 1.0);
+        gestures.touch_down(4, Vector2D::new(10.0, 0.0), 1.0);
+        gestures.touch_move(4, Vector2D::new(0.0, 20.0), 1.0);
+        assert_eq!(gestures.get_value(GestureKind::PinchOut), 1.0);
+        assert!(gestures.get_value(GestureKind::RotateClockwise) > 1.5);
+        gestures.advance(1.0);
+        gestures.touch_up(3, Vector2D::new(0.0, 0.0));
+        assert!(!gestures.happened(GestureKind::LongPress));
+        assert!(!gestures.happened(GestureKind::Tap));
+    }
+}
@@ -1,6 +1,1440 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
 
+use common::{
+    Affine2D, Spring2D, Transform2D, Vector2D,
+    input::{
+        FingerId, GamepadAxis, GamepadButton, GamepadId, InputBackend, InputEvent, KeyModifiers,
+        Keycode, MouseButton, Scancode, WheelSource,
+    },
+    vs_error::VSError,
+    window::WindowSize,
+};
+use log::{info, warn};
 
-#[derive(Default)]
+use crate::{
+    bindings::{AxisDirection, Bindings, InputBinding},
+    context::InputContext,
+    gamepad::{Gamepad, StickSettings, TriggerSettings},
+    gestures::{Gesture, GestureRecognizer, GestureSettings, Touch},
+    recording::{InputRecording, RecordedFrame},
+};
+
+const DEFAULT_MAX_PLAYERS: usize = 4;
+const WHEEL_SPRING_STIFFNESS: f32 = 225.0;
+const WHEEL_SPRING_MAX_STEP: f32 = 1.0 / 120.0;
+const WHEEL_SPRING_SETTLED: f32 = 0.001;
+// how far an axis has to move before it counts as a press
+const AXIS_PRESS_THRESHOLD: f32 = 0.5;
+
+// critically damped, so a notch glides to a stop without bouncing back
+fn wheel_spring() -> Spring2D {
+    Spring2D::new(
+        Vector2D::splat(WHEEL_SPRING_STIFFNESS),
+        2.0 * WHEEL_SPRING_STIFFNESS.sqrt(),
+    )
+}
+
+/// How raw scrolling becomes the scroll axis.  The scales are multipliers,
+/// so a mouse wheel scale of 1 scrolls one unit per notch.  Smoothing spreads
+/// each wheel notch over a few frames; trackpads are smooth already.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WheelSettings {
+    pub smoothing: bool,
+    pub mouse_scale: f32,
+    pub trackpad_scale: f32,
+}
+
+impl Default for WheelSettings {
+    fn default() -> Self {
+        WheelSettings {
+            smoothing: true,
+            mouse_scale: 1.0,
+            trackpad_scale: 0.1,
+        }
+    }
+}
+
+/// Text an IME is still composing, before it is committed
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Composition {
+    pub text: String,
+    pub cursor: i32,
+    pub selection: i32,
+}
+
+#[derive(Debug, Default)]
+struct KeyboardState {
+    down: HashSet<Scancode>,
+    pressed: HashSet<Scancode>,
+    released: HashSet<Scancode>,
+    keycodes_down: HashSet<Keycode>,
+    keycodes_pressed: HashSet<Keycode>,
+    modifiers: KeyModifiers,
+}
+
+#[derive(Debug, Default)]
+struct MouseState {
+    position: Vector2D, // window points
+    delta: Vector2D,
+    down: HashSet<MouseButton>,
+    pressed: HashSet<MouseButton>,
+    released: HashSet<MouseButton>,
+    wheel: Vector2D,        // raw, this frame
+    scroll: Vector2D,       // scaled and smoothed, this frame
+    wheel_spring: Spring2D, // center is where the wheel has been scrolled to
+}
+
+enum Playback {
+    Live,
+    Recording(InputRecording),
+    Replaying {
+        recording: InputRecording,
+        next_frame: usize,
+    },
+}
+
+/// Collects the backend's input events once per frame, and keeps the state of
+/// the keyboard and mouse for games to query.  "Pressed" and "released" hold
+/// for the single frame on which the change happened.
 pub struct InputSystem {
+    active: bool,
+    source: Option<Box<dyn InputBackend>>,
+    events: Vec<InputEvent>, // this frame's
+    frame: u64,
+    playback: Playback,
+
+    keyboard: KeyboardState,
+    mouse: MouseState,
+    wheel_settings: WheelSettings,
+
+    gestures: GestureRecognizer,
+
+    gamepads: Vec<Gamepad>,          // in connection order
+    players: Vec<Option<GamepadId>>, // one per player slot
+    stick_settings: StickSettings,   // for newly connected gamepads
+    trigger_settings: TriggerSettings,
+
+    bindings: Bindings,
+    bindings_path: Option<PathBuf>,
+    listening_for_binding: bool,
+    captured_binding: Option<InputBinding>,
+    contexts: Vec<InputContext>,       // the last is on top
+    suppressed: HashSet<InputBinding>, // ignored until released
+
+    text_input_active: bool,
+    text: String, // committed this frame
+    composition: Option<Composition>,
+
+    window_to_screen: Affine2D,
+    world_transform: Transform2D,
+
+    quit_requested: bool,
+    resized: Option<WindowSize>,
+    focused: bool,
+}
+
+impl Default for InputSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InputSystem {
+    pub fn new() -> Self {
+        InputSystem {
+            active: true,
+            source: None,
+            events: Vec::new(),
+            frame: 0,
+            playback: Playback::Live,
+            keyboard: KeyboardState::default(),
+            mouse: MouseState {
+                wheel_spring: wheel_spring(),
+                ..Default::default()
+            },
+            wheel_settings: WheelSettings::default(),
+            gestures: GestureRecognizer::new(),
+            gamepads: Vec::new(),
+            players: vec![None; DEFAULT_MAX_PLAYERS],
+            stick_settings: StickSettings::default(),
+            trigger_settings: TriggerSettings::default(),
+            bindings: Bindings::new(),
+            bindings_path: None,
+            listening_for_binding: false,
+            captured_binding: None,
+            contexts: Vec::new(),
+            suppressed: HashSet::new(),
+            text_input_active: false,
+            text: String::new(),
+            composition: None,
+            window_to_screen: Affine2D::IDENTITY,
+            world_transform: Transform2D::IDENTITY,
+            quit_requested: false,
+            resized: None,
+            focused: true,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    pub fn set_active(&mut self, active: bool) {
+        self.active = active;
+    }
+
+    /// Attaches the backend's event source; until then no events arrive
+    pub fn set_source(&mut self, source: Box<dyn InputBackend>) {
+        info!("Input source attached");
+        self.source = Some(source);
+    }
+
+    pub fn has_source(&self) -> bool {
+        self.source.is_some()
+    }
+
+    /// Starts a new frame: clears last frame's changes and handles everything
+    /// the backend has reported since.
+    /// While a recording is replaying, its events and time steps are used in
+    /// place of the backend's, except for window events and requests to quit,
+    /// which still come from the backend.
+    pub fn update(&mut self, time_step: f32) {
+        self.begin_frame();
+        self.frame += 1;
+        let live_events = match &mut self.source {
+            Some(source) => source.poll_events(),
+            None => Vec::new(),
+        };
+
+        let (events, time_step) = match &mut self.playback {
+            Playback::Live => (live_events, time_step),
+            Playback::Recording(recording) => {
+                // handle_event records the events into it
+                recording.frames.push(RecordedFrame {
+                    time_step,
+                    events: Vec::new(),
+                });
+                (live_events, time_step)
+            }
+            Playback::Replaying {
+                recording,
+                next_frame,
+            } => match recording.frames.get(*next_frame) {
+                Some(frame) => {
+                    *next_frame += 1;
+                    let mut events: Vec<InputEvent> = frame
+                        .events
+                        .iter()
+                        .filter(|e| !is_window_event(e))
+                        .cloned()
+                        .collect();
+                    events.extend(live_events.into_iter().filter(is_window_event));
+                    (events, frame.time_step)
+                }
+                None => {
+                    info!("Input playback finished after {} frames", next_frame);
+                    self.playback = Playback::Live;
+                    (live_events, time_step)
+                }
+            },
+        };
+
+        for event in events {
+            self.handle_event(event);
+        }
+        self.update_wheel_smoothing(time_step);
+        self.gestures.advance(time_step);
+        self.release_suppressed();
+    }
+
+    /// Frames since the system started
+    pub fn get_frame(&self) -> u64 {
+        self.frame
+    }
+
+    // ---- recording and playback
+
+    /// Records every event from the next frame on.  `seed` is stored with the
+    /// recording; the caller should reseed its `Random` with it.
+    pub fn start_recording(&mut self, seed: u64) {
+        info!("Input recording started on frame {}", self.frame);
+        self.playback = Playback::Recording(InputRecording::new(seed));
+    }
+
+    pub fn stop_recording(&mut self) -> Option<InputRecording> {
+        match std::mem::replace(&mut self.playback, Playback::Live) {
+            Playback::Recording(recording) => {
+                info!(
+                    "Input recording stopped after {} frames",
+                    recording.frame_count()
+                );
+                Some(recording)
+            }
+            other => {
+                self.playback = other;
+                None
+            }
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        matches!(self.playback, Playback::Recording(..))
+    }
+
+    /// Replays from the next frame on.  The caller should reseed its `Random`
+    /// with the recording's seed at the same time.  Anything held down now is
+    /// released first, so the replay starts from the same state as the recording.
+    pub fn start_playback(&mut self, recording: InputRecording) {
+        info!(
+            "Input playback started, {} frames with seed {}",
+            recording.frame_count(),
+            recording.seed
+        );
+        self.release_all();
+        self.playback = Playback::Replaying {
+            recording,
+            next_frame: 0,
+        };
+    }
+
+    pub fn stop_playback(&mut self) {
+        if self.is_playing_back() {
+            self.playback = Playback::Live;
+        }
+    }
+
+    /// False again once every recorded frame has been replayed
+    pub fn is_playing_back(&self) -> bool {
+        matches!(self.playback, Playback::Replaying { .. })
+    }
+
+    /// The time step of the frame being replayed, which should replace the measured one
+    pub fn get_playback_time_step(&self) -> Option<f32> {
+        match &self.playback {
+            Playback::Replaying {
+                recording,
+                next_frame,
+            } => recording
+                .frames
+                .get(next_frame.checked_sub(1)?)
+                .map(|frame| frame.time_step),
+            _ => None,
+        }
+    }
+
+    pub fn begin_frame(&mut self) {
+        self.events.clear();
+        self.keyboard.pressed.clear();
+        self.keyboard.released.clear();
+        self.keyboard.keycodes_pressed.clear();
+        self.mouse.pressed.clear();
+        self.mouse.released.clear();
+        self.mouse.delta = Vector2D::ZERO;
+        self.mouse.wheel = Vector2D::ZERO;
+        self.mouse.scroll = Vector2D::ZERO;
+        self.gestures.begin_frame();
+        for gamepad in self.gamepads.iter_mut() {
+            gamepad.begin_frame();
+        }
+        self.text.clear();
+    }
+
+    /// Events handled between updates are recorded as part of the last frame
+    pub fn handle_event(&mut self, event: InputEvent) {
+        if let Playback::Recording(recording) = &mut self.playback
+            && let Some(frame) = recording.frames.last_mut()
+        {
+            frame.events.push(event.clone());
+        }
+        if self.listening_for_binding {
+            self.capture_binding(&event);
+        }
+
+        match &event {
+            InputEvent::Quit => self.quit_requested = true,
+            InputEvent::WindowResized(size) => self.resized = Some(*size),
+            InputEvent::WindowFocus(focused) => {
+                self.focused = *focused;
+                if !focused {
+                    // we won't hear about anything released while we're in the background
+                    self.release_all();
+                }
+            }
+
+            InputEvent::KeyDown {
+                scancode,
+                keycode,
+                modifiers,
+                repeat,
+            } => {
+                self.keyboard.modifiers = *modifiers;
+                if !repeat && self.keyboard.down.insert(*scancode) {
+                    self.keyboard.pressed.insert(*scancode);
+                }
+                if !repeat && self.keyboard.keycodes_down.insert(*keycode) {
+                    self.keyboard.keycodes_pressed.insert(*keycode);
+                }
+            }
+            InputEvent::KeyUp {
+                scancode,
+                keycode,
+                modifiers,
+            } => {
+                self.keyboard.modifiers = *modifiers;
+                if self.keyboard.down.remove(scancode) {
+                    self.keyboard.released.insert(*scancode);
+                }
+                self.keyboard.keycodes_down.remove(keycode);
+            }
+            InputEvent::TextInput(text) => {
+                if self.text_input_active {
+                    self.text.push_str(text);
+                    self.composition = None;
+                }
+            }
+            InputEvent::TextEditing {
+                text,
+                cursor,
+                selection,
+            } => {
+                if self.text_input_active {
+                    self.composition = (!text.is_empty()).then(|| Composition {
+                        text: text.clone(),
+                        cursor: *cursor,
+                        selection: *selection,
+                    });
+                }
+            }
+
+            InputEvent::MouseMotion { position, delta } => {
+                self.mouse.position = *position;
+                self.mouse.delta += *delta;
+            }
+            InputEvent::MouseButtonDown {
+                button, position, ..
+            } => {
+                self.mouse.position = *position;
+                if self.mouse.down.insert(*button) {
+                    self.mouse.pressed.insert(*button);
+                }
+            }
+            InputEvent::MouseButtonUp { button, position } => {
+                self.mouse.position = *position;
+                if self.mouse.down.remove(button) {
+                    self.mouse.released.insert(*button);
+                }
+            }
+            InputEvent::MouseWheel { delta, source } => {
+                self.mouse.wheel += *delta;
+                match source {
+                    WheelSource::Wheel => {
+                        let scroll = *delta * self.wheel_settings.mouse_scale;
+                        if self.wheel_settings.smoothing {
+                            self.mouse.wheel_spring.center += scroll;
+                        } else {
+                            self.mouse.scroll += scroll;
+                        }
+                    }
+                    WheelSource::Trackpad => {
+                        self.mouse.scroll += *delta * self.wheel_settings.trackpad_scale
+                    }
+                }
+            }
+
+            InputEvent::TouchDown {
+                finger,
+                position,
+                pressure,
+            } => self.gestures.touch_down(*finger, *position, *pressure),
+            InputEvent::TouchMotion {
+                finger,
+                position,
+                pressure,
+                ..
+            } => self.gestures.touch_move(*finger, *position, *pressure),
+            InputEvent::TouchUp { finger, position } => self.gestures.touch_up(*finger, *position),
+
+            InputEvent::GamepadAdded { id, name } => self.add_gamepad(*id, name),
+            InputEvent::GamepadRemoved { id } => self.remove_gamepad(*id),
+            InputEvent::GamepadButtonDown { id, button } => {
+                if let Some(gamepad) = self.get_gamepad_mut(*id) {
+                    gamepad.button_down(*button);
+                }
+            }
+            InputEvent::GamepadButtonUp { id, button } => {
+                if let Some(gamepad) = self.get_gamepad_mut(*id) {
+                    gamepad.button_up(*button);
+                }
+            }
+            InputEvent::GamepadAxisMotion { id, axis, value } => {
+                if let Some(gamepad) = self.get_gamepad_mut(*id) {
+                    gamepad.set_axis(*axis, *value);
+                }
+            }
+        }
+        self.events.push(event);
+    }
+
+    /// Everything which arrived this frame, in order
+    pub fn events(&self) -> &[InputEvent] {
+        &self.events
+    }
+
+    // ---- actions
+
+    pub fn bindings(&self) -> &Bindings {
+        &self.bindings
+    }
+
+    /// For setting the game's defaults, and for options menus to change
+    pub fn bindings_mut(&mut self) -> &mut Bindings {
+        &mut self.bindings
+    }
+
+    /// Where the player's bindings are kept; they are loaded from it at once.
+    /// Set by the engine, next to its preferences.
+    pub fn set_bindings_file(&mut self, path: &Path) -> Result<(), VSError> {
+        self.bindings_path = Some(path.to_path_buf());
+        self.bindings.load(path)
+    }
+
+    pub fn save_bindings(&self) -> Result<(), VSError> {
+        match &self.bindings_path {
+            Some(path) => self.bindings.save(path),
+            None => {
+                warn!("no bindings file to save bindings to");
+                Ok(())
+            }
+        }
+    }
+
+    // Actions asked about without a context are asked about from beneath the
+    // whole stack, so anything any context consumes is hidden from them.
+
+    pub fn is_action_down(&self, action: &str) -> bool {
+        self.is_action_down_in(None, action)
+    }
+
+    /// True on the frame any of the action's bindings was pressed
+    pub fn was_action_pressed(&self, action: &str) -> bool {
+        self.was_action_pressed_in(None, action)
+    }
+
+    /// True on the frame the last of the action's bindings was let go
+    pub fn was_action_released(&self, action: &str) -> bool {
+        self.was_action_released_in(None, action)
+    }
+
+    /// From 0 to 1; the strongest of the action's bindings
+    pub fn get_action_value(&self, action: &str) -> f32 {
+        self.get_action_value_in(None, action)
+    }
+
+    /// From -1 to 1, for a pair of actions such as "left" and "right"
+    pub fn get_action_axis(&self, negative: &str, positive: &str) -> f32 {
+        self.get_action_value(positive) - self.get_action_value(negative)
+    }
+
+    /// As seen by the named context: contexts above it may have consumed the
+    /// action.  A context which isn't on the stack sees what `None` does.
+    pub fn is_action_down_in(&self, context: Option<&str>, action: &str) -> bool {
+        self.get_action_value_in(context, action) >= AXIS_PRESS_THRESHOLD
+    }
+
+    pub fn was_action_pressed_in(&self, context: Option<&str>, action: &str) -> bool {
+        self.action_bindings(context, action)
+            .iter()
+            .any(|binding| self.was_binding_pressed(*binding))
+    }
+
+    pub fn was_action_released_in(&self, context: Option<&str>, action: &str) -> bool {
+        !self.is_action_down_in(context, action)
+            && self
+                .action_bindings(context, action)
+                .iter()
+                .any(|binding| self.was_binding_released(*binding))
+    }
+
+    pub fn get_action_value_in(&self, context: Option<&str>, action: &str) -> f32 {
+        self.action_bindings(context, action)
+            .iter()
+            .map(|binding| self.get_binding_value(*binding))
+            .fold(0.0, f32::max)
+    }
+
+    fn action_bindings(&self, context: Option<&str>, action: &str) -> &[InputBinding] {
+        let above = context
+            .and_then(|name| self.contexts.iter().rposition(|c| c.get_name() == name))
+            .map_or(0, |index| index + 1);
+        if self.contexts[above..].iter().any(|c| c.consumes(action)) {
+            &[]
+        } else {
+            self.bindings.get_bindings(action)
+        }
+    }
+
+    // ---- contexts
+
+    pub fn push_context(&mut self, context: InputContext) {
+        self.contexts.push(context);
+    }
+
+    /// Removes the top context.  Anything it consumed which is still held stays
+    /// hidden until it's let go, so closing a menu with "fire" doesn't also fire.
+    pub fn pop_context(&mut self) -> Option<InputContext> {
+        let context = self.contexts.pop()?;
+        self.suppress_held(&context);
+        Some(context)
+    }
+
+    /// Removes the named context from wherever it is in the stack
+    pub fn remove_context(&mut self, name: &str) -> Option<InputContext> {
+        let index = self.contexts.iter().rposition(|c| c.get_name() == name)?;
+        let context = self.contexts.remove(index);
+        self.suppress_held(&context);
+        Some(context)
+    }
+
+    pub fn get_top_context(&self) -> Option<&InputContext> {
+        self.contexts.last()
+    }
+
+    pub fn has_context(&self, name: &str) -> bool {
+        self.contexts.iter().any(|c| c.get_name() == name)
+    }
+
+    pub fn contexts(&self) -> &[InputContext] {
+        &self.contexts
+    }
+
+    fn suppress_held(&mut self, context: &InputContext) {
+        let held: Vec<InputBinding> = self
+            .bindings
+            .actions()
+            .into_iter()
+            .filter(|action| context.consumes(action))
+            .flat_map(|action| self.bindings.get_bindings(action).iter().copied())
+            .filter(|binding| self.raw_binding_value(*binding) > 0.0)
+            .collect();
+        self.suppressed.extend(held);
+    }
+
+    fn release_suppressed(&mut self) {
+        let released: Vec<InputBinding> = self
+            .suppressed
+            .iter()
+            .copied()
+            .filter(|binding| self.raw_binding_value(*binding) == 0.0)
+            .collect();
+        for binding in released {
+            self.suppressed.remove(&binding);
+        }
+    }
+
+    // ---- bindings
+
+    pub fn get_binding_value(&self, binding: InputBinding) -> f32 {
+        if self.suppressed.contains(&binding) {
+            return 0.0;
+        }
+        self.raw_binding_value(binding)
+    }
+
+    fn raw_binding_value(&self, binding: InputBinding) -> f32 {
+        let pressed = |down: bool| if down { 1.0 } else { 0.0 };
+        match binding {
+            InputBinding::Key(scancode) => pressed(self.is_key_down(scancode)),
+            InputBinding::Mouse(button) => pressed(self.is_mouse_button_down(button)),
+            InputBinding::Wheel(direction) => (self.get_scroll().y * direction.sign()).max(0.0),
+            InputBinding::GamepadButton(button) => {
+                pressed(self.gamepads.iter().any(|g| g.is_button_down(button)))
+            }
+            InputBinding::GamepadAxis(axis, direction) => self
+                .gamepads
+                .iter()
+                .map(|g| (g.get_axis(axis) * direction.sign()).max(0.0))
+                .fold(0.0, f32::max),
+            InputBinding::Gesture(kind) => self.gestures.get_value(kind),
+        }
+    }
+
+    pub fn was_binding_pressed(&self, binding: InputBinding) -> bool {
+        if self.suppressed.contains(&binding) {
+            return false;
+        }
+        match binding {
+            InputBinding::Key(scancode) => self.was_key_pressed(scancode),
+            InputBinding::Mouse(button) => self.was_mouse_button_pressed(button),
+            InputBinding::Wheel(direction) => self.get_scroll().y * direction.sign() > 0.0,
+            InputBinding::GamepadButton(button) => {
+                self.gamepads.iter().any(|g| g.was_button_pressed(button))
+            }
+            InputBinding::GamepadAxis(axis, direction) => self.gamepads.iter().any(|g| {
+                g.get_previous_axis(axis) * direction.sign() < AXIS_PRESS_THRESHOLD
+                    && g.get_axis(axis) * direction.sign() >= AXIS_PRESS_THRESHOLD
+            }),
+            InputBinding::Gesture(kind) => self.gestures.happened(kind),
+        }
+    }
+
+    pub fn was_binding_released(&self, binding: InputBinding) -> bool {
+        if self.suppressed.contains(&binding) {
+            return false;
+        }
+        match binding {
+            InputBinding::Key(scancode) => self.was_key_released(scancode),
+            InputBinding::Mouse(button) => self.was_mouse_button_released(button),
+            InputBinding::Wheel(_) => false,
+            InputBinding::GamepadButton(button) => {
+                self.gamepads.iter().any(|g| g.was_button_released(button))
+            }
+            InputBinding::GamepadAxis(axis, direction) => self.gamepads.iter().any(|g| {
+                g.get_previous_axis(axis) * direction.sign() >= AXIS_PRESS_THRESHOLD
+                    && g.get_axis(axis) * direction.sign() < AXIS_PRESS_THRESHOLD
+            }),
+            InputBinding::Gesture(_) => false,
+        }
+    }
+
+    /// Waits for the player's next key, button, wheel or stick movement, for
+    /// an options menu to bind; collect it with `take_captured_binding`
+    pub fn listen_for_binding(&mut self) {
+        self.listening_for_binding = true;
+        self.captured_binding = None;
+    }
+
+    pub fn is_listening_for_binding(&self) -> bool {
+        self.listening_for_binding
+    }
+
+    pub fn cancel_listening_for_binding(&mut self) {
+        self.listening_for_binding = false;
+    }
+
+    pub fn take_captured_binding(&mut self) -> Option<InputBinding> {
+        self.captured_binding.take()
+    }
+
+    fn capture_binding(&mut self, event: &InputEvent) {
+        let direction = |value: f32| {
+            if value > 0.0 {
+                AxisDirection::Positive
+            } else {
+                AxisDirection::Negative
+            }
+        };
+        let binding = match event {
+            InputEvent::KeyDown {
+                scancode,
+                repeat: false,
+                ..
+            } if *scancode != Scancode::Unknown => Some(InputBinding::Key(*scancode)),
+            InputEvent::MouseButtonDown { button, .. } => Some(InputBinding::Mouse(*button)),
+            InputEvent::MouseWheel { delta, .. } if delta.y != 0.0 => {
+                Some(InputBinding::Wheel(direction(delta.y)))
+            }
+            InputEvent::GamepadButtonDown { button, .. } => {
+                Some(InputBinding::GamepadButton(*button))
+            }
+            InputEvent::GamepadAxisMotion { axis, value, .. }
+                if value.abs() >= AXIS_PRESS_THRESHOLD =>
+            {
+                Some(InputBinding::GamepadAxis(*axis, direction(*value)))
+            }
+            _ => None,
+        };
+        if binding.is_some() {
+            self.captured_binding = binding;
+            self.listening_for_binding = false;
+        }
+    }
+
+    // ---- keyboard
+
+    pub fn is_key_down(&self, scancode: Scancode) -> bool {
+        self.keyboard.down.contains(&scancode)
+    }
+
+    pub fn was_key_pressed(&self, scancode: Scancode) -> bool {
+        self.keyboard.pressed.contains(&scancode)
+    }
+
+    pub fn was_key_released(&self, scancode: Scancode) -> bool {
+        self.keyboard.released.contains(&scancode)
+    }
+
+    pub fn is_keycode_down(&self, keycode: Keycode) -> bool {
+        self.keyboard.keycodes_down.contains(&keycode)
+    }
+
+    pub fn was_keycode_pressed(&self, keycode: Keycode) -> bool {
+        self.keyboard.keycodes_pressed.contains(&keycode)
+    }
+
+    pub fn get_modifiers(&self) -> KeyModifiers {
+        self.keyboard.modifiers
+    }
+
+    // ---- text
+
+    /// Text entry events are only collected between start and stop.  Keys and
+    /// actions still update while typing, so a name entry screen should push
+    /// a context that consumes the gameplay actions, or skip its key checks
+    /// while `is_text_input_active`.
+    pub fn start_text_input(&mut self) {
+        self.text_input_active = true;
+        if let Some(source) = &mut self.source {
+            source.start_text_input();
+        }
+    }
+
+    pub fn stop_text_input(&mut self) {
+        self.text_input_active = false;
+        self.composition = None;
+        if let Some(source) = &mut self.source {
+            source.stop_text_input();
+        }
+    }
+
+    pub fn is_text_input_active(&self) -> bool {
+        self.text_input_active
+    }
+
+    /// Where the text field is, in window points, so the IME can place its window nearby
+    pub fn set_text_input_rect(&mut self, position: Vector2D, size: Vector2D) {
+        if let Some(source) = &mut self.source {
+            source.set_text_input_rect(position, size);
+        }
+    }
+
+    /// Text typed this frame
+    pub fn get_text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn get_composition(&self) -> Option<&Composition> {
+        self.composition.as_ref()
+    }
+
+    // ---- mouse
+
+    /// Maps window points to logical screen coordinates; set by the engine each frame
+    pub fn set_window_to_screen(&mut self, window_to_screen: Affine2D) {
+        self.window_to_screen = window_to_screen;
+    }
+
+    /// Maps world positions to logical screen coordinates, i.e. the game's camera
+    pub fn set_world_transform(&mut self, world_transform: Transform2D) {
+        self.world_transform = world_transform;
+    }
+
+    /// In window points
+    pub fn get_mouse_position(&self) -> Vector2D {
+        self.mouse.position
+    }
+
+    /// In logical screen coordinates
+    pub fn get_mouse_screen_position(&self) -> Vector2D {
+        self.window_to_screen.transform_point2(self.mouse.position)
+    }
+
+    pub fn get_mouse_world_position(&self) -> Vector2D {
+        self.world_transform
+            .inverse_apply_to(self.get_mouse_screen_position())
+    }
+
+    /// Movement this frame, in window points
+    pub fn get_mouse_delta(&self) -> Vector2D {
+        self.mouse.delta
+    }
+
+    /// As reported by the backend, before any scaling or smoothing
+    pub fn get_mouse_wheel(&self) -> Vector2D {
+        self.mouse.wheel
+    }
+
+    /// The scroll axis: this frame's wheel or trackpad scrolling, after scaling
+    /// and smoothing
+    pub fn get_scroll(&self) -> Vector2D {
+        self.mouse.scroll
+    }
+
+    pub fn get_wheel_settings(&self) -> WheelSettings {
+        self.wheel_settings
+    }
+
+    pub fn set_wheel_settings(&mut self, settings: WheelSettings) {
+        if !settings.smoothing {
+            // whatever was still gliding arrives at once
+            let spring = &mut self.mouse.wheel_spring;
+            self.mouse.scroll += spring.center - spring.position;
+            *spring = wheel_spring();
+        }
+        self.wheel_settings = settings;
+    }
+
+    pub fn is_mouse_button_down(&self, button: MouseButton) -> bool {
+        self.mouse.down.contains(&button)
+    }
+
+    pub fn was_mouse_button_pressed(&self, button: MouseButton) -> bool {
+        self.mouse.pressed.contains(&button)
+    }
+
+    pub fn was_mouse_button_released(&self, button: MouseButton) -> bool {
+        self.mouse.released.contains(&button)
+    }
+
+    // ---- touch
+
+    /// Every finger on the screen, in the order they went down
+    pub fn touches(&self) -> impl Iterator<Item = &Touch> {
+        self.gestures.touches()
+    }
+
+    pub fn get_touch(&self, finger: FingerId) -> Option<&Touch> {
+        self.gestures.get_touch(finger)
+    }
+
+    /// In logical screen coordinates
+    pub fn get_touch_screen_position(&self, finger: FingerId) -> Option<Vector2D> {
+        self.get_touch(finger)
+            .map(|touch| self.window_to_screen.transform_point2(touch.position))
+    }
+
+    /// Gestures recognised this frame, with positions in window points
+    pub fn gestures(&self) -> &[Gesture] {
+        self.gestures.gestures()
+    }
+
+    pub fn get_gesture_settings(&self) -> GestureSettings {
+        self.gestures.get_settings()
+    }
+
+    pub fn set_gesture_settings(&mut self, settings: GestureSettings) {
+        self.gestures.set_settings(settings);
+    }
+
+    // ---- gamepads
+
+    /// Every connected gamepad, in the order they were connected
+    pub fn gamepads(&self) -> &[Gamepad] {
+        &self.gamepads
+    }
+
+    pub fn get_gamepad(&self, id: GamepadId) -> Option<&Gamepad> {
+        self.gamepads.iter().find(|gamepad| gamepad.get_id() == id)
+    }
+
+    pub fn get_gamepad_mut(&mut self, id: GamepadId) -> Option<&mut Gamepad> {
+        self.gamepads
+            .iter_mut()
+            .find(|gamepad| gamepad.get_id() == id)
+    }
+
+    /// The gamepad in a player slot, counting from 0
+    pub fn get_player_gamepad(&self, player: usize) -> Option<&Gamepad> {
+        let id = (*self.players.get(player)?)?;
+        self.get_gamepad(id)
+    }
+
+    pub fn get_max_players(&self) -> usize {
+        self.players.len()
+    }
+
+    /// Gamepads in slots beyond the new maximum lose them, and any free
+    /// gamepads are given the slots which open up
+    pub fn set_max_players(&mut self, max_players: usize) {
+        for id in self.players.iter().skip(max_players).flatten() {
+            if let Some(gamepad) = self.gamepads.iter_mut().find(|g| g.get_id() == *id) {
+                gamepad.set_player(None);
+            }
+        }
+        self.players.resize(max_players, None);
+        self.fill_player_slots();
+    }
+
+    /// Moves a gamepad to a player slot, swapping with whichever gamepad was there
+    pub fn assign_player(&mut self, id: GamepadId, player: usize) {
+        if player >= self.players.len() || self.get_gamepad(id).is_none() {
+            warn!("unable to assign gamepad {} to player {}", id, player);
+            return;
+        }
+
+        let previous_slot = self.players.iter().position(|slot| *slot == Some(id));
+        let displaced = self.players[player];
+        self.players[player] = Some(id);
+        if let Some(previous_slot) = previous_slot {
+            self.players[previous_slot] = displaced.filter(|other| *other != id);
+        }
+        self.update_gamepad_players();
+    }
+
+    /// Used for every gamepad connected from now on, as well as those already connected
+    pub fn set_stick_settings(&mut self, settings: StickSettings) {
+        self.stick_settings = settings;
+        for gamepad in self.gamepads.iter_mut() {
+            gamepad.set_stick_settings(settings);
+        }
+    }
+
+    pub fn set_trigger_settings(&mut self, settings: TriggerSettings) {
+        self.trigger_settings = settings;
+        for gamepad in self.gamepads.iter_mut() {
+            gamepad.set_trigger_settings(settings);
+        }
+    }
+
+    pub fn is_gamepad_button_down(&self, player: usize, button: GamepadButton) -> bool {
+        self.get_player_gamepad(player)
+            .is_some_and(|gamepad| gamepad.is_button_down(button))
+    }
+
+    pub fn was_gamepad_button_pressed(&self, player: usize, button: GamepadButton) -> bool {
+        self.get_player_gamepad(player)
+            .is_some_and(|gamepad| gamepad.was_button_pressed(button))
+    }
+
+    pub fn get_gamepad_axis(&self, player: usize, axis: GamepadAxis) -> f32 {
+        self.get_player_gamepad(player)
+            .map_or(0.0, |gamepad| gamepad.get_axis(axis))
+    }
+
+    /// Strengths run from 0 to 1; gamepads without motors ignore it
+    pub fn rumble(&mut self, id: GamepadId, low_frequency: f32, high_frequency: f32, seconds: f32) {
+        if let Some(source) = &mut self.source {
+            source.set_rumble(id, low_frequency, high_frequency, seconds);
+        }
+    }
+
+    fn add_gamepad(&mut self, id: GamepadId, name: &str) {
+        if self.get_gamepad(id).is_some() {
+            return;
+        }
+        let mut gamepad = Gamepad::new(id, name);
+        gamepad.set_stick_settings(self.stick_settings);
+        gamepad.set_trigger_settings(self.trigger_settings);
+        self.gamepads.push(gamepad);
+        self.fill_player_slots();
+    }
+
+    // the freed slot goes to the first gamepad without one; a pad that
+    // reconnects comes back with a new id, so it waits its turn like any other
+    fn remove_gamepad(&mut self, id: GamepadId) {
+        self.gamepads.retain(|gamepad| gamepad.get_id() != id);
+        for slot in self.players.iter_mut() {
+            if *slot == Some(id) {
+                *slot = None;
+            }
+        }
+        self.fill_player_slots();
+    }
+
+    fn fill_player_slots(&mut self) {
+        for gamepad in self.gamepads.iter() {
+            let id = gamepad.get_id();
+            if self.players.contains(&Some(id)) {
+                continue;
+            }
+            if let Some(slot) = self.players.iter_mut().find(|slot| slot.is_none()) {
+                *slot = Some(id);
+            }
+        }
+        self.update_gamepad_players();
+    }
+
+    fn update_gamepad_players(&mut self) {
+        for gamepad in self.gamepads.iter_mut() {
+            let id = gamepad.get_id();
+            gamepad.set_player(self.players.iter().position(|slot| *slot == Some(id)));
+        }
+    }
+
+    // ---- window
+
+    /// True once, after the player asks to close the window
+    pub fn take_quit_request(&mut self) -> bool {
+        std::mem::take(&mut self.quit_requested)
+    }
+
+    pub fn take_resize(&mut self) -> Option<WindowSize> {
+        self.resized.take()
+    }
+
+    pub fn is_focused(&self) -> bool {
+        self.focused
+    }
+
+    // the spring glides towards everywhere the wheel has been scrolled to, and
+    // the scroll axis is how far it moved this frame
+    fn update_wheel_smoothing(&mut self, time_step: f32) {
+        let spring = &mut self.mouse.wheel_spring;
+        if spring.center == Vector2D::ZERO && spring.position == Vector2D::ZERO {
+            return;
+        }
+
+        let start = spring.position;
+        let steps = (time_step / WHEEL_SPRING_MAX_STEP).ceil().max(1.0);
+        for _ in 0..steps as u32 {
+            spring.update(time_step / steps);
+        }
+        self.mouse.scroll += spring.position - start;
+
+        // once settled, hand over the remainder and start again from zero
+        let remaining = spring.center - spring.position;
+        if remaining.length() < WHEEL_SPRING_SETTLED
+            && spring.velocity.length() < WHEEL_SPRING_SETTLED
+        {
+            self.mouse.scroll += remaining;
+            *spring = wheel_spring();
+        }
+    }
+
+    fn release_all(&mut self) {
+        self.keyboard.released.extend(self.keyboard.down.drain());
+        self.keyboard.keycodes_down.clear();
+        self.mouse.released.extend(self.mouse.down.drain());
+        for gamepad in self.gamepads.iter_mut() {
+            gamepad.release_all();
+        }
+        self.gestures.cancel();
+    }
+}
+
+// events about the window itself rather than the player's input
+fn is_window_event(event: &InputEvent) -> bool {
+    matches!(
+        event,
+        InputEvent::Quit | InputEvent::WindowResized(..) | InputEvent::WindowFocus(..)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+
+    use super::*;
+    use crate::gestures::GestureKind;
+
+    fn key_down(scancode: Scancode, repeat: bool) -> InputEvent {
+        InputEvent::KeyDown {
+            scancode,
+            keycode: Keycode::from_scancode(scancode),
+            modifiers: KeyModifiers::default(),
+            repeat,
+        }
+    }
+
+    #[test]
+    fn key_and_text_state() {
+        let mut input = InputSystem::new();
+        input.handle_event(key_down(Scancode::Space, false));
+        assert!(input.was_key_pressed(Scancode::Space));
+        assert!(input.is_keycode_down(Keycode::Space));
+
+        input.begin_frame();
+        input.handle_event(key_down(Scancode::Space, true));
+        assert!(input.is_key_down(Scancode::Space));
+        assert!(!input.was_key_pressed(Scancode::Space));
+
+        // text only arrives while text input is on
+        input.handle_event(InputEvent::TextInput("a".to_string()));
+        input.start_text_input();
+        input.handle_event(InputEvent::TextEditing {
+            text: "ni".to_string(),
+            cursor: 2,
+            selection: 0,
+        });
+        assert_eq!(input.get_composition().map(|c| c.cursor), Some(2));
+        input.handle_event(InputEvent::TextInput("你".to_string()));
+        assert_eq!(input.get_text(), "你");
+        assert!(input.get_composition().is_none());
+
+        input.handle_event(InputEvent::WindowFocus(false));
+        assert!(input.was_key_released(Scancode::Space));
+        assert!(!input.is_key_down(Scancode::Space));
+    }
+
+    #[test]
+    fn mouse_spaces() {
+        let mut input = InputSystem::new();
+        input.set_window_to_screen(Affine2D::from_scale(Vector2D::splat(0.5)));
+        input.set_world_transform(Transform2D::from_position(Vector2D::new(10.0, 0.0)));
+        input.handle_event(InputEvent::MouseMotion {
+            position: Vector2D::new(100.0, 40.0),
+            delta: Vector2D::new(3.0, -1.0),
+        });
+        assert_eq!(input.get_mouse_screen_position(), Vector2D::new(50.0, 20.0));
+        assert_eq!(input.get_mouse_world_position(), Vector2D::new(40.0, 20.0));
+        assert_eq!(input.get_mouse_delta(), Vector2D::new(3.0, -1.0));
+    }
+
+    #[test]
+    fn wheel_scaling_and_smoothing() {
+        let mut input = InputSystem::new();
+        input.set_wheel_settings(WheelSettings {
+            smoothing: true,
+            mouse_scale: 2.0,
+            trackpad_scale: 0.5,
+        });
+        input.handle_event(InputEvent::MouseWheel {
+            delta: Vector2D::new(0.0, 3.0),
+            source: WheelSource::Trackpad,
+        });
+        assert_eq!(input.get_scroll(), Vector2D::new(0.0, 1.5));
+
+        // a notch arrives spread across several frames, but all of it arrives
+        input.begin_frame();
+        input.handle_event(InputEvent::MouseWheel {
+            delta: Vector2D::new(0.0, 1.0),
+            source: WheelSource::Wheel,
+        });
+        input.update_wheel_smoothing(1.0 / 60.0);
+        let first = input.get_scroll().y;
+        assert!(first > 0.0 && first < 2.0);
+        let mut total = first;
+        for _ in 0..120 {
+            input.begin_frame();
+            input.update_wheel_smoothing(1.0 / 60.0);
+            total += input.get_scroll().y;
+        }
+        assert!((total - 2.0).abs() < 1e-4);
+        assert_eq!(input.mouse.wheel_spring, wheel_spring());
+    }
+
+    #[test]
+    fn actions_and_capture() {
+        let mut input = InputSystem::new();
+        input.bindings_mut().set_default(
+            "right",
+            &[
+                InputBinding::Key(Scancode::D),
+                InputBinding::GamepadAxis(GamepadAxis::LeftX, AxisDirection::Positive),
+            ],
+        );
+        input.handle_event(InputEvent::GamepadAdded {
+            id: 1,
+            name: "Pad".to_string(),
+        });
+        input.handle_event(InputEvent::GamepadAxisMotion {
+            id: 1,
+            axis: GamepadAxis::LeftX,
+            value: 1.0,
+        });
+        assert!(input.was_action_pressed("right"));
+        assert_eq!(input.get_action_axis("left", "right"), 1.0);
+
+        input.begin_frame();
+        input.handle_event(InputEvent::GamepadAxisMotion {
+            id: 1,
+            axis: GamepadAxis::LeftX,
+            value: 0.0,
+        });
+        assert!(input.was_action_released("right"));
+
+        input.listen_for_binding();
+        input.handle_event(key_down(Scancode::Unknown, false));
+        assert!(input.is_listening_for_binding());
+        input.handle_event(key_down(Scancode::F, false));
+        assert_eq!(
+            input.take_captured_binding(),
+            Some(InputBinding::Key(Scancode::F))
+        );
+        assert!(!input.is_listening_for_binding());
+    }
+
+    // hands out queued events, a frame's worth per poll
+    #[derive(Default)]
+    struct MockBackend {
+        frames: Rc<RefCell<VecDeque<Vec<InputEvent>>>>,
+    }
+
+    impl InputBackend for MockBackend {
+        fn poll_events(&mut self) -> Vec<InputEvent> {
+            self.frames.borrow_mut().pop_front().unwrap_or_default()
+        }
+        fn start_text_input(&mut self) {}
+        fn stop_text_input(&mut self) {}
+        fn set_text_input_rect(&mut self, _position: Vector2D, _size: Vector2D) {}
+        fn set_rumble(&mut self, _: GamepadId, _: f32, _: f32, _: f32) {}
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let backend = MockBackend::default();
+        let frames = backend.frames.clone();
+        let mut input = InputSystem::new();
+        input.set_source(Box::new(backend));
+        frames
+            .borrow_mut()
+            .extend([vec![], vec![key_down(Scancode::W, false)]]);
+
+        input.start_recording(7);
+        input.update(0.5);
+        input.handle_event(key_down(Scancode::A, false));
+        input.update(0.25);
+        assert!(input.was_key_pressed(Scancode::W));
+        let recording = input.stop_recording().expect("recording");
+        assert!(!input.is_recording());
+        assert_eq!(
+            recording.frames,
+            vec![
+                RecordedFrame {
+                    time_step: 0.5,
+                    events: vec![key_down(Scancode::A, false)],
+                },
+                RecordedFrame {
+                    time_step: 0.25,
+                    events: vec![key_down(Scancode::W, false)],
+                },
+            ]
+        );
+
+        let mut replay = InputSystem::new();
+        replay.start_playback(recording);
+        replay.update(1.0);
+        assert!(replay.was_key_pressed(Scancode::A));
+        assert_eq!(replay.get_playback_time_step(), Some(0.5));
+        replay.update(1.0);
+        assert!(replay.was_key_pressed(Scancode::W));
+        assert_eq!(replay.get_playback_time_step(), Some(0.25));
+        replay.update(1.0);
+        assert!(!replay.is_playing_back());
+        assert!(replay.is_key_down(Scancode::W));
+    }
+
+    #[test]
+    fn window_events_are_live_during_replay() {
+        let size = |width| WindowSize {
+            width,
+            height: 100,
+            width_pixels: width,
+            height_pixels: 100,
+        };
+        let recording = InputRecording {
+            frames: vec![RecordedFrame {
+                time_step: 0.5,
+                events: vec![
+                    InputEvent::WindowResized(size(200)),
+                    key_down(Scancode::A, false),
+                ],
+            }],
+            ..InputRecording::new(7)
+        };
+
+        let backend = MockBackend::default();
+        let frames = backend.frames.clone();
+        let mut replay = InputSystem::new();
+        replay.set_source(Box::new(backend));
+        frames.borrow_mut().push_back(vec![
+            InputEvent::WindowResized(size(300)),
+            key_down(Scancode::W, false),
+        ]);
+
+        replay.start_playback(recording);
+        replay.update(1.0);
+        assert_eq!(replay.take_resize(), Some(size(300)));
+        assert!(replay.was_key_pressed(Scancode::A));
+        assert!(!replay.is_key_down(Scancode::W));
+    }
+
+    #[test]
+    fn contexts_consume_actions() {
+        let mut input = InputSystem::new();
+        input
+            .bindings_mut()
+            .set_default("fire", &[InputBinding::Key(Scancode::Space)]);
+        input
+            .bindings_mut()
+            .set_default("jump", &[InputBinding::Key(Scancode::W)]);
+        input.push_context(InputContext::new("gameplay"));
+        input.push_context(InputContext::new("pause").consuming(&["fire"]));
+
+        input.handle_event(key_down(Scancode::Space, false));
+        input.handle_event(key_down(Scancode::W, false));
+        assert!(input.was_action_pressed_in(Some("pause"), "fire"));
+        assert!(!input.was_action_pressed_in(Some("gameplay"), "fire"));
+        assert!(input.is_action_down_in(Some("gameplay"), "jump"));
+
+        // closing the menu with fire still held mustn't fire
+        input.pop_context();
+        input.update(0.0);
+        assert!(!input.is_action_down("fire"));
+        input.handle_event(InputEvent::KeyUp {
+            scancode: Scancode::Space,
+            keycode: Keycode::Space,
+            modifiers: KeyModifiers::default(),
+        });
+        assert!(!input.was_action_released("fire"));
+        input.update(0.0);
+        input.handle_event(key_down(Scancode::Space, false));
+        assert!(input.was_action_pressed("fire"));
+    }
+
+    #[test]
+    fn gestures_drive_actions() {
+        let mut input = InputSystem::new();
+        input.bindings_mut().set_default(
+            "zoom_in",
+            &[
+                InputBinding::Wheel(AxisDirection::Positive),
+                InputBinding::Gesture(GestureKind::PinchOut),
+            ],
+        );
+        input.handle_event(InputEvent::TouchDown {
+            finger: 1,
+            position: Vector2D::new(0.0, 0.0),
+            pressure: 1.0,
+        });
+        input.handle_event(InputEvent::TouchDown {
+            finger: 2,
+            position: Vector2D::new(10.0, 0.0),
+            pressure: 0.5,
+        });
+        assert_eq!(input.touches().count(), 2);
+        input.handle_event(InputEvent::TouchMotion {
+            finger: 2,
+            position: Vector2D::new(15.0, 0.0),
+            delta: Vector2D::new(5.0, 0.0),
+            pressure: 0.5,
+        });
+        assert!(input.was_action_pressed("zoom_in"));
+        assert_eq!(input.get_action_value("zoom_in"), 0.5);
+
+        input.handle_event(InputEvent::WindowFocus(false));
+        assert_eq!(input.touches().count(), 0);
+    }
+
+    #[test]
+    fn gamepad_player_slots() {
+        let mut input = InputSystem::new();
+        input.set_max_players(2);
+        for id in [7, 8, 9] {
+            input.handle_event(InputEvent::GamepadAdded {
+                id,
+                name: "Pad".to_string(),
+            });
+        }
+        assert_eq!(input.get_player_gamepad(1).map(|g| g.get_id()), Some(8));
+        assert_eq!(input.get_gamepad(9).and_then(|g| g.get_player()), None);
+
+        // a disconnect hands the slot to the waiting gamepad
+        input.handle_event(InputEvent::GamepadRemoved { id: 7 });
+        assert_eq!(input.get_player_gamepad(0).map(|g| g.get_id()), Some(9));
+
+        input.assign_player(8, 0);
+        assert_eq!(input.get_player_gamepad(1).map(|g| g.get_id()), Some(9));
 
-}
\ No newline at end of file
+        input.handle_event(InputEvent::GamepadButtonDown {
+            id: 8,
+            button: GamepadButton::A,
+        });
+        assert!(input.was_gamepad_button_pressed(0, GamepadButton::A));
+        input.begin_frame();
+        assert!(input.is_gamepad_button_down(0, GamepadButton::A));
+        assert!(!input.was_gamepad_button_pressed(0, GamepadButton::A));
+    }
+}
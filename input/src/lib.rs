@@ -2,7 +2,12 @@ use std::{cell::RefCell, rc::Rc};
 
 use crate::input_system::InputSystem;
 
+pub mod bindings;
+pub mod context;
+pub mod gamepad;
+pub mod gestures;
 pub mod input_system;
+pub mod recording;
 
 pub type RefInputSystem = Rc<RefCell<InputSystem>>;
 
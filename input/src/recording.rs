@@ -0,0 +1,482 @@
+//! A recording is every input event of a session, frame by frame, along with
+//! each frame's time step and the seed the session's `Random` started from.
+//! Played back with the same game code, it reproduces the session exactly,
+//! without a window or a player.
+//!
+//! Recordings are saved as text, so that short scripted playthroughs can be
+//! written and edited by hand:
+//!
+//! ```text
+//! vs_input_recording 2
+//! seed 1234
+//! frame 0 0.016666668
+//! KeyDown Space Space - false
+//! frame 1 0.016666668
+//! KeyUp Space Space -
+//! ```
+//!
+//! Each frame is numbered, from 0, so a frame missing from an edited file is
+//! an error rather than every later event quietly moving a frame earlier.
+
+use std::{fs, path::Path};
+
+use common::{
+    Vector2D,
+    input::{
+        GamepadAxis, GamepadButton, InputEvent, KeyModifiers, Keycode, MouseButton, Scancode,
+        WheelSource,
+    },
+    vs_error::VSError,
+    window::{WindowExtent, WindowSize},
+};
+use log::{error, info, warn};
+
+const HEADER: &str = "vs_input_recording 2";
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RecordedFrame {
+    pub time_step: f32,
+    pub events: Vec<InputEvent>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct InputRecording {
+    pub seed: u64,
+    pub frames: Vec<RecordedFrame>,
+}
+
+impl InputRecording {
+    pub fn new(seed: u64) -> Self {
+        InputRecording {
+            seed,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{}\nseed {}\n", HEADER, self.seed);
+        for (index, frame) in self.frames.iter().enumerate() {
+            text.push_str(&format!("frame {} {}\n", index, frame.time_step));
+            for event in frame.events.iter() {
+                text.push_str(&event_to_text(event));
+                text.push('\n');
+            }
+        }
+        text
+    }
+
+    pub fn from_text(text: &str) -> Result<Self, VSError> {
+        let mut lines = text.lines().enumerate();
+        if lines.next().map(|(_, line)| line.trim()) != Some(HEADER) {
+            error!("input recording has no '{}' header", HEADER);
+            return Err(VSError::Input_RecordingParseFailed);
+        }
+
+        let mut recording = InputRecording::default();
+        for (number, line) in lines {
+            let line = line.trim();
+            let mut words = line.split_whitespace();
+            let parsed = match words.next() {
+                None => Some(()),
+                Some(word) if word.starts_with('#') => Some(()),
+                Some("seed") => words.next().and_then(|seed| seed.parse().ok()).map(|seed| {
+                    recording.seed = seed;
+                }),
+                Some("frame") => {
+                    let index = words.next().and_then(|index| index.parse::<usize>().ok());
+                    let time_step = words.next().and_then(|time_step| time_step.parse().ok());
+                    match (index, time_step) {
+                        (Some(index), Some(time_step)) if index == recording.frames.len() => {
+                            recording.frames.push(RecordedFrame {
+                                time_step,
+                                events: Vec::new(),
+                            });
+                            Some(())
+                        }
+                        (Some(index), Some(_)) => {
+                            error!(
+                                "input recording frame {} is out of order, expected frame {}",
+                                index,
+                                recording.frames.len()
+                            );
+                            None
+                        }
+                        _ => None,
+                    }
+                }
+                Some(_) => match (recording.frames.last_mut(), event_from_text(line)) {
+                    (Some(frame), Some(event)) => {
+                        frame.events.push(event);
+                        Some(())
+                    }
+                    _ => None,
+                },
+            };
+            if parsed.is_none() {
+                error!(
+                    "unable to read input recording line {}: {}",
+                    number + 1,
+                    line
+                );
+                return Err(VSError::Input_RecordingParseFailed);
+            }
+        }
+        Ok(recording)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), VSError> {
+        fs::write(path, self.to_text()).map_err(|e| {
+            error!(
+                "unable to write input recording to {}: {}",
+                path.display(),
+                e
+            );
+            VSError::Input_RecordingWriteFailed
+        })?;
+        info!(
+            "Saved input recording of {} frames to {}",
+            self.frames.len(),
+            path.display()
+        );
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, VSError> {
+        let text = fs::read_to_string(path).map_err(|e| {
+            error!(
+                "unable to read input recording from {}: {}",
+                path.display(),
+                e
+            );
+            VSError::Input_RecordingReadFailed
+        })?;
+        let recording = InputRecording::from_text(&text)?;
+        info!(
+            "Loaded input recording of {} frames from {}",
+            recording.frames.len(),
+            path.display()
+        );
+        Ok(recording)
+    }
+}
+
+// text is escaped so it stays a single word on a single line
+fn escape(text: &str) -> String {
+    if text.is_empty() {
+        return "\\0".to_string();
+    }
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ' ' => escaped.push_str("\\s"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape(word: &str) -> String {
+    let mut text = String::new();
+    let mut chars = word.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => text.push(' '),
+            Some('t') => text.push('\t'),
+            Some('n') => text.push('\n'),
+            Some('r') => text.push('\r'),
+            Some('0') => {}
+            Some(other) => text.push(other),
+            None => {}
+        }
+    }
+    text
+}
+
+fn modifiers_to_text(modifiers: KeyModifiers) -> String {
+    let flags: String = [
+        (modifiers.shift, 'S'),
+        (modifiers.ctrl, 'C'),
+        (modifiers.alt, 'A'),
+        (modifiers.gui, 'G'),
+    ]
+    .iter()
+    .filter(|(on, _)| *on)
+    .map(|(_, flag)| *flag)
+    .collect();
+    if flags.is_empty() {
+        "-".to_string()
+    } else {
+        flags
+    }
+}
+
+fn modifiers_from_text(word: &str) -> KeyModifiers {
+    KeyModifiers {
+        shift: word.contains('S'),
+        ctrl: word.contains('C'),
+        alt: word.contains('A'),
+        gui: word.contains('G'),
+    }
+}
+
+fn find_by_name<T: std::fmt::Debug + Copy>(all: &[T], name: &str) -> Option<T> {
+    all.iter()
+        .find(|item| format!("{:?}", item) == name)
+        .copied()
+}
+
+fn event_to_text(event: &InputEvent) -> String {
+    match event {
+        InputEvent::Quit => "Quit".to_string(),
+        InputEvent::WindowResized(size) => format!(
+            "WindowResized {} {} {} {}",
+            size.width, size.height, size.width_pixels, size.height_pixels
+        ),
+        InputEvent::WindowFocus(focused) => format!("WindowFocus {}", focused),
+        InputEvent::KeyDown {
+            scancode,
+            keycode,
+            modifiers,
+            repeat,
+        } => format!(
+            "KeyDown {} {} {} {}",
+            scancode.id(),
+            keycode.id(),
+            modifiers_to_text(*modifiers),
+            repeat
+        ),
+        InputEvent::KeyUp {
+            scancode,
+            keycode,
+            modifiers,
+        } => format!(
+            "KeyUp {} {} {}",
+            scancode.id(),
+            keycode.id(),
+            modifiers_to_text(*modifiers)
+        ),
+        InputEvent::TextInput(text) => format!("TextInput {}", escape(text)),
+        InputEvent::TextEditing {
+            text,
+            cursor,
+            selection,
+        } => format!("TextEditing {} {} {}", escape(text), cursor, selection),
+        InputEvent::MouseMotion { position, delta } => format!(
+            "MouseMotion {} {} {} {}",
+            position.x, position.y, delta.x, delta.y
+        ),
+        InputEvent::MouseButtonDown {
+            button,
+            position,
+            clicks,
+        } => format!(
+            "MouseButtonDown {:?} {} {} {}",
+            button, position.x, position.y, clicks
+        ),
+        InputEvent::MouseButtonUp { button, position } => {
+            format!("MouseButtonUp {:?} {} {}", button, position.x, position.y)
+        }
+        InputEvent::MouseWheel { delta, source } => {
+            format!("MouseWheel {} {} {:?}", delta.x, delta.y, source)
+        }
+        InputEvent::TouchDown {
+            finger,
+            position,
+            pressure,
+        } => format!(
+            "TouchDown {} {} {} {}",
+            finger, position.x, position.y, pressure
+        ),
+        InputEvent::TouchMotion {
+            finger,
+            position,
+            delta,
+            pressure,
+        } => format!(
+            "TouchMotion {} {} {} {} {} {}",
+            finger, position.x, position.y, delta.x, delta.y, pressure
+        ),
+        InputEvent::TouchUp { finger, position } => {
+            format!("TouchUp {} {} {}", finger, position.x, position.y)
+        }
+        InputEvent::GamepadAdded { id, name } => format!("GamepadAdded {} {}", id, escape(name)),
+        InputEvent::GamepadRemoved { id } => format!("GamepadRemoved {}", id),
+        InputEvent::GamepadButtonDown { id, button } => {
+            format!("GamepadButtonDown {} {:?}", id, button)
+        }
+        InputEvent::GamepadButtonUp { id, button } => {
+            format!("GamepadButtonUp {} {:?}", id, button)
+        }
+        InputEvent::GamepadAxisMotion { id, axis, value } => {
+            format!("GamepadAxisMotion {} {:?} {}", id, axis, value)
+        }
+    }
+}
+
+fn event_from_text(line: &str) -> Option<InputEvent> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let word = |i: usize| words.get(i).copied();
+    let number = |i: usize| word(i)?.parse::<f32>().ok();
+    let vector = |i: usize| Some(Vector2D::new(number(i)?, number(i + 1)?));
+    let extent = |i: usize| word(i)?.parse::<WindowExtent>().ok();
+    let id = |i: usize| word(i)?.parse::<u32>().ok();
+
+    let event = match *words.first()? {
+        "Quit" => InputEvent::Quit,
+        "WindowResized" => InputEvent::WindowResized(WindowSize {
+            width: extent(1)?,
+            height: extent(2)?,
+            width_pixels: extent(3)?,
+            height_pixels: extent(4)?,
+        }),
+        "WindowFocus" => InputEvent::WindowFocus(word(1)?.parse().ok()?),
+        "KeyDown" => InputEvent::KeyDown {
+            scancode: Scancode::from_id(word(1)?).unwrap_or(Scancode::Unknown),
+            keycode: Keycode::from_id(word(2)?).unwrap_or(Keycode::Unknown),
+            modifiers: modifiers_from_text(word(3)?),
+            repeat: word(4)?.parse().ok()?,
+        },
+        "KeyUp" => InputEvent::KeyUp {
+            scancode: Scancode::from_id(word(1)?).unwrap_or(Scancode::Unknown),
+            keycode: Keycode::from_id(word(2)?).unwrap_or(Keycode::Unknown),
+            modifiers: modifiers_from_text(word(3)?),
+        },
+        "TextInput" => InputEvent::TextInput(unescape(word(1)?)),
+        "TextEditing" => InputEvent::TextEditing {
+            text: unescape(word(1)?),
+            cursor: word(2)?.parse().ok()?,
+            selection: word(3)?.parse().ok()?,
+        },
+        "MouseMotion" => InputEvent::MouseMotion {
+            position: vector(1)?,
+            delta: vector(3)?,
+        },
+        "MouseButtonDown" => InputEvent::MouseButtonDown {
+            button: find_by_name(MouseButton::ALL, word(1)?)?,
+            position: vector(2)?,
+            clicks: word(4)?.parse().ok()?,
+        },
+        "MouseButtonUp" => InputEvent::MouseButtonUp {
+            button: find_by_name(MouseButton::ALL, word(1)?)?,
+            position: vector(2)?,
+        },
+        "MouseWheel" => InputEvent::MouseWheel {
+            delta: vector(1)?,
+            source: find_by_name(&[WheelSource::Wheel, WheelSource::Trackpad], word(3)?)?,
+        },
+        "TouchDown" => InputEvent::TouchDown {
+            finger: word(1)?.parse().ok()?,
+            position: vector(2)?,
+            pressure: number(4)?,
+        },
+        "TouchMotion" => InputEvent::TouchMotion {
+            finger: word(1)?.parse().ok()?,
+            position: vector(2)?,
+            delta: vector(4)?,
+            pressure: number(6)?,
+        },
+        "TouchUp" => InputEvent::TouchUp {
+            finger: word(1)?.parse().ok()?,
+            position: vector(2)?,
+        },
+        "GamepadAdded" => InputEvent::GamepadAdded {
+            id: id(1)?,
+            name: unescape(word(2)?),
+        },
+        "GamepadRemoved" => InputEvent::GamepadRemoved { id: id(1)? },
+        "GamepadButtonDown" => InputEvent::GamepadButtonDown {
+            id: id(1)?,
+            button: find_by_name(GamepadButton::ALL, word(2)?)?,
+        },
+        "GamepadButtonUp" => InputEvent::GamepadButtonUp {
+            id: id(1)?,
+            button: find_by_name(GamepadButton::ALL, word(2)?)?,
+        },
+        "GamepadAxisMotion" => InputEvent::GamepadAxisMotion {
+            id: id(1)?,
+            axis: find_by_name(GamepadAxis::ALL, word(2)?)?,
+            value: number(3)?,
+        },
+        other => {
+            warn!("unknown input event in recording: {}", other);
+            return None;
+        }
+    };
+    Some(event)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_round_trip() {
+        let mut recording = InputRecording::new(42);
+        recording.frames.push(RecordedFrame {
+            time_step: 1.0 / 60.0,
+            events: vec![
+                InputEvent::KeyDown {
+                    scancode: Scancode::Space,
+                    keycode: Keycode::Space,
+                    modifiers: KeyModifiers {
+                        shift: true,
+                        ..Default::default()
+                    },
+                    repeat: false,
+                },
+                InputEvent::TextInput("a b\\c".to_string()),
+                InputEvent::TextEditing {
+                    text: String::new(),
+                    cursor: 0,
+                    selection: 0,
+                },
+                InputEvent::MouseMotion {
+                    position: Vector2D::new(0.1, 200.5),
+                    delta: Vector2D::new(-3.0, 1e-7),
+                },
+            ],
+        });
+        recording.frames.push(RecordedFrame {
+            time_step: 0.02,
+            events: vec![
+                InputEvent::GamepadAdded {
+                    id: 3,
+                    name: "Pro Controller".to_string(),
+                },
+                InputEvent::GamepadAxisMotion {
+                    id: 3,
+                    axis: GamepadAxis::TriggerLeft,
+                    value: 0.333,
+                },
+                InputEvent::WindowResized(WindowSize::from_points(640, 480)),
+                InputEvent::TouchMotion {
+                    finger: -9,
+                    position: Vector2D::new(5.5, 6.0),
+                    delta: Vector2D::new(0.25, -1.0),
+                    pressure: 0.75,
+                },
+            ],
+        });
+
+        let text = recording.to_text();
+        assert!(text.contains("\nframe 1 0.02\n"));
+        assert_eq!(InputRecording::from_text(&text).ok(), Some(recording));
+        assert!(InputRecording::from_text("seed 1\n").is_err());
+
+        // a frame cut out of the middle is caught
+        let skipped = format!("{}\nseed 1\nframe 0 0.5\nframe 2 0.5\n", HEADER);
+        assert!(InputRecording::from_text(&skipped).is_err());
+    }
+}
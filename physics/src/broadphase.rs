@@ -0,0 +1,112 @@
+//! Broadphase: a spatial hash of grid cells, so only colliders sharing a
+//! cell are tested against each other.
+
+use std::collections::HashMap;
+
+use crate::shape::Bounds;
+
+pub const DEFAULT_CELL_SIZE: f32 = 64.0;
+// anything covering more cells than this is checked against everything
+const MAX_CELLS: i64 = 64;
+
+#[derive(Debug)]
+pub(crate) struct SpatialHash {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+    large: Vec<usize>,
+    all: Vec<usize>,
+}
+
+impl SpatialHash {
+    pub(crate) fn new(cell_size: f32) -> Self {
+        SpatialHash {
+            cell_size: cell_size.max(f32::EPSILON),
+            cells: HashMap::new(),
+            large: Vec::new(),
+            all: Vec::new(),
+        }
+    }
+
+    pub(crate) fn get_cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    pub(crate) fn set_cell_size(&mut self, cell_size: f32) {
+        self.cell_size = cell_size.max(f32::EPSILON);
+        self.clear();
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.cells.clear();
+        self.large.clear();
+        self.all.clear();
+    }
+
+    fn cell_range(&self, bounds: &Bounds) -> ((i32, i32), (i32, i32)) {
+        let cell = |value: f32| (value / self.cell_size).floor() as i32;
+        (
+            (cell(bounds.min.x), cell(bounds.min.y)),
+            (cell(bounds.max.x), cell(bounds.max.y)),
+        )
+    }
+
+    fn is_large(min: (i32, i32), max: (i32, i32)) -> bool {
+        let width = max.0 as i64 - min.0 as i64 + 1;
+        let height = max.1 as i64 - min.1 as i64 + 1;
+        width * height > MAX_CELLS
+    }
+
+    pub(crate) fn insert(&mut self, index: usize, bounds: &Bounds) {
+        self.all.push(index);
+        let (min, max) = self.cell_range(bounds);
+        if Self::is_large(min, max) {
+            self.large.push(index);
+            return;
+        }
+        for x in min.0..=max.0 {
+            for y in min.1..=max.1 {
+                self.cells.entry((x, y)).or_default().push(index);
+            }
+        }
+    }
+
+    /// Every pair that shares a cell, lowest index first, sorted and without
+    /// repeats
+    pub(crate) fn find_pairs(&self, pairs: &mut Vec<(usize, usize)>) {
+        pairs.clear();
+        for cell in self.cells.values() {
+            for (i, first) in cell.iter().enumerate() {
+                for second in cell[i + 1..].iter() {
+                    pairs.push((*first.min(second), *first.max(second)));
+                }
+            }
+        }
+        for large in self.large.iter() {
+            for other in self.all.iter().filter(|other| *other != large) {
+                pairs.push((*large.min(other), *large.max(other)));
+            }
+        }
+        pairs.sort_unstable();
+        pairs.dedup();
+    }
+
+    /// Everything that might overlap `bounds`, sorted and without repeats
+    pub(crate) fn query(&self, bounds: &Bounds, found: &mut Vec<usize>) {
+        found.clear();
+        let (min, max) = self.cell_range(bounds);
+        if Self::is_large(min, max) {
+            found.extend(self.all.iter());
+        } else {
+            for x in min.0..=max.0 {
+                for y in min.1..=max.1 {
+                    if let Some(cell) = self.cells.get(&(x, y)) {
+                        found.extend(cell.iter());
+                    }
+                }
+            }
+            found.extend(self.large.iter());
+        }
+        found.sort_unstable();
+        found.dedup();
+    }
+}
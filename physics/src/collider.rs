@@ -0,0 +1,127 @@
+use common::Vector2D;
+
+use crate::shape::{Bounds, Shape};
+
+/// Identifies a collider in the collision system.  Once it's removed, the
+/// old handle no longer refers to anything, even if its slot is reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ColliderHandle {
+    pub(crate) index: usize,
+    pub(crate) generation: u32,
+}
+
+/// Which layers a collider is on, and which layers it touches.  Two
+/// colliders only meet if each one's mask includes a layer of the other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CollisionFilter {
+    pub layers: u32,
+    pub mask: u32,
+}
+
+impl Default for CollisionFilter {
+    fn default() -> Self {
+        CollisionFilter::DEFAULT
+    }
+}
+
+impl CollisionFilter {
+    /// On layer 1, touching every layer
+    pub const DEFAULT: CollisionFilter = CollisionFilter {
+        layers: 1,
+        mask: u32::MAX,
+    };
+
+    /// On every layer and touching every layer
+    pub const EVERYTHING: CollisionFilter = CollisionFilter {
+        layers: u32::MAX,
+        mask: u32::MAX,
+    };
+
+    pub fn new(layers: u32, mask: u32) -> Self {
+        CollisionFilter { layers, mask }
+    }
+
+    pub fn can_touch(&self, other: &CollisionFilter) -> bool {
+        self.mask & other.layers != 0 && other.mask & self.layers != 0
+    }
+}
+
+/// A shape placed in the world
+#[derive(Debug, Clone, PartialEq)]
+pub struct Collider {
+    pub shape: Shape,
+    pub position: Vector2D,
+    /// In radians
+    pub angle: f32,
+    pub filter: CollisionFilter,
+    /// Sensors report contacts but nothing is pushed out of them
+    pub sensor: bool,
+    /// Mass per unit area, for the body the collider is attached to
+    pub density: f32,
+    /// 0 is ice; two colliders use the geometric mean of their frictions
+    pub friction: f32,
+    /// Bounciness, from 0 to 1; the bouncier of two colliders wins
+    pub restitution: f32,
+    /// Free for the game, e.g. to find the entity the collider belongs to
+    pub user_data: u64,
+}
+
+impl Collider {
+    pub fn new(shape: Shape) -> Self {
+        Collider {
+            shape,
+            position: Vector2D::ZERO,
+            angle: 0.0,
+            filter: CollisionFilter::default(),
+            sensor: false,
+            density: 1.0,
+            friction: 0.5,
+            restitution: 0.0,
+            user_data: 0,
+        }
+    }
+
+    pub fn with_position(mut self, position: Vector2D) -> Self {
+        self.position = position;
+        self
+    }
+
+    pub fn with_angle(mut self, angle: f32) -> Self {
+        self.angle = angle;
+        self
+    }
+
+    pub fn with_filter(mut self, filter: CollisionFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn as_sensor(mut self) -> Self {
+        self.sensor = true;
+        self
+    }
+
+    pub fn with_density(mut self, density: f32) -> Self {
+        self.density = density;
+        self
+    }
+
+    pub fn with_friction(mut self, friction: f32) -> Self {
+        self.friction = friction;
+        self
+    }
+
+    pub fn with_restitution(mut self, restitution: f32) -> Self {
+        self.restitution = restitution;
+        self
+    }
+
+    pub fn with_user_data(mut self, user_data: u64) -> Self {
+        self.user_data = user_data;
+        self
+    }
+
+    pub fn get_bounds(&self) -> Bounds {
+        self.shape.get_bounds(self.position, self.angle)
+    }
+}
@@ -1,7 +1,1110 @@
+use std::collections::BTreeMap;
 
+use common::Vector2D;
 
+use crate::{
+    broadphase::{DEFAULT_CELL_SIZE, SpatialHash},
+    collider::{Collider, ColliderHandle},
+    contact::{Manifold, collide},
+    query::{QueryFilter, QueryHit, raycast, sweep},
+    rigid_body::{BodyHandle, BodyType, MassData, RigidBody},
+    shape::{Bounds, Hull, Shape},
+    solver::{BodyState, ContactConstraint, ImpulseCache, SolverSettings},
+};
 
-#[derive(Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContactPhase {
+    /// The colliders started touching this update
+    Begin,
+    /// They were touching last update too
+    Stay,
+    /// They stopped touching, or one was removed; the manifold is the last one
+    End,
+}
+
+/// Two colliders touching.  The manifold's normal points from `first` to
+/// `second`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContactEvent {
+    pub phase: ContactPhase,
+    pub first: ColliderHandle,
+    pub second: ColliderHandle,
+    pub manifold: Manifold,
+}
+
+pub type ContactListener = Box<dyn FnMut(&ContactEvent)>;
+
+// where a collider sits on its body
+#[derive(Debug, Clone, Copy)]
+struct Attachment {
+    body: BodyHandle,
+    position: Vector2D,
+    angle: f32,
+}
+
+#[derive(Debug)]
+struct ColliderSlot {
+    collider: Option<Collider>,
+    generation: u32,
+    attachment: Option<Attachment>,
+    hull: Hull,
+    bounds: Bounds,
+}
+
+#[derive(Debug)]
+struct BodySlot {
+    body: Option<RigidBody>,
+    generation: u32,
+    colliders: Vec<ColliderHandle>,
+}
+
+/// The collision world.  Each update moves the bodies, finds which colliders
+/// touch, pushes bodies apart, and tells the contact listeners what began,
+/// stayed and ended.  Colliders without a body never move by themselves.
 pub struct CollisionSystem {
+    active: bool,
+    slots: Vec<ColliderSlot>,
+    free: Vec<usize>,
+    bodies: Vec<BodySlot>,
+    free_bodies: Vec<usize>,
+    gravity: Vector2D,
+    settings: SolverSettings,
+    broadphase: SpatialHash,
+    pairs: Vec<(usize, usize)>,
+    dirty: bool, // colliders have changed since the broadphase was built
+    contacts: BTreeMap<(ColliderHandle, ColliderHandle), Manifold>,
+    impulses: ImpulseCache,
+    states: Vec<BodyState>,
+    events: Vec<ContactEvent>,
+    listeners: Vec<ContactListener>,
+}
+
+impl Default for CollisionSystem {
+    fn default() -> Self {
+        CollisionSystem::new()
+    }
+}
+
+impl CollisionSystem {
+    pub fn new() -> Self {
+        CollisionSystem {
+            active: true,
+            slots: Vec::new(),
+            free: Vec::new(),
+            bodies: Vec::new(),
+            free_bodies: Vec::new(),
+            gravity: Vector2D::ZERO,
+            settings: SolverSettings::default(),
+            broadphase: SpatialHash::new(DEFAULT_CELL_SIZE),
+            pairs: Vec::new(),
+            dirty: false,
+            contacts: BTreeMap::new(),
+            impulses: ImpulseCache::new(),
+            states: Vec::new(),
+            events: Vec::new(),
+            listeners: Vec::new(),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    pub fn set_active(&mut self, active: bool) {
+        self.active = active;
+    }
+
+    /// The broadphase grid's cell size, in world units; about the size of a
+    /// typical collider works best
+    pub fn get_cell_size(&self) -> f32 {
+        self.broadphase.get_cell_size()
+    }
+
+    pub fn set_cell_size(&mut self, cell_size: f32) {
+        self.dirty = true;
+        self.broadphase.set_cell_size(cell_size);
+    }
+
+    /// Zero to begin with; set it for the world's units and which way is down
+    pub fn get_gravity(&self) -> Vector2D {
+        self.gravity
+    }
+
+    pub fn set_gravity(&mut self, gravity: Vector2D) {
+        self.gravity = gravity;
+    }
+
+    pub fn get_solver_settings(&self) -> SolverSettings {
+        self.settings
+    }
+
+    pub fn set_solver_settings(&mut self, settings: SolverSettings) {
+        self.settings = settings;
+    }
+
+    pub fn add_collider(&mut self, collider: Collider) -> ColliderHandle {
+        self.dirty = true;
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                self.slots.push(ColliderSlot {
+                    collider: None,
+                    generation: 0,
+                    attachment: None,
+                    hull: Hull::default(),
+                    bounds: collider.get_bounds(),
+                });
+                self.slots.len() - 1
+            }
+        };
+        let slot = &mut self.slots[index];
+        slot.collider = Some(collider);
+        slot.attachment = None;
+        ColliderHandle {
+            index,
+            generation: slot.generation,
+        }
+    }
+
+    /// Its contacts end at the next update.  Taking a collider off a body
+    /// changes the body's mass.
+    pub fn remove_collider(&mut self, handle: ColliderHandle) -> Option<Collider> {
+        self.dirty = true;
+        let slot = self
+            .slots
+            .get_mut(handle.index)
+            .filter(|slot| slot.generation == handle.generation)?;
+        let collider = slot.collider.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(handle.index);
+        if let Some(attachment) = slot.attachment.take()
+            && let Some(body) = self.body_slot_mut(attachment.body)
+        {
+            body.colliders.retain(|attached| *attached != handle);
+            self.reset_mass_data(attachment.body);
+        }
+        Some(collider)
+    }
+
+    pub fn get_collider(&self, handle: ColliderHandle) -> Option<&Collider> {
+        self.slots
+            .get(handle.index)
+            .filter(|slot| slot.generation == handle.generation)
+            .and_then(|slot| slot.collider.as_ref())
+    }
+
+    /// Moves, turns or reshapes take effect at the next update, though
+    /// queries see them at once
+    pub fn get_collider_mut(&mut self, handle: ColliderHandle) -> Option<&mut Collider> {
+        self.dirty = true;
+        self.slots
+            .get_mut(handle.index)
+            .filter(|slot| slot.generation == handle.generation)
+            .and_then(|slot| slot.collider.as_mut())
+    }
+
+    pub fn get_collider_count(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    pub fn colliders(&self) -> impl Iterator<Item = (ColliderHandle, &Collider)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            slot.collider.as_ref().map(|collider| {
+                (
+                    ColliderHandle {
+                        index,
+                        generation: slot.generation,
+                    },
+                    collider,
+                )
+            })
+        })
+    }
+
+    pub fn add_body(&mut self, body: RigidBody) -> BodyHandle {
+        self.dirty = true;
+        let index = match self.free_bodies.pop() {
+            Some(index) => index,
+            None => {
+                self.bodies.push(BodySlot {
+                    body: None,
+                    generation: 0,
+                    colliders: Vec::new(),
+                });
+                self.bodies.len() - 1
+            }
+        };
+        let slot = &mut self.bodies[index];
+        slot.body = Some(body);
+        BodyHandle {
+            index,
+            generation: slot.generation,
+        }
+    }
+
+    /// Removes the body and every collider attached to it
+    pub fn remove_body(&mut self, handle: BodyHandle) -> Option<RigidBody> {
+        self.dirty = true;
+        let slot = self.body_slot_mut(handle)?;
+        let body = slot.body.take()?;
+        let colliders = std::mem::take(&mut slot.colliders);
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free_bodies.push(handle.index);
+        for collider in colliders {
+            self.remove_collider(collider);
+        }
+        Some(body)
+    }
+
+    pub fn get_body(&self, handle: BodyHandle) -> Option<&RigidBody> {
+        self.bodies
+            .get(handle.index)
+            .filter(|slot| slot.generation == handle.generation)
+            .and_then(|slot| slot.body.as_ref())
+    }
+
+    pub fn get_body_mut(&mut self, handle: BodyHandle) -> Option<&mut RigidBody> {
+        self.dirty = true;
+        self.body_slot_mut(handle)
+            .and_then(|slot| slot.body.as_mut())
+    }
+
+    pub fn get_body_count(&self) -> usize {
+        self.bodies.len() - self.free_bodies.len()
+    }
+
+    pub fn bodies(&self) -> impl Iterator<Item = (BodyHandle, &RigidBody)> {
+        self.bodies.iter().enumerate().filter_map(|(index, slot)| {
+            slot.body.as_ref().map(|body| {
+                (
+                    BodyHandle {
+                        index,
+                        generation: slot.generation,
+                    },
+                    body,
+                )
+            })
+        })
+    }
+
+    /// Adds a collider that moves with the body, and adds its mass to the
+    /// body's.  The collider's position and angle say where it sits on the
+    /// body; from then on they follow the body around the world.
+    pub fn attach_collider(
+        &mut self,
+        body: BodyHandle,
+        collider: Collider,
+    ) -> Option<ColliderHandle> {
+        self.get_body(body)?;
+        let attachment = Attachment {
+            body,
+            position: collider.position,
+            angle: collider.angle,
+        };
+        let handle = self.add_collider(collider);
+        self.slots[handle.index].attachment = Some(attachment);
+        self.body_slot_mut(body)?.colliders.push(handle);
+        self.reset_mass_data(body);
+        self.sync_collider(handle.index);
+        Some(handle)
+    }
+
+    pub fn get_collider_body(&self, collider: ColliderHandle) -> Option<BodyHandle> {
+        self.slots
+            .get(collider.index)
+            .filter(|slot| slot.generation == collider.generation)
+            .and_then(|slot| slot.attachment)
+            .map(|attachment| attachment.body)
+    }
+
+    pub fn get_body_colliders(&self, body: BodyHandle) -> &[ColliderHandle] {
+        self.bodies
+            .get(body.index)
+            .filter(|slot| slot.generation == body.generation)
+            .map_or(&[], |slot| &slot.colliders)
+    }
+
+    /// Works the body's mass out again from its colliders, e.g. after
+    /// changing their density
+    pub fn reset_mass_data(&mut self, body: BodyHandle) {
+        let parts: Vec<MassData> = self
+            .get_body_colliders(body)
+            .iter()
+            .filter_map(|handle| {
+                let slot = &self.slots[handle.index];
+                let (collider, attachment) = (slot.collider.as_ref()?, slot.attachment?);
+                if collider.sensor {
+                    return None;
+                }
+                let mut part = collider.shape.get_mass_data(collider.density);
+                part.centre = Vector2D::from_angle(attachment.angle).rotate(part.centre)
+                    + attachment.position;
+                Some(part)
+            })
+            .collect();
+        if let Some(body) = self.get_body_mut(body) {
+            body.set_mass_data(MassData::combine(&parts));
+        }
+    }
+
+    /// The first collider along a ray, within `max_distance`
+    pub fn raycast(
+        &self,
+        origin: Vector2D,
+        direction: Vector2D,
+        max_distance: f32,
+        filter: QueryFilter,
+    ) -> Option<QueryHit> {
+        self.raycast_all(origin, direction, max_distance, filter)
+            .into_iter()
+            .next()
+    }
+
+    /// Every collider along a ray, within `max_distance`, nearest first
+    pub fn raycast_all(
+        &self,
+        origin: Vector2D,
+        direction: Vector2D,
+        max_distance: f32,
+        filter: QueryFilter,
+    ) -> Vec<QueryHit> {
+        let mut hits = Vec::new();
+        let Some(direction) = direction.try_normalize() else {
+            return hits;
+        };
+        let end = origin + direction * max_distance.min(f32::MAX);
+        self.visit_colliders(&Bounds::new(origin, end), &filter, |collider, hull| {
+            if let Some((distance, normal)) = raycast(hull, origin, direction, max_distance) {
+                hits.push(QueryHit {
+                    collider,
+                    point: origin + direction * distance,
+                    normal,
+                    distance,
+                });
+            }
+        });
+        hits.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        hits
+    }
+
+    /// The first collider a shape would hit moving by `translation`, without
+    /// turning.  The hit's distance is how far it gets.
+    pub fn sweep_shape(
+        &self,
+        shape: &Shape,
+        position: Vector2D,
+        angle: f32,
+        translation: Vector2D,
+        filter: QueryFilter,
+    ) -> Option<QueryHit> {
+        let mut hull = Hull::default();
+        shape.fill_hull(position, angle, &mut hull);
+        let max_distance = translation.length();
+        let direction = translation.try_normalize().unwrap_or(Vector2D::X);
+        let bounds = hull.get_bounds();
+        let swept = bounds.union(&Bounds::new(
+            bounds.min + translation,
+            bounds.max + translation,
+        ));
+        let mut best: Option<QueryHit> = None;
+        self.visit_colliders(&swept, &filter, |collider, target| {
+            if let Some((distance, point, normal)) = sweep(&hull, direction, max_distance, target)
+                && best.is_none_or(|best| distance < best.distance)
+            {
+                best = Some(QueryHit {
+                    collider,
+                    point,
+                    normal,
+                    distance,
+                });
+            }
+        });
+        best
+    }
+
+    /// Every collider with `point` inside it
+    pub fn colliders_at_point(&self, point: Vector2D, filter: QueryFilter) -> Vec<ColliderHandle> {
+        let mut found = Vec::new();
+        self.visit_colliders(&Bounds::new(point, point), &filter, |collider, hull| {
+            if hull.get_distance_to(point) <= 0.0 {
+                found.push(collider);
+            }
+        });
+        found
+    }
+
+    /// Every collider overlapping a shape placed in the world
+    pub fn overlap_shape(
+        &self,
+        shape: &Shape,
+        position: Vector2D,
+        angle: f32,
+        filter: QueryFilter,
+    ) -> Vec<ColliderHandle> {
+        let mut hull = Hull::default();
+        shape.fill_hull(position, angle, &mut hull);
+        let mut found = Vec::new();
+        self.visit_colliders(&hull.get_bounds(), &filter, |collider, target| {
+            if collide(&hull, target).is_some() {
+                found.push(collider);
+            }
+        });
+        found
+    }
+
+    pub fn overlap_bounds(&self, bounds: &Bounds, filter: QueryFilter) -> Vec<ColliderHandle> {
+        let shape = Shape::Aabb {
+            half_extents: bounds.get_half_extents(),
+        };
+        self.overlap_shape(&shape, bounds.get_centre(), 0.0, filter)
+    }
+
+    pub fn overlap_circle(
+        &self,
+        centre: Vector2D,
+        radius: f32,
+        filter: QueryFilter,
+    ) -> Vec<ColliderHandle> {
+        self.overlap_shape(&Shape::circle(radius), centre, 0.0, filter)
+    }
+
+    /// Called with every contact event, as each update finds them
+    pub fn add_contact_listener(&mut self, listener: impl FnMut(&ContactEvent) + 'static) {
+        self.listeners.push(Box::new(listener));
+    }
+
+    pub fn clear_contact_listeners(&mut self) {
+        self.listeners.clear();
+    }
+
+    /// Everything the last update found, step by step; within a step, ended
+    /// contacts come last
+    pub fn get_events(&self) -> &[ContactEvent] {
+        &self.events
+    }
+
+    /// What's touching `handle` now, with normals pointing away from it
+    pub fn get_contacts(&self, handle: ColliderHandle) -> Vec<(ColliderHandle, Manifold)> {
+        self.contacts
+            .iter()
+            .filter_map(|((first, second), manifold)| {
+                if *first == handle {
+                    Some((*second, *manifold))
+                } else if *second == handle {
+                    Some((*first, manifold.flipped()))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    pub fn is_touching(&self, a: ColliderHandle, b: ColliderHandle) -> bool {
+        self.contacts.contains_key(&(a.min(b), a.max(b)))
+    }
+
+    /// Advances the world by `time_step` seconds, in steps no longer than
+    /// the solver settings' `max_step`
+    pub fn update(&mut self, time_step: f32) {
+        self.events.clear();
+        let settings = self.settings;
+        let time_step = time_step.clamp(0.0, settings.max_step * settings.max_steps as f32);
+        let steps = ((time_step / settings.max_step).ceil() as usize).clamp(1, settings.max_steps);
+        for _ in 0..steps {
+            self.step(time_step / steps as f32);
+        }
+        self.refresh_broadphase();
+
+        for event in self.events.iter() {
+            for listener in self.listeners.iter_mut() {
+                listener(event);
+            }
+        }
+    }
+
+    fn body_slot_mut(&mut self, handle: BodyHandle) -> Option<&mut BodySlot> {
+        self.bodies
+            .get_mut(handle.index)
+            .filter(|slot| slot.generation == handle.generation)
+    }
+
+    fn handle(&self, index: usize) -> ColliderHandle {
+        ColliderHandle {
+            index,
+            generation: self.slots[index].generation,
+        }
+    }
+
+    // the body a collider moves with, if it's still there
+    fn body_index(&self, collider: usize) -> Option<usize> {
+        let attachment = self.slots[collider].attachment?;
+        let slot = self.bodies.get(attachment.body.index)?;
+        (slot.generation == attachment.body.generation && slot.body.is_some())
+            .then_some(attachment.body.index)
+    }
+
+    // where a collider is now, following its body if it has one
+    fn get_world_transform(&self, index: usize) -> Option<(Vector2D, f32)> {
+        let slot = &self.slots[index];
+        let collider = slot.collider.as_ref()?;
+        let body = slot
+            .attachment
+            .zip(self.body_index(index))
+            .and_then(|(attachment, body)| Some((attachment, self.bodies[body].body.as_ref()?)));
+        Some(match body {
+            Some((attachment, body)) => (
+                body.get_world_point(attachment.position),
+                body.get_angle() + attachment.angle,
+            ),
+            None => (collider.position, collider.angle),
+        })
+    }
+
+    fn sync_collider(&mut self, index: usize) {
+        if let Some((position, angle)) = self.get_world_transform(index)
+            && let Some(collider) = &mut self.slots[index].collider
+        {
+            collider.position = position;
+            collider.angle = angle;
+        }
+    }
+
+    fn sync_colliders(&mut self) {
+        for index in 0..self.slots.len() {
+            self.sync_collider(index);
+        }
+    }
+
+    fn step(&mut self, time_step: f32) {
+        self.refresh_broadphase();
+        self.find_contacts();
+        if time_step <= 0.0 {
+            return;
+        }
+
+        let mut islands = Islands::new(self.bodies.len());
+        let mut disturbed = vec![false; self.bodies.len()];
+        let mut touching = Vec::new();
+        for ((first, second), _) in self.contacts.iter() {
+            let (Some(a), Some(b)) = (
+                &self.slots[first.index].collider,
+                &self.slots[second.index].collider,
+            ) else {
+                continue;
+            };
+            if a.sensor || b.sensor {
+                continue;
+            }
+            let bodies = [self.body_index(first.index), self.body_index(second.index)];
+            let body_type = |body: Option<usize>| {
+                body.and_then(|body| self.bodies[body].body.as_ref())
+                    .map_or(BodyType::Static, |body| body.get_body_type())
+            };
+            let types = [body_type(bodies[0]), body_type(bodies[1])];
+            if !types.contains(&BodyType::Dynamic) {
+                continue;
+            }
+            match (bodies, types) {
+                ([Some(a), Some(b)], [BodyType::Dynamic, BodyType::Dynamic]) => islands.join(a, b),
+                // moving platforms keep what's on them awake
+                _ => {
+                    for side in 0..2 {
+                        let other =
+                            bodies[1 - side].and_then(|body| self.bodies[body].body.as_ref());
+                        if types[side] == BodyType::Dynamic
+                            && other.is_some_and(|other| {
+                                other.get_body_type() == BodyType::Kinematic
+                                    && (other.get_linear_velocity() != Vector2D::ZERO
+                                        || other.get_angular_velocity() != 0.0)
+                            })
+                            && let Some(body) = bodies[side]
+                        {
+                            disturbed[body] = true;
+                        }
+                    }
+                }
+            }
+            touching.push(((*first, *second), bodies));
+        }
+
+        // anything touching something awake wakes up with it
+        let mut awake_islands = vec![false; self.bodies.len()];
+        for (index, slot) in self.bodies.iter().enumerate() {
+            if let Some(body) = &slot.body
+                && body.get_body_type() == BodyType::Dynamic
+                && (body.is_awake() || disturbed[index])
+            {
+                awake_islands[islands.find(index)] = true;
+            }
+        }
+        for (index, slot) in self.bodies.iter_mut().enumerate() {
+            if let Some(body) = &mut slot.body {
+                if disturbed[index] || (!body.is_awake() && awake_islands[islands.find(index)]) {
+                    body.wake();
+                }
+                body.integrate_velocity(self.gravity, time_step);
+            }
+        }
+
+        self.states.clear();
+        self.states
+            .extend(self.bodies.iter().map(|slot| match &slot.body {
+                Some(body) if body.is_moving() => BodyState {
+                    linear: body.get_linear_velocity(),
+                    angular: body.get_angular_velocity(),
+                    inverse_mass: body.get_inverse_mass(),
+                    inverse_inertia: body.get_inverse_inertia(),
+                    centre: body.get_centre_of_mass(),
+                },
+                Some(body) => BodyState {
+                    centre: body.get_centre_of_mass(),
+                    ..Default::default()
+                },
+                None => BodyState::default(),
+            }));
+
+        let mut constraints = Vec::new();
+        for (key, bodies) in touching {
+            let moving = |body: Option<usize>| {
+                body.filter(|body| {
+                    self.bodies[*body]
+                        .body
+                        .as_ref()
+                        .is_some_and(|body| body.is_moving())
+                })
+            };
+            let bodies = [moving(bodies[0]), moving(bodies[1])];
+            if bodies
+                .iter()
+                .all(|body| body.is_none_or(|body| self.states[body].inverse_mass == 0.0))
+            {
+                continue;
+            }
+            let (Some(a), Some(b)) = (
+                &self.slots[key.0.index].collider,
+                &self.slots[key.1.index].collider,
+            ) else {
+                continue;
+            };
+            let mut constraint = ContactConstraint::new(
+                key,
+                bodies,
+                &self.contacts[&key],
+                (a.friction * b.friction).sqrt(),
+                a.restitution.max(b.restitution),
+                &self.states,
+                &self.settings,
+                time_step,
+            );
+            constraint.warm_start(&self.impulses, &mut self.states);
+            constraints.push(constraint);
+        }
+        for _ in 0..self.settings.iterations {
+            for constraint in constraints.iter_mut() {
+                constraint.solve(&mut self.states);
+            }
+        }
+        self.impulses.clear();
+        for constraint in constraints.iter() {
+            constraint.store_impulses(&mut self.impulses);
+        }
+
+        let settings = self.settings;
+        let mut island_sleep = vec![f32::MAX; self.bodies.len()];
+        for (index, slot) in self.bodies.iter_mut().enumerate() {
+            let Some(body) = &mut slot.body else {
+                continue;
+            };
+            if body.get_body_type() == BodyType::Dynamic && body.is_awake() {
+                let state = self.states[index];
+                body.set_velocities(state.linear, state.angular);
+                let still = body.get_linear_velocity().length() < settings.sleep_linear_speed
+                    && body.get_angular_velocity().abs() < settings.sleep_angular_speed;
+                body.sleep_time = if body.can_sleep && still {
+                    body.sleep_time + time_step
+                } else {
+                    0.0
+                };
+                let island = islands.find(index);
+                island_sleep[island] = island_sleep[island].min(body.sleep_time);
+            }
+            body.integrate_position(time_step);
+        }
+
+        // islands sleep together, once everything in them has been still a while
+        for (index, slot) in self.bodies.iter_mut().enumerate() {
+            if let Some(body) = &mut slot.body
+                && body.get_body_type() == BodyType::Dynamic
+                && body.is_awake()
+                && island_sleep[islands.find(index)] >= settings.time_to_sleep
+            {
+                body.sleep();
+            }
+        }
+    }
+
+    fn refresh_broadphase(&mut self) {
+        self.sync_colliders();
+        self.broadphase.clear();
+        for (index, slot) in self.slots.iter_mut().enumerate() {
+            if let Some(collider) = &slot.collider {
+                collider
+                    .shape
+                    .fill_hull(collider.position, collider.angle, &mut slot.hull);
+                slot.bounds = slot.hull.get_bounds();
+                self.broadphase.insert(index, &slot.bounds);
+            }
+        }
+        self.dirty = false;
+    }
+
+    // calls `visit` with each collider the filter accepts whose bounds
+    // overlap `bounds`, in handle order
+    fn visit_colliders(
+        &self,
+        bounds: &Bounds,
+        filter: &QueryFilter,
+        mut visit: impl FnMut(ColliderHandle, &Hull),
+    ) {
+        let mut found = Vec::new();
+        if self.dirty {
+            found.extend(
+                (0..self.slots.len()).filter(|index| self.slots[*index].collider.is_some()),
+            );
+        } else {
+            self.broadphase.query(bounds, &mut found);
+        }
+        let mut moved = Hull::default();
+        for index in found {
+            let slot = &self.slots[index];
+            let Some(collider) = slot
+                .collider
+                .as_ref()
+                .filter(|collider| filter.accepts(collider))
+            else {
+                continue;
+            };
+            let hull = match self.get_world_transform(index) {
+                Some((position, angle)) if self.dirty => {
+                    collider.shape.fill_hull(position, angle, &mut moved);
+                    &moved
+                }
+                _ => &slot.hull,
+            };
+            if hull.get_bounds().overlaps(bounds) {
+                visit(self.handle(index), hull);
+            }
+        }
+    }
+
+    fn find_contacts(&mut self) {
+        let mut pairs = std::mem::take(&mut self.pairs);
+        self.broadphase.find_pairs(&mut pairs);
+        let mut contacts = BTreeMap::new();
+        for (first, second) in pairs.iter().copied() {
+            let (a, b) = (&self.slots[first], &self.slots[second]);
+            let (Some(collider_a), Some(collider_b)) = (&a.collider, &b.collider) else {
+                continue;
+            };
+            if !collider_a.filter.can_touch(&collider_b.filter) || !a.bounds.overlaps(&b.bounds) {
+                continue;
+            }
+            // the parts of one body don't touch each other
+            let body = self.body_index(first);
+            if body.is_some() && body == self.body_index(second) {
+                continue;
+            }
+            if let Some(manifold) = collide(&a.hull, &b.hull) {
+                contacts.insert((self.handle(first), self.handle(second)), manifold);
+            }
+        }
+        self.pairs = pairs;
+
+        for ((first, second), manifold) in contacts.iter() {
+            let phase = if self.contacts.contains_key(&(*first, *second)) {
+                ContactPhase::Stay
+            } else {
+                ContactPhase::Begin
+            };
+            self.events.push(ContactEvent {
+                phase,
+                first: *first,
+                second: *second,
+                manifold: *manifold,
+            });
+        }
+        for ((first, second), manifold) in self.contacts.iter() {
+            if !contacts.contains_key(&(*first, *second)) {
+                self.events.push(ContactEvent {
+                    phase: ContactPhase::End,
+                    first: *first,
+                    second: *second,
+                    manifold: *manifold,
+                });
+            }
+        }
+        self.contacts = contacts;
+    }
+}
+
+// groups of dynamic bodies touching each other, which sleep and wake together
+struct Islands {
+    parents: Vec<usize>,
+}
+
+impl Islands {
+    fn new(count: usize) -> Self {
+        Islands {
+            parents: (0..count).collect(),
+        }
+    }
+
+    fn find(&mut self, mut index: usize) -> usize {
+        while self.parents[index] != index {
+            self.parents[index] = self.parents[self.parents[index]];
+            index = self.parents[index];
+        }
+        index
+    }
+
+    fn join(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        self.parents[a.max(b)] = a.min(b);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use common::Vector2D;
+
+    use super::*;
+    use crate::{
+        collider::CollisionFilter,
+        rigid_body::{BodyType, RigidBody},
+        shape::Shape,
+    };
+
+    #[test]
+    fn contacts_begin_stay_and_end() {
+        let mut collision = CollisionSystem::new();
+        let phases = Rc::new(RefCell::new(Vec::new()));
+        let heard = phases.clone();
+        collision.add_contact_listener(move |event| heard.borrow_mut().push(event.phase));
+
+        let ground = collision.add_collider(
+            Collider::new(Shape::aabb(1000.0, 10.0)).with_position(Vector2D::new(0.0, -5.0)),
+        );
+        let ball = collision.add_collider(
+            Collider::new(Shape::circle(5.0)).with_position(Vector2D::new(100.0, 4.0)),
+        );
+        // overlapping the ball, but on a layer it doesn't touch
+        collision.add_collider(
+            Collider::new(Shape::circle(5.0))
+                .with_position(Vector2D::new(100.0, 6.0))
+                .with_filter(CollisionFilter::new(2, 2)),
+        );
+
+        collision.update(0.0);
+        collision.update(0.0);
+        assert!(collision.is_touching(ball, ground));
+        let contacts = collision.get_contacts(ball);
+        assert_eq!(contacts.len(), 1);
+        assert_eq!(contacts[0].0, ground);
+        assert!(contacts[0].1.normal.distance(-Vector2D::Y) < 1e-5);
+        assert!((contacts[0].1.get_depth() - 1.0).abs() < 1e-4);
+
+        collision.get_collider_mut(ball).unwrap().position.y = 20.0;
+        collision.update(0.0);
+        collision.update(0.0);
+        assert_eq!(
+            *phases.borrow(),
+            [ContactPhase::Begin, ContactPhase::Stay, ContactPhase::End]
+        );
+
+        // removing a collider ends its contacts, and its handle goes stale
+        collision.get_collider_mut(ball).unwrap().position.y = 0.0;
+        collision.update(0.0);
+        assert!(collision.remove_collider(ball).is_some());
+        let replacement = collision.add_collider(Collider::new(Shape::circle(1.0)));
+        assert!(collision.get_collider(ball).is_none());
+        assert_ne!(replacement, ball);
+        collision.update(0.0);
+        assert_eq!(collision.get_events()[0].phase, ContactPhase::Begin);
+        assert_eq!(collision.get_events()[1].phase, ContactPhase::End);
+        assert_eq!(collision.get_events()[1].second, ball);
+    }
+
+    #[test]
+    fn colliders_on_one_body_never_touch() {
+        let mut collision = CollisionSystem::new();
+        let body = collision.add_body(RigidBody::new(BodyType::Dynamic));
+        let hull = collision
+            .attach_collider(body, Collider::new(Shape::rectangle(4.0, 2.0)))
+            .unwrap();
+        let turret = collision
+            .attach_collider(body, Collider::new(Shape::circle(1.5)))
+            .unwrap();
+
+        collision.update(1.0 / 60.0);
+        collision.update(1.0 / 60.0);
+        assert!(collision.get_events().is_empty());
+        assert!(!collision.is_touching(hull, turret));
+        assert!(collision.get_contacts(hull).is_empty());
+    }
+
+    #[test]
+    fn bodies_fall_bounce_and_sleep() {
+        let mut collision = CollisionSystem::new();
+        collision.set_gravity(Vector2D::new(0.0, -10.0));
+        collision.add_collider(
+            Collider::new(Shape::aabb(100.0, 2.0)).with_position(Vector2D::new(0.0, -1.0)),
+        );
+
+        let box_body = collision
+            .add_body(RigidBody::new(BodyType::Dynamic).with_position(Vector2D::new(0.0, 3.0)));
+        collision.attach_collider(
+            box_body,
+            Collider::new(Shape::rectangle(2.0, 1.0)).with_density(2.0),
+        );
+        let mass = collision.get_body(box_body).unwrap().get_mass_data();
+        assert_eq!(mass.mass, 4.0);
+        assert!((mass.inertia - 4.0 * 5.0 / 12.0).abs() < 1e-5);
+
+        // it lands flat and settles, then goes to sleep
+        for _ in 0..120 {
+            collision.update(1.0 / 60.0);
+        }
+        let body = collision.get_body(box_body).unwrap();
+        assert!((body.get_position().y - 0.5).abs() < 0.02);
+        assert!(body.get_angle().abs() < 1e-3);
+        assert!(!body.is_awake());
+
+        // a kick wakes it, and friction slows it down
+        let body = collision.get_body_mut(box_body).unwrap();
+        body.apply_impulse(Vector2D::new(8.0, 0.0), None);
+        assert_eq!(body.get_linear_velocity(), Vector2D::new(2.0, 0.0));
+        collision.update(1.0 / 60.0);
+        let body = collision.get_body(box_body).unwrap();
+        assert!(body.is_awake());
+        let speed = body.get_linear_velocity().x;
+        assert!(speed > 1.8 && speed < 2.0);
+
+        // a bouncy ball comes back up
+        let ball = collision.add_body(
+            RigidBody::new(BodyType::Dynamic)
+                .with_position(Vector2D::new(20.0, 2.0))
+                .with_velocity(Vector2D::new(0.0, -10.0)),
+        );
+        collision.attach_collider(
+            ball,
+            Collider::new(Shape::circle(0.5)).with_restitution(1.0),
+        );
+        for _ in 0..20 {
+            collision.update(1.0 / 60.0);
+        }
+        assert!(collision.get_body(ball).unwrap().get_linear_velocity().y > 6.0);
+    }
+
+    #[test]
+    fn queries_filter_by_layer() {
+        let mut collision = CollisionSystem::new();
+        let wall = collision.add_collider(
+            Collider::new(Shape::aabb(2.0, 20.0)).with_position(Vector2D::new(10.0, 0.0)),
+        );
+        let ghost = collision.add_collider(
+            Collider::new(Shape::circle(1.0))
+                .with_position(Vector2D::new(5.0, 0.0))
+                .with_filter(CollisionFilter::new(2, u32::MAX)),
+        );
+        let trigger = collision.add_collider(
+            Collider::new(Shape::circle(1.0))
+                .with_position(Vector2D::new(0.0, 5.0))
+                .as_sensor(),
+        );
+
+        // queries work before any update, and after one
+        for _ in 0..2 {
+            let hits = collision.raycast_all(
+                Vector2D::ZERO,
+                Vector2D::new(2.0, 0.0),
+                100.0,
+                QueryFilter::default(),
+            );
+            assert_eq!(hits.len(), 2);
+            assert_eq!(hits[0].collider, ghost);
+            assert!((hits[0].distance - 4.0).abs() < 1e-4);
+            assert_eq!(hits[1].collider, wall);
+            assert!(hits[1].point.distance(Vector2D::new(9.0, 0.0)) < 1e-4);
+            assert_eq!(hits[1].normal, -Vector2D::X);
+
+            let hit = collision
+                .raycast(Vector2D::ZERO, Vector2D::X, 100.0, QueryFilter::new(1, 1))
+                .unwrap();
+            assert_eq!(hit.collider, wall);
+            collision.update(1.0 / 60.0);
+        }
+
+        // the mask picks the layers seen; the layers must be in their masks
+        let ray = |collision: &CollisionSystem, layers, mask| {
+            collision
+                .raycast(
+                    Vector2D::ZERO,
+                    Vector2D::X,
+                    100.0,
+                    QueryFilter::new(layers, mask),
+                )
+                .map(|hit| hit.collider)
+        };
+        assert_eq!(ray(&collision, 1, 2), Some(ghost));
+        assert_eq!(ray(&collision, 2, 1), Some(wall));
+        collision.get_collider_mut(ghost).unwrap().filter = CollisionFilter::new(2, 4);
+        assert_eq!(ray(&collision, 1, 2), None);
+        assert_eq!(ray(&collision, 4, 2), Some(ghost));
+        collision.get_collider_mut(ghost).unwrap().filter = CollisionFilter::new(2, u32::MAX);
+
+        assert!(
+            collision
+                .raycast(Vector2D::ZERO, Vector2D::X, 3.0, QueryFilter::default())
+                .is_none()
+        );
+
+        // sensors only show up when asked for
+        let above = Vector2D::new(0.0, 5.5);
+        assert!(
+            collision
+                .colliders_at_point(above, QueryFilter::default())
+                .is_empty()
+        );
+        assert_eq!(
+            collision.colliders_at_point(above, QueryFilter::default().with_sensors()),
+            vec![trigger]
+        );
+
+        let region = Bounds::new(Vector2D::new(4.0, -1.0), Vector2D::new(9.5, 1.0));
+        assert_eq!(
+            collision.overlap_bounds(&region, QueryFilter::default()),
+            vec![wall, ghost]
+        );
+        assert_eq!(
+            collision.overlap_circle(Vector2D::new(8.0, 0.0), 1.5, QueryFilter::new(1, 1)),
+            vec![wall]
+        );
 
-}
\ No newline at end of file
+        // a box pushed right stops against the wall, once it's moved
+        collision.get_collider_mut(ghost).unwrap().position = Vector2D::new(5.0, 50.0);
+        let hit = collision
+            .sweep_shape(
+                &Shape::rectangle(2.0, 2.0),
+                Vector2D::ZERO,
+                0.0,
+                Vector2D::new(20.0, 0.0),
+                QueryFilter::default(),
+            )
+            .unwrap();
+        assert_eq!(hit.collider, wall);
+        assert!((hit.distance - 8.0).abs() < 1e-3);
+        assert!(hit.normal.distance(-Vector2D::X) < 1e-3);
+    }
+}
@@ -0,0 +1,347 @@
+//! Narrowphase: where two hulls touch.  Separating axes find the direction
+//! of least overlap, then the touching edges are clipped against each other
+//! for up to two contact points.
+
+use common::Vector2D;
+
+use crate::shape::Hull;
+
+// how much better a corner's axis has to be before it's used over a face's,
+// so flat contacts don't flicker between one and two points
+const AXIS_TOLERANCE: f32 = 1e-3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ContactPoint {
+    /// Halfway between the two surfaces
+    pub position: Vector2D,
+    /// How far the shapes overlap here
+    pub depth: f32,
+    // the features that made the point, to match it up across frames
+    pub(crate) id: u32,
+}
+
+/// How two shapes touch.  The normal points from the first shape to the
+/// second, so moving the second along it separates them.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Manifold {
+    pub normal: Vector2D,
+    points: [ContactPoint; 2],
+    point_count: usize,
+}
+
+impl Manifold {
+    fn new(normal: Vector2D) -> Self {
+        Manifold {
+            normal,
+            ..Default::default()
+        }
+    }
+
+    fn push(&mut self, point: ContactPoint) {
+        if self.point_count < self.points.len() {
+            self.points[self.point_count] = point;
+            self.point_count += 1;
+        }
+    }
+
+    pub fn get_points(&self) -> &[ContactPoint] {
+        &self.points[..self.point_count]
+    }
+
+    pub fn get_depth(&self) -> f32 {
+        self.get_points()
+            .iter()
+            .fold(0.0, |depth, point| depth.max(point.depth))
+    }
+
+    /// The same contact seen from the other shape
+    pub fn flipped(&self) -> Manifold {
+        Manifold {
+            normal: -self.normal,
+            ..*self
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Axis {
+    FaceA(usize),
+    FaceB(usize),
+    Points(Vector2D, usize, usize),
+}
+
+// how far `b` is in front of the face, negative when they overlap
+fn face_separation(face: &Hull, index: usize, other: &Hull) -> f32 {
+    let origin = face.points[index];
+    let normal = face.normals[index];
+    other
+        .points
+        .iter()
+        .map(|point| (*point - origin).dot(normal))
+        .fold(f32::MAX, f32::min)
+}
+
+fn axis_separation(a: &Hull, b: &Hull, axis: Vector2D) -> f32 {
+    let a_max = a
+        .points
+        .iter()
+        .map(|point| point.dot(axis))
+        .fold(f32::MIN, f32::max);
+    let b_min = b
+        .points
+        .iter()
+        .map(|point| point.dot(axis))
+        .fold(f32::MAX, f32::min);
+    b_min - a_max
+}
+
+// the axis that best separates the hulls' cores, and by how much.  Past
+// `limit` they're apart, and the search stops early.
+fn best_axis(a: &Hull, b: &Hull, limit: f32, corners: bool) -> Option<(f32, Axis)> {
+    let mut best: Option<(f32, Axis)> = None;
+    for index in 0..a.normals.len() {
+        let separation = face_separation(a, index, b);
+        if best.is_none_or(|(best, _)| separation > best) {
+            best = Some((separation, Axis::FaceA(index)));
+        }
+    }
+    for index in 0..b.normals.len() {
+        let separation = face_separation(b, index, a);
+        if best.is_none_or(|(best, _)| separation > best + AXIS_TOLERANCE) {
+            best = Some((separation, Axis::FaceB(index)));
+        }
+    }
+    if !corners || best.is_some_and(|(separation, _)| separation > limit) {
+        return best;
+    }
+
+    for (i, point_a) in a.points.iter().enumerate() {
+        for (j, point_b) in b.points.iter().enumerate() {
+            let Some(axis) = (*point_b - *point_a).try_normalize() else {
+                continue;
+            };
+            let separation = axis_separation(a, b, axis);
+            if best.is_none_or(|(best, _)| separation > best + AXIS_TOLERANCE) {
+                best = Some((separation, Axis::Points(axis, i, j)));
+            }
+        }
+    }
+    best
+}
+
+/// The gap between the hulls' surfaces, negative if they overlap, and the
+/// direction from `a` to `b` across it
+pub(crate) fn distance(a: &Hull, b: &Hull) -> Option<(f32, Vector2D)> {
+    if a.points.is_empty() || b.points.is_empty() {
+        return None;
+    }
+    let radius = a.radius + b.radius;
+    let Some((separation, axis)) = best_axis(a, b, f32::MAX, true) else {
+        return Some((-radius, Vector2D::X));
+    };
+    let normal = match axis {
+        Axis::FaceA(index) => a.normals[index],
+        Axis::FaceB(index) => -b.normals[index],
+        Axis::Points(normal, _, _) => normal,
+    };
+    Some((separation - radius, normal))
+}
+
+/// None unless the hulls overlap
+pub(crate) fn collide(a: &Hull, b: &Hull) -> Option<Manifold> {
+    if a.points.is_empty() || b.points.is_empty() {
+        return None;
+    }
+    let radius = a.radius + b.radius;
+
+    // round shapes can also be kept apart across a corner
+    let corners = radius > 0.0 || a.normals.is_empty() || b.normals.is_empty();
+    let best = best_axis(a, b, radius, corners);
+
+    let Some((separation, axis)) = best else {
+        // two points in the same place; push them apart any way at all
+        let mut manifold = Manifold::new(Vector2D::X);
+        manifold.push(ContactPoint {
+            position: a.points[0],
+            depth: radius,
+            id: 0,
+        });
+        return Some(manifold);
+    };
+    if separation > radius {
+        return None;
+    }
+
+    match axis {
+        Axis::Points(normal, i, j) => {
+            let depth = radius - separation;
+            let mut manifold = Manifold::new(normal);
+            manifold.push(ContactPoint {
+                position: b.points[j] - normal * (b.radius - depth * 0.5),
+                depth,
+                id: (2 << 24) | ((i as u32) << 8) | j as u32,
+            });
+            Some(manifold)
+        }
+        Axis::FaceA(index) => clip(a, index, b, 0),
+        Axis::FaceB(index) => clip(b, index, a, 1).map(|manifold| manifold.flipped()),
+    }
+}
+
+// contact points from the `reference` hull's face against the `incident`
+// hull; the normal points from the reference hull to the incident one
+fn clip(reference: &Hull, index: usize, incident: &Hull, side: u32) -> Option<Manifold> {
+    let normal = reference.normals[index];
+    let (start, end) = reference.get_edge(index);
+    let radius = reference.radius + incident.radius;
+    let mut manifold = Manifold::new(normal);
+    let point = |position: Vector2D, feature: u32| {
+        let depth = radius - (position - start).dot(normal);
+        let point = ContactPoint {
+            position: position - normal * (incident.radius - depth * 0.5),
+            depth,
+            id: (side << 24) | ((index as u32) << 16) | feature,
+        };
+        (depth >= 0.0).then_some(point)
+    };
+
+    if incident.normals.is_empty() {
+        manifold.push(point(incident.points[0], 0)?);
+        return Some(manifold);
+    }
+
+    // the incident edge is the one facing most against the reference face
+    let facing = (0..incident.normals.len())
+        .min_by(|a, b| {
+            incident.normals[*a]
+                .dot(normal)
+                .total_cmp(&incident.normals[*b].dot(normal))
+        })
+        .unwrap_or(0);
+    let (mut first, mut second) = incident.get_edge(facing);
+
+    // trim it to the sides of the reference face
+    let tangent = (end - start).normalize_or_zero();
+    let (low, high) = (start.dot(tangent), end.dot(tangent));
+    for (limit, sign) in [(low, 1.0), (high, -1.0)] {
+        let first_inside = (first.dot(tangent) - limit) * sign;
+        let second_inside = (second.dot(tangent) - limit) * sign;
+        if first_inside < 0.0 && second_inside < 0.0 {
+            // the edges don't overlap, so they meet at a corner
+            let corner = if sign > 0.0 { start } else { end };
+            let closest = if first.distance_squared(corner) < second.distance_squared(corner) {
+                first
+            } else {
+                second
+            };
+            manifold.push(point(closest, (facing as u32) << 8)?);
+            return Some(manifold);
+        }
+        if first_inside < 0.0 {
+            first = first + (second - first) * (first_inside / (first_inside - second_inside));
+        } else if second_inside < 0.0 {
+            second = second + (first - second) * (second_inside / (second_inside - first_inside));
+        }
+    }
+
+    let facing = (facing as u32) << 8;
+    for found in [point(first, facing), point(second, facing | 1)]
+        .into_iter()
+        .flatten()
+    {
+        manifold.push(found);
+    }
+    if manifold.point_count == 0 {
+        return None;
+    }
+    // a segment's two points meet in one for shapes that only just touch
+    if manifold.point_count == 2
+        && manifold.points[0]
+            .position
+            .distance_squared(manifold.points[1].position)
+            < 1e-10
+    {
+        manifold.point_count = 1;
+    }
+    Some(manifold)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shape::Shape;
+
+    fn hull(shape: &Shape, position: Vector2D, angle: f32) -> Hull {
+        let mut hull = Hull::default();
+        shape.fill_hull(position, angle, &mut hull);
+        hull
+    }
+
+    #[test]
+    fn shapes_make_contacts() {
+        let circle = Shape::circle(1.0);
+        let a = hull(&circle, Vector2D::ZERO, 0.0);
+        let b = hull(&circle, Vector2D::new(1.5, 0.0), 0.0);
+        let manifold = collide(&a, &b).unwrap();
+        assert_eq!(manifold.normal, Vector2D::X);
+        assert_eq!(manifold.get_points().len(), 1);
+        assert!((manifold.get_depth() - 0.5).abs() < 1e-5);
+        assert!(
+            manifold.get_points()[0]
+                .position
+                .distance(Vector2D::new(0.75, 0.0))
+                < 1e-5
+        );
+        assert!(collide(&a, &hull(&circle, Vector2D::new(2.1, 0.0), 0.0)).is_none());
+
+        // a box resting on a wider one touches along its whole bottom
+        let ground = hull(&Shape::aabb(10.0, 1.0), Vector2D::ZERO, 0.0);
+        let crate_box = hull(&Shape::rectangle(1.0, 1.0), Vector2D::new(2.0, 0.9), 0.0);
+        let manifold = collide(&ground, &crate_box).unwrap();
+        assert!(manifold.normal.distance(Vector2D::Y) < 1e-5);
+        assert_eq!(manifold.get_points().len(), 2);
+        for point in manifold.get_points() {
+            assert!((point.depth - 0.1).abs() < 1e-5);
+        }
+        // seen the other way round, the normal flips
+        let manifold = collide(&crate_box, &ground).unwrap();
+        assert!(manifold.normal.distance(-Vector2D::Y) < 1e-5);
+
+        // a circle near a turned box's corner is pushed off the corner
+        let diamond = hull(
+            &Shape::rectangle(2.0, 2.0),
+            Vector2D::ZERO,
+            std::f32::consts::FRAC_PI_4,
+        );
+        let ball = hull(&circle, Vector2D::new(2.0, 0.0), 0.0);
+        let manifold = collide(&diamond, &ball).unwrap();
+        assert!(manifold.normal.distance(Vector2D::X) < 1e-4);
+        assert!((manifold.get_depth() - (std::f32::consts::SQRT_2 - 1.0)).abs() < 1e-4);
+
+        // capsules and segments cross
+        let capsule = hull(
+            &Shape::capsule(Vector2D::new(-1.0, 0.0), Vector2D::new(1.0, 0.0), 0.25),
+            Vector2D::ZERO,
+            0.0,
+        );
+        let segment = hull(
+            &Shape::segment(Vector2D::new(0.0, -1.0), Vector2D::new(0.0, 1.0)),
+            Vector2D::new(0.5, 0.0),
+            0.0,
+        );
+        assert!(collide(&capsule, &segment).is_some());
+        let triangle = Shape::polygon(&[
+            Vector2D::new(0.0, 0.0),
+            Vector2D::new(1.0, 0.0),
+            Vector2D::new(0.0, 1.0),
+            Vector2D::new(0.2, 0.2),
+        ])
+        .unwrap();
+        let Shape::Polygon(polygon) = &triangle else {
+            unreachable!();
+        };
+        assert_eq!(polygon.get_points().len(), 3);
+        assert!(collide(&hull(&triangle, Vector2D::new(0.0, 0.1), 0.0), &capsule).is_some());
+        assert!(collide(&hull(&triangle, Vector2D::new(0.6, 0.0), 0.0), &segment).is_none());
+    }
+}
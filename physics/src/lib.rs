@@ -2,7 +2,14 @@ use std::{cell::RefCell, rc::Rc};
 
 use crate::collision_system::CollisionSystem;
 
+pub mod broadphase;
+pub mod collider;
 pub mod collision_system;
+pub mod contact;
+pub mod query;
+pub mod rigid_body;
+pub mod shape;
+pub mod solver;
 
 pub type RefCollisionSystem = Rc<RefCell<CollisionSystem>>;
 
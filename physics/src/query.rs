@@ -0,0 +1,205 @@
+//! Asking the collision world what's where: rays, swept shapes, points and
+//! regions.
+
+use common::Vector2D;
+
+use crate::{
+    collider::{Collider, ColliderHandle, CollisionFilter},
+    contact::{collide, distance},
+    shape::Hull,
+};
+
+// sweeps stop once the shapes are this close
+const SWEEP_TOLERANCE: f32 = 1e-4;
+const SWEEP_ITERATIONS: usize = 32;
+
+/// Which colliders a query sees: those its filter could touch, and sensors
+/// only if asked for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueryFilter {
+    pub filter: CollisionFilter,
+    pub sensors: bool,
+}
+
+impl Default for QueryFilter {
+    fn default() -> Self {
+        QueryFilter {
+            filter: CollisionFilter::EVERYTHING,
+            sensors: false,
+        }
+    }
+}
+
+impl QueryFilter {
+    /// Sees colliders on any of `mask`'s layers whose own masks include one
+    /// of `layers`; see `CollisionFilter::can_touch`
+    pub fn new(layers: u32, mask: u32) -> Self {
+        QueryFilter {
+            filter: CollisionFilter::new(layers, mask),
+            sensors: false,
+        }
+    }
+
+    pub fn with_sensors(mut self) -> Self {
+        self.sensors = true;
+        self
+    }
+
+    pub fn accepts(&self, collider: &Collider) -> bool {
+        (self.sensors || !collider.sensor) && self.filter.can_touch(&collider.filter)
+    }
+}
+
+/// Where a ray or swept shape first meets a collider.  The normal is the
+/// collider's surface normal there, facing back along the ray.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QueryHit {
+    pub collider: ColliderHandle,
+    pub point: Vector2D,
+    pub normal: Vector2D,
+    /// How far along the ray, or how far the shape moved
+    pub distance: f32,
+}
+
+/// Where a ray starting at `origin` along the unit `direction` enters the
+/// hull, within `max_distance`.  Rays starting inside hit at once.
+pub(crate) fn raycast(
+    hull: &Hull,
+    origin: Vector2D,
+    direction: Vector2D,
+    max_distance: f32,
+) -> Option<(f32, Vector2D)> {
+    if hull.points.is_empty() {
+        return None;
+    }
+    if hull.get_distance_to(origin) <= 0.0 {
+        return Some((0.0, -direction));
+    }
+
+    let mut best: Option<(f32, Vector2D)> = None;
+    let mut consider = |distance: f32, normal: Vector2D| {
+        if (0.0..=max_distance).contains(&distance) && best.is_none_or(|(best, _)| distance < best)
+        {
+            best = Some((distance, normal));
+        }
+    };
+
+    // the faces, pushed out by the radius
+    for (index, normal) in hull.normals.iter().enumerate() {
+        let facing = direction.dot(*normal);
+        if facing >= 0.0 {
+            continue;
+        }
+        let (start, end) = hull.get_edge(index);
+        let (start, end) = (start + *normal * hull.radius, end + *normal * hull.radius);
+        let distance = (start - origin).dot(*normal) / facing;
+        let edge = end - start;
+        let along = (origin + direction * distance - start).dot(edge) / edge.length_squared();
+        if (0.0..=1.0).contains(&along) {
+            consider(distance, *normal);
+        }
+    }
+
+    // and the rounded corners
+    if hull.radius > 0.0 {
+        for corner in hull.points.iter() {
+            let offset = origin - *corner;
+            let half_b = offset.dot(direction);
+            let c = offset.length_squared() - hull.radius * hull.radius;
+            let discriminant = half_b * half_b - c;
+            if discriminant < 0.0 {
+                continue;
+            }
+            let distance = -half_b - discriminant.sqrt();
+            let normal = (origin + direction * distance - *corner) / hull.radius;
+            consider(distance, normal);
+        }
+    }
+    best
+}
+
+/// How far `hull` moves along the unit `direction` before it touches
+/// `target`, by conservative advancement; with the point and the target's
+/// normal there
+pub(crate) fn sweep(
+    hull: &Hull,
+    direction: Vector2D,
+    max_distance: f32,
+    target: &Hull,
+) -> Option<(f32, Vector2D, Vector2D)> {
+    let mut moved = hull.clone();
+    let mut travelled = 0.0;
+    for _ in 0..SWEEP_ITERATIONS {
+        let (gap, normal) = distance(&moved, target)?;
+        if gap <= SWEEP_TOLERANCE {
+            // grow the shape a touch, so the contact has a point to report
+            moved.radius += SWEEP_TOLERANCE * 2.0;
+            let manifold = collide(&moved, target)?;
+            let points = manifold.get_points();
+            let point = points
+                .iter()
+                .fold(Vector2D::ZERO, |sum, point| sum + point.position)
+                / points.len() as f32;
+            return Some((travelled, point, -manifold.normal));
+        }
+        let closing = direction.dot(normal);
+        if closing <= 0.0 {
+            return None;
+        }
+        let step = gap / closing;
+        travelled += step;
+        if travelled > max_distance {
+            return None;
+        }
+        for point in moved.points.iter_mut() {
+            *point += direction * step;
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shape::Shape;
+
+    fn hull(shape: &Shape, position: Vector2D) -> Hull {
+        let mut hull = Hull::default();
+        shape.fill_hull(position, 0.0, &mut hull);
+        hull
+    }
+
+    #[test]
+    fn rays_and_sweeps_hit_shapes() {
+        let circle = hull(&Shape::circle(1.0), Vector2D::new(5.0, 0.0));
+        let (distance, normal) = raycast(&circle, Vector2D::ZERO, Vector2D::X, 10.0).unwrap();
+        assert!((distance - 4.0).abs() < 1e-5);
+        assert!(normal.distance(-Vector2D::X) < 1e-5);
+        assert!(raycast(&circle, Vector2D::ZERO, Vector2D::X, 3.0).is_none());
+        assert!(raycast(&circle, Vector2D::ZERO, Vector2D::Y, 10.0).is_none());
+        assert_eq!(
+            raycast(&circle, Vector2D::new(5.5, 0.0), Vector2D::Y, 10.0),
+            Some((0.0, -Vector2D::Y))
+        );
+
+        // down onto a box's top, and a capsule's rounded end
+        let block = hull(&Shape::rectangle(2.0, 2.0), Vector2D::ZERO);
+        let (distance, normal) =
+            raycast(&block, Vector2D::new(0.5, 5.0), -Vector2D::Y, 10.0).unwrap();
+        assert!((distance - 4.0).abs() < 1e-5 && normal == Vector2D::Y);
+        let capsule = hull(
+            &Shape::capsule(Vector2D::new(-1.0, 0.0), Vector2D::new(1.0, 0.0), 0.5),
+            Vector2D::ZERO,
+        );
+        let (distance, _) = raycast(&capsule, Vector2D::new(5.0, 0.0), -Vector2D::X, 10.0).unwrap();
+        assert!((distance - 3.5).abs() < 1e-5);
+
+        // a ball rolled at the box stops against its side
+        let ball = hull(&Shape::circle(0.5), Vector2D::new(-5.0, 0.0));
+        let (distance, point, normal) = sweep(&ball, Vector2D::X, 10.0, &block).unwrap();
+        assert!((distance - 3.5).abs() < 1e-3);
+        assert!(point.distance(Vector2D::new(-1.0, 0.0)) < 1e-3);
+        assert!(normal.distance(-Vector2D::X) < 1e-3);
+        assert!(sweep(&ball, Vector2D::Y, 10.0, &block).is_none());
+    }
+}
@@ -0,0 +1,345 @@
+use common::Vector2D;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyType {
+    /// Never moves, like the ground and walls
+    Static,
+    /// Moves at its velocity, pushing dynamic bodies but never pushed back;
+    /// moving platforms and doors
+    Kinematic,
+    /// Moved by forces, gravity and contacts
+    Dynamic,
+}
+
+/// Identifies a body in the collision system.  Once it's removed, the old
+/// handle no longer refers to anything, even if its slot is reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BodyHandle {
+    pub(crate) index: usize,
+    pub(crate) generation: u32,
+}
+
+/// A body's mass, where its centre of mass sits relative to its position,
+/// and its rotational inertia about that centre
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct MassData {
+    pub mass: f32,
+    pub centre: Vector2D,
+    pub inertia: f32,
+}
+
+impl MassData {
+    /// Several shapes' masses, as one
+    pub fn combine(parts: &[MassData]) -> MassData {
+        let mass: f32 = parts.iter().map(|part| part.mass).sum();
+        if mass <= 0.0 {
+            return MassData::default();
+        }
+        let centre = parts
+            .iter()
+            .fold(Vector2D::ZERO, |sum, part| sum + part.centre * part.mass)
+            / mass;
+        // parallel axis theorem
+        let inertia = parts
+            .iter()
+            .map(|part| part.inertia + part.mass * part.centre.distance_squared(centre))
+            .sum();
+        MassData {
+            mass,
+            centre,
+            inertia,
+        }
+    }
+}
+
+/// Something that moves.  Its shape comes from the colliders attached to it,
+/// which also give it its mass.  Changing how it moves wakes it up.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RigidBody {
+    body_type: BodyType,
+    position: Vector2D,
+    angle: f32,
+    linear_velocity: Vector2D,
+    angular_velocity: f32,
+    force: Vector2D,
+    torque: f32,
+    mass_data: MassData,
+    inverse_mass: f32,
+    inverse_inertia: f32,
+    awake: bool,
+    pub(crate) sleep_time: f32,
+    /// Fraction of velocity lost per second
+    pub linear_damping: f32,
+    pub angular_damping: f32,
+    pub gravity_scale: f32,
+    /// Never turns, however it's hit
+    pub fixed_rotation: bool,
+    pub can_sleep: bool,
+    /// Free for the game, e.g. to find the entity the body belongs to
+    pub user_data: u64,
+}
+
+impl RigidBody {
+    pub fn new(body_type: BodyType) -> Self {
+        let mut body = RigidBody {
+            body_type,
+            position: Vector2D::ZERO,
+            angle: 0.0,
+            linear_velocity: Vector2D::ZERO,
+            angular_velocity: 0.0,
+            force: Vector2D::ZERO,
+            torque: 0.0,
+            mass_data: MassData::default(),
+            inverse_mass: 0.0,
+            inverse_inertia: 0.0,
+            awake: true,
+            sleep_time: 0.0,
+            linear_damping: 0.0,
+            angular_damping: 0.0,
+            gravity_scale: 1.0,
+            fixed_rotation: false,
+            can_sleep: true,
+            user_data: 0,
+        };
+        body.set_mass_data(MassData::default());
+        body
+    }
+
+    pub fn with_position(mut self, position: Vector2D) -> Self {
+        self.position = position;
+        self
+    }
+
+    pub fn with_angle(mut self, angle: f32) -> Self {
+        self.angle = angle;
+        self
+    }
+
+    pub fn with_velocity(mut self, linear_velocity: Vector2D) -> Self {
+        self.linear_velocity = linear_velocity;
+        self
+    }
+
+    pub fn with_damping(mut self, linear_damping: f32, angular_damping: f32) -> Self {
+        self.linear_damping = linear_damping;
+        self.angular_damping = angular_damping;
+        self
+    }
+
+    pub fn with_gravity_scale(mut self, gravity_scale: f32) -> Self {
+        self.gravity_scale = gravity_scale;
+        self
+    }
+
+    pub fn with_fixed_rotation(mut self) -> Self {
+        self.fixed_rotation = true;
+        self.set_mass_data(self.mass_data);
+        self
+    }
+
+    pub fn with_user_data(mut self, user_data: u64) -> Self {
+        self.user_data = user_data;
+        self
+    }
+
+    pub fn get_body_type(&self) -> BodyType {
+        self.body_type
+    }
+
+    pub fn set_body_type(&mut self, body_type: BodyType) {
+        self.body_type = body_type;
+        if body_type == BodyType::Static {
+            self.linear_velocity = Vector2D::ZERO;
+            self.angular_velocity = 0.0;
+        }
+        self.set_mass_data(self.mass_data);
+        self.wake();
+    }
+
+    pub fn get_position(&self) -> Vector2D {
+        self.position
+    }
+
+    pub fn set_position(&mut self, position: Vector2D) {
+        self.position = position;
+        self.wake();
+    }
+
+    /// In radians
+    pub fn get_angle(&self) -> f32 {
+        self.angle
+    }
+
+    pub fn set_angle(&mut self, angle: f32) {
+        self.angle = angle;
+        self.wake();
+    }
+
+    /// Where a point on the body is in the world
+    pub fn get_world_point(&self, local: Vector2D) -> Vector2D {
+        Vector2D::from_angle(self.angle).rotate(local) + self.position
+    }
+
+    pub fn get_centre_of_mass(&self) -> Vector2D {
+        self.get_world_point(self.mass_data.centre)
+    }
+
+    pub(crate) fn set_centre_of_mass(&mut self, centre: Vector2D) {
+        self.position = centre - Vector2D::from_angle(self.angle).rotate(self.mass_data.centre);
+    }
+
+    pub fn get_linear_velocity(&self) -> Vector2D {
+        self.linear_velocity
+    }
+
+    pub fn set_linear_velocity(&mut self, velocity: Vector2D) {
+        if self.body_type != BodyType::Static {
+            self.linear_velocity = velocity;
+            self.wake();
+        }
+    }
+
+    /// In radians per second, anticlockwise
+    pub fn get_angular_velocity(&self) -> f32 {
+        self.angular_velocity
+    }
+
+    pub fn set_angular_velocity(&mut self, velocity: f32) {
+        if self.body_type != BodyType::Static {
+            self.angular_velocity = velocity;
+            self.wake();
+        }
+    }
+
+    /// How fast a point in the world, fixed to the body, is moving
+    pub fn get_velocity_at(&self, point: Vector2D) -> Vector2D {
+        let arm = point - self.get_centre_of_mass();
+        self.linear_velocity + arm.perp() * self.angular_velocity
+    }
+
+    pub fn get_mass(&self) -> f32 {
+        self.mass_data.mass
+    }
+
+    pub fn get_mass_data(&self) -> MassData {
+        self.mass_data
+    }
+
+    pub(crate) fn get_inverse_mass(&self) -> f32 {
+        self.inverse_mass
+    }
+
+    pub(crate) fn get_inverse_inertia(&self) -> f32 {
+        self.inverse_inertia
+    }
+
+    /// Normally worked out from the attached colliders.  A dynamic body
+    /// with no mass gets a mass of 1, so it still moves.
+    pub fn set_mass_data(&mut self, mass_data: MassData) {
+        self.mass_data = mass_data;
+        if self.body_type != BodyType::Dynamic {
+            self.inverse_mass = 0.0;
+            self.inverse_inertia = 0.0;
+            return;
+        }
+        if self.mass_data.mass <= 0.0 {
+            self.mass_data.mass = 1.0;
+        }
+        self.inverse_mass = 1.0 / self.mass_data.mass;
+        self.inverse_inertia = if self.fixed_rotation || self.mass_data.inertia <= 0.0 {
+            0.0
+        } else {
+            1.0 / self.mass_data.inertia
+        };
+    }
+
+    /// Pushes on the body until the next update; at `point` in the world, or
+    /// at its centre of mass
+    pub fn apply_force(&mut self, force: Vector2D, point: Option<Vector2D>) {
+        if self.body_type != BodyType::Dynamic {
+            return;
+        }
+        self.force += force;
+        if let Some(point) = point {
+            self.torque += (point - self.get_centre_of_mass()).perp_dot(force);
+        }
+        self.wake();
+    }
+
+    pub fn apply_torque(&mut self, torque: f32) {
+        if self.body_type == BodyType::Dynamic {
+            self.torque += torque;
+            self.wake();
+        }
+    }
+
+    /// Changes the body's velocity at once, as a hit would
+    pub fn apply_impulse(&mut self, impulse: Vector2D, point: Option<Vector2D>) {
+        if self.body_type != BodyType::Dynamic {
+            return;
+        }
+        self.linear_velocity += impulse * self.inverse_mass;
+        if let Some(point) = point {
+            self.angular_velocity +=
+                (point - self.get_centre_of_mass()).perp_dot(impulse) * self.inverse_inertia;
+        }
+        self.wake();
+    }
+
+    pub fn apply_angular_impulse(&mut self, impulse: f32) {
+        if self.body_type == BodyType::Dynamic {
+            self.angular_velocity += impulse * self.inverse_inertia;
+            self.wake();
+        }
+    }
+
+    pub fn is_awake(&self) -> bool {
+        self.awake
+    }
+
+    pub fn wake(&mut self) {
+        self.awake = true;
+        self.sleep_time = 0.0;
+    }
+
+    pub fn sleep(&mut self) {
+        self.awake = false;
+        self.sleep_time = 0.0;
+        self.linear_velocity = Vector2D::ZERO;
+        self.angular_velocity = 0.0;
+        self.force = Vector2D::ZERO;
+        self.torque = 0.0;
+    }
+
+    /// Whether it moves this step: asleep and static bodies don't
+    pub(crate) fn is_moving(&self) -> bool {
+        self.awake && self.body_type != BodyType::Static
+    }
+
+    /// Adds forces and gravity to the velocity, then clears the forces
+    pub(crate) fn integrate_velocity(&mut self, gravity: Vector2D, time_step: f32) {
+        if self.body_type == BodyType::Dynamic && self.awake {
+            let acceleration = gravity * self.gravity_scale + self.force * self.inverse_mass;
+            self.linear_velocity += acceleration * time_step;
+            self.angular_velocity += self.torque * self.inverse_inertia * time_step;
+            self.linear_velocity *= 1.0 / (1.0 + time_step * self.linear_damping);
+            self.angular_velocity *= 1.0 / (1.0 + time_step * self.angular_damping);
+        }
+        self.force = Vector2D::ZERO;
+        self.torque = 0.0;
+    }
+
+    pub(crate) fn integrate_position(&mut self, time_step: f32) {
+        if !self.is_moving() {
+            return;
+        }
+        let centre = self.get_centre_of_mass() + self.linear_velocity * time_step;
+        self.angle += self.angular_velocity * time_step;
+        self.set_centre_of_mass(centre);
+    }
+
+    pub(crate) fn set_velocities(&mut self, linear: Vector2D, angular: f32) {
+        self.linear_velocity = linear;
+        self.angular_velocity = angular;
+    }
+}
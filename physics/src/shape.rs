@@ -0,0 +1,364 @@
+//! Collision shapes, in the collider's local space.  Every shape is handled
+//! as a convex hull of points grown by a radius: a circle is one point, a
+//! capsule two, and boxes and polygons have no radius at all.
+
+use std::f32::consts::PI;
+
+use common::Vector2D;
+
+use crate::rigid_body::MassData;
+
+/// An axis aligned box, for broadphase and region queries
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bounds {
+    pub min: Vector2D,
+    pub max: Vector2D,
+}
+
+impl Bounds {
+    pub fn new(min: Vector2D, max: Vector2D) -> Self {
+        Bounds {
+            min: min.min(max),
+            max: min.max(max),
+        }
+    }
+
+    pub fn from_centre(centre: Vector2D, half_extents: Vector2D) -> Self {
+        Bounds::new(centre - half_extents, centre + half_extents)
+    }
+
+    pub fn get_centre(&self) -> Vector2D {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn get_half_extents(&self) -> Vector2D {
+        (self.max - self.min) * 0.5
+    }
+
+    pub fn overlaps(&self, other: &Bounds) -> bool {
+        self.min.x <= other.max.x
+            && other.min.x <= self.max.x
+            && self.min.y <= other.max.y
+            && other.min.y <= self.max.y
+    }
+
+    pub fn contains(&self, point: Vector2D) -> bool {
+        point.cmpge(self.min).all() && point.cmple(self.max).all()
+    }
+
+    pub fn union(&self, other: &Bounds) -> Bounds {
+        Bounds {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    pub fn grown(&self, amount: f32) -> Bounds {
+        Bounds {
+            min: self.min - Vector2D::splat(amount),
+            max: self.max + Vector2D::splat(amount),
+        }
+    }
+}
+
+/// A convex polygon, wound anticlockwise (with y up)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConvexPolygon {
+    points: Vec<Vector2D>,
+}
+
+impl ConvexPolygon {
+    /// The convex hull of `points`; None if they're all in a line
+    pub fn new(points: &[Vector2D]) -> Option<Self> {
+        let mut sorted: Vec<Vector2D> = points.to_vec();
+        sorted.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+        sorted.dedup();
+        if sorted.len() < 3 {
+            return None;
+        }
+
+        // Andrew's monotone chain
+        let mut hull: Vec<Vector2D> = Vec::with_capacity(sorted.len() * 2);
+        for pass in 0..2 {
+            let start = hull.len();
+            for point in sorted.iter() {
+                while hull.len() >= start + 2
+                    && (hull[hull.len() - 1] - hull[hull.len() - 2])
+                        .perp_dot(*point - hull[hull.len() - 2])
+                        <= 0.0
+                {
+                    hull.pop();
+                }
+                hull.push(*point);
+            }
+            hull.pop();
+            if pass == 0 {
+                sorted.reverse();
+            }
+        }
+        (hull.len() >= 3).then_some(ConvexPolygon { points: hull })
+    }
+
+    pub fn rectangle(half_extents: Vector2D) -> Self {
+        let Vector2D { x, y } = half_extents.abs();
+        ConvexPolygon {
+            points: vec![
+                Vector2D::new(-x, -y),
+                Vector2D::new(x, -y),
+                Vector2D::new(x, y),
+                Vector2D::new(-x, y),
+            ],
+        }
+    }
+
+    pub fn get_points(&self) -> &[Vector2D] {
+        &self.points
+    }
+
+    /// Area and centroid, for working out mass
+    pub fn get_area_centroid(&self) -> (f32, Vector2D) {
+        let origin = self.points[0];
+        let mut area = 0.0;
+        let mut centroid = Vector2D::ZERO;
+        for pair in self.points[1..].windows(2) {
+            let (a, b) = (pair[0] - origin, pair[1] - origin);
+            let triangle = a.perp_dot(b) * 0.5;
+            area += triangle;
+            centroid += (a + b) * (triangle / 3.0);
+        }
+        let centroid = if area > f32::EPSILON {
+            centroid / area + origin
+        } else {
+            origin
+        };
+        (area, centroid)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    Circle {
+        radius: f32,
+    },
+    /// Stays lined up with the world axes however the collider is turned
+    Aabb {
+        half_extents: Vector2D,
+    },
+    /// A box that turns with the collider
+    Box {
+        half_extents: Vector2D,
+    },
+    Polygon(ConvexPolygon),
+    /// A line with no thickness, between two local points
+    Segment {
+        a: Vector2D,
+        b: Vector2D,
+    },
+    /// A segment grown by `radius`, with round ends
+    Capsule {
+        a: Vector2D,
+        b: Vector2D,
+        radius: f32,
+    },
+}
+
+impl Shape {
+    pub fn circle(radius: f32) -> Self {
+        Shape::Circle { radius }
+    }
+
+    pub fn aabb(width: f32, height: f32) -> Self {
+        Shape::Aabb {
+            half_extents: Vector2D::new(width, height) * 0.5,
+        }
+    }
+
+    pub fn rectangle(width: f32, height: f32) -> Self {
+        Shape::Box {
+            half_extents: Vector2D::new(width, height) * 0.5,
+        }
+    }
+
+    /// None if the points are all in a line
+    pub fn polygon(points: &[Vector2D]) -> Option<Self> {
+        ConvexPolygon::new(points).map(Shape::Polygon)
+    }
+
+    pub fn segment(a: Vector2D, b: Vector2D) -> Self {
+        Shape::Segment { a, b }
+    }
+
+    pub fn capsule(a: Vector2D, b: Vector2D, radius: f32) -> Self {
+        Shape::Capsule { a, b, radius }
+    }
+
+    /// Builds the shape's hull, placed at `position` and turned by `angle`
+    pub(crate) fn fill_hull(&self, position: Vector2D, angle: f32, hull: &mut Hull) {
+        let rotation = Vector2D::from_angle(angle);
+        let place = |point: Vector2D| rotation.rotate(point) + position;
+        hull.points.clear();
+        hull.radius = 0.0;
+        match self {
+            Shape::Circle { radius } => {
+                hull.points.push(position);
+                hull.radius = *radius;
+            }
+            Shape::Aabb { half_extents } => {
+                let rectangle = ConvexPolygon::rectangle(*half_extents);
+                hull.points
+                    .extend(rectangle.points.iter().map(|point| *point + position));
+            }
+            Shape::Box { half_extents } => {
+                let rectangle = ConvexPolygon::rectangle(*half_extents);
+                hull.points
+                    .extend(rectangle.points.iter().map(|point| place(*point)));
+            }
+            Shape::Polygon(polygon) => {
+                hull.points
+                    .extend(polygon.points.iter().map(|point| place(*point)));
+            }
+            Shape::Segment { a, b } => {
+                hull.points.extend([place(*a), place(*b)]);
+            }
+            Shape::Capsule { a, b, radius } => {
+                hull.points.extend([place(*a), place(*b)]);
+                hull.radius = *radius;
+            }
+        }
+        if hull.points.len() == 2 && hull.points[0].distance_squared(hull.points[1]) < 1e-12 {
+            hull.points.pop();
+        }
+        hull.update_normals();
+    }
+
+    /// Mass, centroid and inertia in the shape's own space, for `density`
+    /// per unit area.  Segments have no area, so no mass.
+    pub fn get_mass_data(&self, density: f32) -> MassData {
+        let box_mass = |half_extents: Vector2D| {
+            let size = half_extents.abs() * 2.0;
+            let mass = density * size.x * size.y;
+            MassData {
+                mass,
+                centre: Vector2D::ZERO,
+                inertia: mass * size.length_squared() / 12.0,
+            }
+        };
+        match self {
+            Shape::Circle { radius } => {
+                let mass = density * PI * radius * radius;
+                MassData {
+                    mass,
+                    centre: Vector2D::ZERO,
+                    inertia: mass * radius * radius * 0.5,
+                }
+            }
+            Shape::Aabb { half_extents } | Shape::Box { half_extents } => box_mass(*half_extents),
+            Shape::Polygon(polygon) => {
+                let (area, centre) = polygon.get_area_centroid();
+                let mass = density * area;
+                // a fan of triangles from the centroid
+                let mut inertia = 0.0;
+                for index in 0..polygon.points.len() {
+                    let a = polygon.points[index] - centre;
+                    let b = polygon.points[(index + 1) % polygon.points.len()] - centre;
+                    inertia += a.perp_dot(b) * (a.dot(a) + a.dot(b) + b.dot(b)) / 12.0;
+                }
+                MassData {
+                    mass,
+                    centre,
+                    inertia: density * inertia,
+                }
+            }
+            Shape::Segment { a, b } => MassData {
+                mass: 0.0,
+                centre: (*a + *b) * 0.5,
+                inertia: 0.0,
+            },
+            Shape::Capsule { a, b, radius } => {
+                let length = a.distance(*b);
+                let middle = density * length * radius * 2.0;
+                let ends = density * PI * radius * radius;
+                MassData {
+                    mass: middle + ends,
+                    centre: (*a + *b) * 0.5,
+                    inertia: middle * (length * length + 4.0 * radius * radius) / 12.0
+                        + ends * (radius * radius * 0.5 + length * length * 0.25),
+                }
+            }
+        }
+    }
+
+    pub fn get_bounds(&self, position: Vector2D, angle: f32) -> Bounds {
+        let mut hull = Hull::default();
+        self.fill_hull(position, angle, &mut hull);
+        hull.get_bounds()
+    }
+}
+
+/// A shape placed in the world: a convex hull of points, grown by `radius`.
+/// Edge `i` runs from point `i` to the next, with `normals[i]` facing out.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Hull {
+    pub(crate) points: Vec<Vector2D>,
+    pub(crate) normals: Vec<Vector2D>,
+    pub(crate) radius: f32,
+}
+
+impl Hull {
+    fn update_normals(&mut self) {
+        self.normals.clear();
+        if self.points.len() < 2 {
+            return;
+        }
+        for index in 0..self.points.len() {
+            let edge = self.get_edge(index);
+            let direction = edge.1 - edge.0;
+            self.normals
+                .push(Vector2D::new(direction.y, -direction.x).normalize_or_zero());
+        }
+    }
+
+    pub(crate) fn get_edge(&self, index: usize) -> (Vector2D, Vector2D) {
+        (
+            self.points[index],
+            self.points[(index + 1) % self.points.len()],
+        )
+    }
+
+    pub(crate) fn get_bounds(&self) -> Bounds {
+        let (min, max) = self.points.iter().fold(
+            (Vector2D::splat(f32::MAX), Vector2D::splat(f32::MIN)),
+            |(min, max), point| (min.min(*point), max.max(*point)),
+        );
+        Bounds { min, max }.grown(self.radius)
+    }
+
+    /// How far a point is outside the hull's surface, negative inside
+    pub(crate) fn get_distance_to(&self, point: Vector2D) -> f32 {
+        let count = self.points.len();
+        let core = match count {
+            0 => return f32::MAX,
+            1 => self.points[0].distance(point),
+            _ => {
+                let faces =
+                    (0..count).map(|index| (point - self.points[index]).dot(self.normals[index]));
+                let deepest = faces.fold(f32::MIN, f32::max);
+                if count > 2 && deepest <= 0.0 {
+                    deepest
+                } else {
+                    (0..count)
+                        .map(|index| {
+                            let (start, end) = self.get_edge(index);
+                            let edge = end - start;
+                            let along = ((point - start).dot(edge)
+                                / edge.length_squared().max(f32::EPSILON))
+                            .clamp(0.0, 1.0);
+                            point.distance(start + edge * along)
+                        })
+                        .fold(f32::MAX, f32::min)
+                }
+            }
+        };
+        core - self.radius
+    }
+}
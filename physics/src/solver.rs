@@ -0,0 +1,230 @@
+//! Sequential impulses: each contact point pushes its two bodies apart just
+//! enough to stop them closing, over several passes so stacks settle.
+//! Impulses are remembered between steps and reapplied up front, which
+//! steadies resting contacts.
+
+use std::collections::HashMap;
+
+use common::Vector2D;
+
+use crate::{collider::ColliderHandle, contact::Manifold};
+
+/// Speeds and distances are in world units, so they may need scaling to
+/// suit the world; the defaults suit metres.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SolverSettings {
+    /// Passes over the contacts each step; more makes stacks stiffer
+    pub iterations: usize,
+    /// The longest step taken; longer updates are split into several steps
+    pub max_step: f32,
+    /// More steps than this in an update and the world runs slow instead
+    pub max_steps: usize,
+    /// How much of any overlap is pushed out each step, from 0 to 1
+    pub position_correction: f32,
+    /// Overlap left alone, so resting contacts don't jitter
+    pub slop: f32,
+    /// Slower impacts than this don't bounce
+    pub restitution_threshold: f32,
+    /// Bodies slower than these for `time_to_sleep` seconds go to sleep
+    pub sleep_linear_speed: f32,
+    pub sleep_angular_speed: f32,
+    pub time_to_sleep: f32,
+}
+
+impl Default for SolverSettings {
+    fn default() -> Self {
+        SolverSettings {
+            iterations: 8,
+            max_step: 1.0 / 60.0,
+            max_steps: 4,
+            position_correction: 0.2,
+            slop: 0.01,
+            restitution_threshold: 1.0,
+            sleep_linear_speed: 0.05,
+            sleep_angular_speed: 2.0f32.to_radians(),
+            time_to_sleep: 0.5,
+        }
+    }
+}
+
+/// A body's motion while solving.  Static bodies, and colliders without a
+/// body, are the default: still, and impossible to push.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct BodyState {
+    pub(crate) linear: Vector2D,
+    pub(crate) angular: f32,
+    pub(crate) inverse_mass: f32,
+    pub(crate) inverse_inertia: f32,
+    pub(crate) centre: Vector2D,
+}
+
+impl BodyState {
+    fn velocity_at(&self, arm: Vector2D) -> Vector2D {
+        self.linear + arm.perp() * self.angular
+    }
+
+    fn apply(&mut self, arm: Vector2D, impulse: Vector2D) {
+        self.linear += impulse * self.inverse_mass;
+        self.angular += arm.perp_dot(impulse) * self.inverse_inertia;
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct ConstraintPoint {
+    id: u32,
+    arms: [Vector2D; 2],
+    normal_mass: f32,
+    tangent_mass: f32,
+    normal_impulse: f32,
+    tangent_impulse: f32,
+    bias: f32,
+}
+
+/// Impulses from last step, by contact point id
+pub(crate) type ImpulseCache = HashMap<(ColliderHandle, ColliderHandle), [(u32, f32, f32); 2]>;
+
+#[derive(Debug, Clone)]
+pub(crate) struct ContactConstraint {
+    key: (ColliderHandle, ColliderHandle),
+    bodies: [Option<usize>; 2],
+    normal: Vector2D,
+    friction: f32,
+    points: [ConstraintPoint; 2],
+    point_count: usize,
+}
+
+impl ContactConstraint {
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        key: (ColliderHandle, ColliderHandle),
+        bodies: [Option<usize>; 2],
+        manifold: &Manifold,
+        friction: f32,
+        restitution: f32,
+        states: &[BodyState],
+        settings: &SolverSettings,
+        time_step: f32,
+    ) -> Self {
+        let state = |body: Option<usize>| body.map_or(BodyState::default(), |body| states[body]);
+        let (a, b) = (state(bodies[0]), state(bodies[1]));
+        let normal = manifold.normal;
+        let tangent = -normal.perp();
+        let mut constraint = ContactConstraint {
+            key,
+            bodies,
+            normal,
+            friction,
+            points: [ConstraintPoint::default(); 2],
+            point_count: 0,
+        };
+        for contact in manifold.get_points() {
+            let arms = [contact.position - a.centre, contact.position - b.centre];
+            let effective_mass = |direction: Vector2D| {
+                let mass = a.inverse_mass
+                    + b.inverse_mass
+                    + a.inverse_inertia * arms[0].perp_dot(direction).powi(2)
+                    + b.inverse_inertia * arms[1].perp_dot(direction).powi(2);
+                if mass > 0.0 { 1.0 / mass } else { 0.0 }
+            };
+
+            // bounce off fast impacts, and push out of any overlap
+            let closing = (b.velocity_at(arms[1]) - a.velocity_at(arms[0])).dot(normal);
+            let bounce = if closing < -settings.restitution_threshold {
+                -restitution * closing
+            } else {
+                0.0
+            };
+            let push =
+                settings.position_correction / time_step * (contact.depth - settings.slop).max(0.0);
+
+            constraint.points[constraint.point_count] = ConstraintPoint {
+                id: contact.id,
+                arms,
+                normal_mass: effective_mass(normal),
+                tangent_mass: effective_mass(tangent),
+                normal_impulse: 0.0,
+                tangent_impulse: 0.0,
+                bias: bounce.max(push),
+            };
+            constraint.point_count += 1;
+        }
+        constraint
+    }
+
+    fn points_mut(&mut self) -> &mut [ConstraintPoint] {
+        &mut self.points[..self.point_count]
+    }
+
+    fn states(&self, states: &[BodyState]) -> (BodyState, BodyState) {
+        let state = |body: Option<usize>| body.map_or(BodyState::default(), |body| states[body]);
+        (state(self.bodies[0]), state(self.bodies[1]))
+    }
+
+    fn store_states(&self, states: &mut [BodyState], a: BodyState, b: BodyState) {
+        if let Some(body) = self.bodies[0] {
+            states[body] = a;
+        }
+        if let Some(body) = self.bodies[1] {
+            states[body] = b;
+        }
+    }
+
+    /// Reapplies last step's impulses for points that are still there
+    pub(crate) fn warm_start(&mut self, cache: &ImpulseCache, states: &mut [BodyState]) {
+        let Some(cached) = cache.get(&self.key) else {
+            return;
+        };
+        let (mut a, mut b) = self.states(states);
+        let (normal, tangent) = (self.normal, -self.normal.perp());
+        for point in self.points_mut() {
+            if let Some((_, normal_impulse, tangent_impulse)) =
+                cached.iter().find(|(id, _, _)| *id == point.id)
+            {
+                point.normal_impulse = *normal_impulse;
+                point.tangent_impulse = *tangent_impulse;
+                let impulse = normal * point.normal_impulse + tangent * point.tangent_impulse;
+                a.apply(point.arms[0], -impulse);
+                b.apply(point.arms[1], impulse);
+            }
+        }
+        self.store_states(states, a, b);
+    }
+
+    pub(crate) fn solve(&mut self, states: &mut [BodyState]) {
+        let (mut a, mut b) = self.states(states);
+        let (normal, tangent, friction) = (self.normal, -self.normal.perp(), self.friction);
+        for point in self.points_mut() {
+            // friction first, as it's the less important of the two
+            let relative = b.velocity_at(point.arms[1]) - a.velocity_at(point.arms[0]);
+            let limit = friction * point.normal_impulse;
+            let total = (point.tangent_impulse - relative.dot(tangent) * point.tangent_mass)
+                .clamp(-limit, limit);
+            let impulse = tangent * (total - point.tangent_impulse);
+            point.tangent_impulse = total;
+            a.apply(point.arms[0], -impulse);
+            b.apply(point.arms[1], impulse);
+
+            // contacts can push but never pull
+            let relative = b.velocity_at(point.arms[1]) - a.velocity_at(point.arms[0]);
+            let total = (point.normal_impulse
+                + (point.bias - relative.dot(normal)) * point.normal_mass)
+                .max(0.0);
+            let impulse = normal * (total - point.normal_impulse);
+            point.normal_impulse = total;
+            a.apply(point.arms[0], -impulse);
+            b.apply(point.arms[1], impulse);
+        }
+        self.store_states(states, a, b);
+    }
+
+    pub(crate) fn store_impulses(&self, cache: &mut ImpulseCache) {
+        let mut impulses = [(u32::MAX, 0.0, 0.0); 2];
+        for (stored, point) in impulses
+            .iter_mut()
+            .zip(self.points[..self.point_count].iter())
+        {
+            *stored = (point.id, point.normal_impulse, point.tangent_impulse);
+        }
+        cache.insert(self.key, impulses);
+    }
+}
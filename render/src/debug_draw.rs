@@ -0,0 +1,359 @@
+//! Immediate-mode debug drawing, callable from anywhere on the main thread.
+//! Shapes are queued with a lifetime in seconds (0 for a single frame), and
+//! drawn over the top of the game's scene by the `Screen`.  In release builds
+//! every call is a no-op.
+//!
+//! World positions are drawn through the transform set with
+//! `set_world_transform` (the game's camera, if it has one); screen positions
+//! are in logical screen coordinates and ignore it.
+
+use std::cell::RefCell;
+
+use common::{Transform2D, VScolor, Vector2D};
+
+use super::{
+    display_list::{DisplayList, Geometry, PrimitiveType},
+    material::Material,
+};
+
+const COMPILED_IN: bool = cfg!(debug_assertions);
+const CIRCLE_SEGMENTS: usize = 24;
+const ARROW_HEAD_FRACTION: f32 = 0.2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugSpace {
+    World,
+    Screen,
+}
+
+#[derive(Debug, Clone)]
+struct DebugShape {
+    space: DebugSpace,
+    color: VScolor,
+    lifetime: f32,
+    lines: Vec<(Vector2D, Vector2D)>,
+}
+
+struct DebugDraw {
+    enabled: bool,
+    world_transform: Transform2D,
+    shapes: Vec<DebugShape>,
+}
+
+thread_local! {
+    static DEBUG_DRAW: RefCell<DebugDraw> = const {
+        RefCell::new(DebugDraw {
+            enabled: COMPILED_IN,
+            world_transform: Transform2D::IDENTITY,
+            shapes: Vec::new(),
+        })
+    };
+}
+
+pub fn set_enabled(enabled: bool) {
+    DEBUG_DRAW.with_borrow_mut(|debug| debug.enabled = COMPILED_IN && enabled);
+}
+
+pub fn is_enabled() -> bool {
+    DEBUG_DRAW.with_borrow(|debug| debug.enabled)
+}
+
+pub fn set_world_transform(transform: Transform2D) {
+    DEBUG_DRAW.with_borrow_mut(|debug| debug.world_transform = transform);
+}
+
+/// Removes every queued shape, however long it had left
+pub fn clear() {
+    DEBUG_DRAW.with_borrow_mut(|debug| debug.shapes.clear());
+}
+
+pub fn line(space: DebugSpace, from: Vector2D, to: Vector2D, color: VScolor, lifetime: f32) {
+    add_shape(space, color, lifetime, || vec![(from, to)]);
+}
+
+pub fn arrow(space: DebugSpace, from: Vector2D, to: Vector2D, color: VScolor, lifetime: f32) {
+    add_shape(space, color, lifetime, || {
+        let back = (from - to) * ARROW_HEAD_FRACTION;
+        let side = back.perp() * 0.5;
+        vec![(from, to), (to, to + back + side), (to, to + back - side)]
+    });
+}
+
+pub fn circle(space: DebugSpace, center: Vector2D, radius: f32, color: VScolor, lifetime: f32) {
+    add_shape(space, color, lifetime, || {
+        let points: Vec<Vector2D> = (0..CIRCLE_SEGMENTS)
+            .map(|i| {
+                let angle = i as f32 * std::f32::consts::TAU / CIRCLE_SEGMENTS as f32;
+                center + Vector2D::from_angle(angle) * radius
+            })
+            .collect();
+        loop_lines(&points)
+    });
+}
+
+/// An axis-aligned box
+pub fn rect(space: DebugSpace, min: Vector2D, max: Vector2D, color: VScolor, lifetime: f32) {
+    add_shape(space, color, lifetime, || {
+        loop_lines(&[
+            min,
+            Vector2D::new(max.x, min.y),
+            max,
+            Vector2D::new(min.x, max.y),
+        ])
+    });
+}
+
+/// A closed outline, such as a collision polygon
+pub fn polygon(space: DebugSpace, points: &[Vector2D], color: VScolor, lifetime: f32) {
+    add_shape(space, color, lifetime, || loop_lines(points));
+}
+
+pub fn cross(space: DebugSpace, center: Vector2D, size: f32, color: VScolor, lifetime: f32) {
+    add_shape(space, color, lifetime, || {
+        let half = size * 0.5;
+        vec![
+            (
+                center - Vector2D::new(half, half),
+                center + Vector2D::new(half, half),
+            ),
+            (
+                center - Vector2D::new(half, -half),
+                center + Vector2D::new(half, -half),
+            ),
+        ]
+    });
+}
+
+/// Text in a simple stroke font, with its top left at `position`, `size` tall.
+/// Lowercase letters are drawn as capitals.
+pub fn text(
+    space: DebugSpace,
+    position: Vector2D,
+    size: f32,
+    text: &str,
+    color: VScolor,
+    lifetime: f32,
+) {
+    add_shape(space, color, lifetime, || text_lines(position, size, text));
+}
+
+/// Appends every queued shape to the end of `list`, so they sit over the scene.
+/// Any transforms the game left pushed are popped first, so screen shapes are
+/// always in screen space.
+pub fn draw_overlay(list: &mut DisplayList) {
+    DEBUG_DRAW.with_borrow(|debug| {
+        if !debug.enabled || debug.shapes.is_empty() {
+            return;
+        }
+
+        for _ in 0..list.get_transform_depth() {
+            list.pop_transform();
+        }
+        list.set_material(Material::default());
+        list.set_color(VScolor::WHITE);
+        for space in [DebugSpace::World, DebugSpace::Screen] {
+            let world = space == DebugSpace::World && !debug.world_transform.is_identity();
+            if world {
+                list.push_transform(debug.world_transform);
+            }
+
+            let mut geometry = Geometry::new(PrimitiveType::Lines);
+            for shape in debug.shapes.iter().filter(|s| s.space == space) {
+                for (from, to) in shape.lines.iter() {
+                    if geometry.vertices.len() + 2 > u16::MAX as usize {
+                        list.draw(std::mem::replace(
+                            &mut geometry,
+                            Geometry::new(PrimitiveType::Lines),
+                        ));
+                    }
+                    let base = geometry.vertices.len() as u16;
+                    geometry.vertices.extend([*from, *to]);
+                    geometry.colors.extend([shape.color, shape.color]);
+                    geometry.indices.extend([base, base + 1]);
+                }
+            }
+            list.draw(geometry);
+
+            if world {
+                list.pop_transform();
+            }
+        }
+    });
+}
+
+/// Ages every shape by `delta` seconds, dropping those which have expired.  Called
+/// after each frame is drawn, so every shape is drawn at least once.
+pub fn advance(delta: f32) {
+    DEBUG_DRAW.with_borrow_mut(|debug| {
+        for shape in debug.shapes.iter_mut() {
+            shape.lifetime -= delta;
+        }
+        debug.shapes.retain(|shape| shape.lifetime > 0.0);
+    });
+}
+
+pub fn shape_count() -> usize {
+    DEBUG_DRAW.with_borrow(|debug| debug.shapes.len())
+}
+
+// the lines are only built when debug drawing is on
+fn add_shape(
+    space: DebugSpace,
+    color: VScolor,
+    lifetime: f32,
+    lines: impl FnOnce() -> Vec<(Vector2D, Vector2D)>,
+) {
+    if !COMPILED_IN {
+        return;
+    }
+    DEBUG_DRAW.with_borrow_mut(|debug| {
+        if debug.enabled {
+            debug.shapes.push(DebugShape {
+                space,
+                color,
+                lifetime,
+                lines: lines(),
+            });
+        }
+    });
+}
+
+fn loop_lines(points: &[Vector2D]) -> Vec<(Vector2D, Vector2D)> {
+    points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(from, to)| (*from, *to))
+        .collect()
+}
+
+// Glyphs are polylines on a 3x5 grid, with (0,0) at the top left.  Each
+// polyline is a run of "xy" digit pairs; polylines are separated by spaces.
+fn glyph(c: char) -> &'static str {
+    match c.to_ascii_uppercase() {
+        '0' => "0020240400 0420",
+        '1' => "011014 0424",
+        '2' => "002022020424",
+        '3' => "00202404 0222",
+        '4' => "000222 2024",
+        '5' | 'S' => "200002222404",
+        '6' => "200004242202",
+        '7' => "002014",
+        '8' => "0020240400 0222",
+        '9' => "220200202404",
+        'A' => "0401102124 0222",
+        'B' => "0010211223140400 0212",
+        'C' => "20000424",
+        'D' => "00102123140400",
+        'E' => "20000424 0212",
+        'F' => "200004 0212",
+        'G' => "200004242212",
+        'H' => "0004 2024 0222",
+        'I' => "0020 1014 0424",
+        'J' => "20241403",
+        'K' => "0004 200224",
+        'L' => "000424",
+        'M' => "0400112024",
+        'N' => "04002420",
+        'O' => "0020240400",
+        'P' => "0400202202",
+        'Q' => "0020240400 1224",
+        'R' => "0400202202 1224",
+        'T' => "0020 1014",
+        'U' => "00042420",
+        'V' => "001420",
+        'W' => "0004132420",
+        'X' => "0024 2004",
+        'Y' => "001120 1114",
+        'Z' => "00200424",
+        ' ' => "",
+        '.' => "1314",
+        ',' => "1304",
+        ':' => "1011 1314",
+        '-' => "0222",
+        '+' => "0222 1113",
+        '=' => "0121 0323",
+        '/' => "0420",
+        '(' => "100214",
+        ')' => "102214",
+        '[' => "10000414",
+        ']' => "10202414",
+        '<' => "200224",
+        '>' => "002204",
+        '_' => "0424",
+        '!' => "1012 1314",
+        '\'' => "1011",
+        '"' => "0001 2021",
+        '*' => "0123 2103",
+        _ => "00202212 1314", // '?'
+    }
+}
+
+fn text_lines(position: Vector2D, size: f32, text: &str) -> Vec<(Vector2D, Vector2D)> {
+    let scale = size / 4.0;
+    let mut lines = Vec::new();
+    let mut cursor = position;
+    for c in text.chars() {
+        if c == '\n' {
+            cursor = Vector2D::new(position.x, cursor.y + 6.0 * scale);
+            continue;
+        }
+
+        for polyline in glyph(c).split_whitespace() {
+            let points: Vec<Vector2D> = polyline
+                .as_bytes()
+                .chunks_exact(2)
+                .map(|xy| {
+                    cursor + Vector2D::new((xy[0] - b'0') as f32, (xy[1] - b'0') as f32) * scale
+                })
+                .collect();
+            lines.extend(points.windows(2).map(|pair| (pair[0], pair[1])));
+        }
+        cursor.x += 3.0 * scale;
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display_list::DisplayListOp;
+
+    #[test]
+    fn shapes_expire_after_drawing() {
+        clear();
+        set_world_transform(Transform2D::from_position(Vector2D::new(5.0, 0.0)));
+        cross(DebugSpace::World, Vector2D::ZERO, 1.0, VScolor::RED, 0.0);
+        text(
+            DebugSpace::Screen,
+            Vector2D::ZERO,
+            4.0,
+            "OK",
+            VScolor::WHITE,
+            1.0,
+        );
+        assert_eq!(shape_count(), 2);
+
+        // a transform the game forgot to pop doesn't move the overlay
+        let mut list = DisplayList::new();
+        list.push_transform(Transform2D::from_position(Vector2D::new(0.0, 9.0)));
+        draw_overlay(&mut list);
+        assert!(matches!(list.ops()[1], DisplayListOp::PopTransform));
+        assert!(matches!(list.ops()[4], DisplayListOp::PushTransform(..)));
+        assert_eq!(list.get_transform_depth(), 0);
+        let draws: Vec<usize> = list
+            .ops()
+            .iter()
+            .filter_map(|op| match op {
+                DisplayListOp::Draw(geometry) => Some(geometry.vertices.len()),
+                _ => None,
+            })
+            .collect();
+        // a cross is two lines; 'O' is four and 'K' is three
+        assert_eq!(draws, vec![4, 14]);
+
+        advance(0.5);
+        assert_eq!(shape_count(), 1);
+        advance(0.5);
+        assert_eq!(shape_count(), 0);
+    }
+}
@@ -1,21 +1,123 @@
+use common::{Transform2D, VScolor, Vector2D};
 
+use super::material::Material;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PrimitiveType {
+    Points,
+    Lines,
+    #[default]
+    Triangles,
+}
+
+/// A single batch of vertex data.  Colors and texels are optional; when present
+/// they must have one entry per vertex.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Geometry {
+    pub primitive: PrimitiveType,
+    pub vertices: Vec<Vector2D>,
+    pub colors: Vec<VScolor>,
+    pub texels: Vec<Vector2D>,
+    pub indices: Vec<u16>,
+}
+
+impl Geometry {
+    pub fn new(primitive: PrimitiveType) -> Self {
+        Geometry {
+            primitive,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    pub fn is_valid(&self) -> bool {
+        (self.colors.is_empty() || self.colors.len() == self.vertices.len())
+            && (self.texels.is_empty() || self.texels.len() == self.vertices.len())
+            && self
+                .indices
+                .iter()
+                .all(|i| (*i as usize) < self.vertices.len())
+    }
+}
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum DisplayListOp {
+    SetColor(VScolor),
+    SetMaterial(Material),
+    PushTransform(Transform2D),
+    PopTransform,
+    Draw(Geometry),
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct DisplayList {
-    // TODO
+    ops: Vec<DisplayListOp>,
 }
 
 impl DisplayList {
-    pub fn new ( ) -> Self {
-        DisplayList {
+    pub fn new() -> Self {
+        DisplayList { ops: Vec::new() }
+    }
 
-        }
+    pub fn clear(&mut self) {
+        self.ops.clear();
     }
 
-}
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    pub fn ops(&self) -> &[DisplayListOp] {
+        &self.ops
+    }
+
+    pub fn push_op(&mut self, op: DisplayListOp) {
+        self.ops.push(op);
+    }
+
+    pub fn append(&mut self, other: &DisplayList) {
+        self.ops.extend(other.ops.iter().cloned());
+    }
+
+    pub fn set_color(&mut self, color: VScolor) {
+        self.ops.push(DisplayListOp::SetColor(color));
+    }
 
-impl Default for DisplayList {
-    fn default ( ) -> Self {
-        Self::new()
+    pub fn set_material(&mut self, material: Material) {
+        self.ops.push(DisplayListOp::SetMaterial(material));
+    }
+
+    pub fn push_transform(&mut self, transform: Transform2D) {
+        self.ops.push(DisplayListOp::PushTransform(transform));
+    }
+
+    pub fn pop_transform(&mut self) {
+        self.ops.push(DisplayListOp::PopTransform);
+    }
+
+    /// How many pushed transforms are still in effect at the end of the list
+    pub fn get_transform_depth(&self) -> usize {
+        self.ops.iter().fold(0, |depth, op| match op {
+            DisplayListOp::PushTransform(..) => depth + 1,
+            DisplayListOp::PopTransform => depth.saturating_sub(1),
+            _ => depth,
+        })
+    }
+
+    pub fn draw(&mut self, geometry: Geometry) {
+        debug_assert!(
+            geometry.is_valid(),
+            "invalid geometry added to display list"
+        );
+        if !geometry.is_empty() {
+            self.ops.push(DisplayListOp::Draw(geometry));
+        }
     }
 }
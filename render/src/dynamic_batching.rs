@@ -0,0 +1,267 @@
+use common::{Affine2D, Transform2D, VScolor};
+
+use super::{
+    display_list::{DisplayList, DisplayListOp, Geometry},
+    material::Material,
+};
+
+const MAX_BATCH_VERTICES: usize = u16::MAX as usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BatchStats {
+    pub draws_before: u32,
+    pub draws_after: u32,
+}
+
+impl BatchStats {
+    /// Stats for a list which is rendered without batching
+    pub fn unbatched(list: &DisplayList) -> Self {
+        let draws = count_draws(list);
+        BatchStats {
+            draws_before: draws,
+            draws_after: draws,
+        }
+    }
+
+    pub fn draws_saved(&self) -> u32 {
+        self.draws_before.saturating_sub(self.draws_after)
+    }
+}
+
+/// Merges runs of small draws which share a material into single draws.  Small
+/// draws have their transform and color baked into their vertices, so that
+/// sprites and particles which each push their own transform can still be merged.
+#[derive(Debug, Clone, Copy)]
+pub struct DynamicBatcher {
+    pub max_draw_vertices: usize, // draws larger than this are passed through untouched
+}
+
+impl Default for DynamicBatcher {
+    fn default() -> Self {
+        DynamicBatcher {
+            max_draw_vertices: 64,
+        }
+    }
+}
+
+struct PendingBatch {
+    depth: usize, // number of emitted transforms the batch sits under
+    material: Material,
+    geometry: Geometry,
+}
+
+struct StackEntry {
+    transform: Transform2D,
+    emitted: bool,
+}
+
+#[derive(Default)]
+struct BatchState {
+    output: DisplayList,
+    // emitted entries always form a prefix of the stack; the deferred suffix is baked into small draws
+    stack: Vec<StackEntry>,
+    material: Material,
+    color: VScolor,
+    emitted_material: Option<Material>,
+    emitted_color: Option<VScolor>,
+    pending: Option<PendingBatch>,
+}
+
+impl DynamicBatcher {
+    pub fn batch(&self, list: &DisplayList) -> (DisplayList, BatchStats) {
+        let mut state = BatchState {
+            color: VScolor::WHITE,
+            ..Default::default()
+        };
+
+        for op in list.ops() {
+            match op {
+                DisplayListOp::SetColor(color) => state.color = *color,
+                DisplayListOp::SetMaterial(material) => state.material = material.clone(),
+                DisplayListOp::PushTransform(transform) => state.stack.push(StackEntry {
+                    transform: *transform,
+                    emitted: false,
+                }),
+                DisplayListOp::PopTransform => {
+                    if state.stack.pop().is_some_and(|entry| entry.emitted) {
+                        state.flush();
+                        state.output.pop_transform();
+                    }
+                }
+                DisplayListOp::Draw(geometry) => {
+                    if geometry.vertices.len() <= self.max_draw_vertices {
+                        state.add_small_draw(geometry);
+                    } else {
+                        state.flush();
+                        state.emit_transforms();
+                        state.emit_state(state.material.clone(), Some(state.color));
+                        state.output.draw(geometry.clone());
+                    }
+                }
+            }
+        }
+        state.flush();
+
+        let stats = BatchStats {
+            draws_before: count_draws(list),
+            draws_after: count_draws(&state.output),
+        };
+        (state.output, stats)
+    }
+}
+
+impl BatchState {
+    fn emitted_depth(&self) -> usize {
+        self.stack.iter().take_while(|entry| entry.emitted).count()
+    }
+
+    fn deferred_transform(&self) -> Affine2D {
+        self.stack
+            .iter()
+            .filter(|entry| !entry.emitted)
+            .fold(Affine2D::IDENTITY, |acc, entry| {
+                acc * entry.transform.to_affine()
+            })
+    }
+
+    fn emit_transforms(&mut self) {
+        for entry in self.stack.iter_mut().filter(|entry| !entry.emitted) {
+            self.output.push_transform(entry.transform);
+            entry.emitted = true;
+        }
+    }
+
+    fn emit_state(&mut self, material: Material, color: Option<VScolor>) {
+        if self.emitted_material.as_ref() != Some(&material) {
+            self.output.set_material(material.clone());
+            self.emitted_material = Some(material);
+        }
+        if let Some(color) = color
+            && self.emitted_color != Some(color)
+        {
+            self.output.set_color(color);
+            self.emitted_color = Some(color);
+        }
+    }
+
+    fn add_small_draw(&mut self, geometry: &Geometry) {
+        let depth = self.emitted_depth();
+        let compatible = self.pending.as_ref().is_some_and(|pending| {
+            pending.depth == depth
+                && pending.material == self.material
+                && pending.geometry.primitive == geometry.primitive
+                && pending.geometry.texels.is_empty() == geometry.texels.is_empty()
+                && pending.geometry.vertices.len() + geometry.vertices.len() <= MAX_BATCH_VERTICES
+        });
+        if !compatible {
+            self.flush();
+            self.pending = Some(PendingBatch {
+                depth,
+                material: self.material.clone(),
+                geometry: Geometry::new(geometry.primitive),
+            });
+        }
+
+        let transform = self.deferred_transform();
+        let color = self.color;
+        let pending = self
+            .pending
+            .as_mut()
+            .expect("no pending batch in dynamic batcher");
+        let base = pending.geometry.vertices.len() as u16;
+        pending.geometry.vertices.extend(
+            geometry
+                .vertices
+                .iter()
+                .map(|v| transform.transform_point2(*v)),
+        );
+        if geometry.colors.is_empty() {
+            pending
+                .geometry
+                .colors
+                .extend(std::iter::repeat_n(color, geometry.vertices.len()));
+        } else {
+            pending.geometry.colors.extend_from_slice(&geometry.colors);
+        }
+        pending.geometry.texels.extend_from_slice(&geometry.texels);
+        pending
+            .geometry
+            .indices
+            .extend(geometry.indices.iter().map(|i| i + base));
+    }
+
+    fn flush(&mut self) {
+        if let Some(pending) = self.pending.take() {
+            // vertex colors are baked in, so the current color doesn't matter to the batch
+            self.emit_state(pending.material, None);
+            self.output.draw(pending.geometry);
+        }
+    }
+}
+
+fn count_draws(list: &DisplayList) -> u32 {
+    list.ops()
+        .iter()
+        .filter(|op| matches!(op, DisplayListOp::Draw(..)))
+        .count() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display_list::PrimitiveType;
+    use common::Vector2D;
+
+    fn quad() -> Geometry {
+        Geometry {
+            primitive: PrimitiveType::Triangles,
+            vertices: vec![
+                Vector2D::new(0.0, 0.0),
+                Vector2D::new(1.0, 0.0),
+                Vector2D::new(1.0, 1.0),
+                Vector2D::new(0.0, 1.0),
+            ],
+            indices: vec![0, 1, 2, 0, 2, 3],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn merges_transformed_quads() {
+        let mut list = DisplayList::new();
+        for x in 0..10 {
+            list.push_transform(Transform2D::from_position(Vector2D::new(
+                x as f32 * 2.0,
+                0.0,
+            )));
+            list.set_color(VScolor::RED);
+            list.draw(quad());
+            list.pop_transform();
+        }
+
+        let (batched, stats) = DynamicBatcher::default().batch(&list);
+        assert_eq!(stats.draws_before, 10);
+        assert_eq!(stats.draws_after, 1);
+
+        let Some(DisplayListOp::Draw(geometry)) = batched.ops().last() else {
+            panic!("batched list should end with a draw");
+        };
+        assert_eq!(geometry.vertices.len(), 40);
+        assert_eq!(geometry.vertices[36], Vector2D::new(18.0, 0.0));
+        assert_eq!(geometry.indices[59], 39);
+        assert!(geometry.colors.iter().all(|c| *c == VScolor::RED));
+    }
+
+    #[test]
+    fn material_change_splits_batch() {
+        let mut list = DisplayList::new();
+        list.draw(quad());
+        list.set_material(Material::new(None, crate::material::DrawMode::Additive));
+        list.draw(quad());
+        list.draw(quad());
+
+        let (_, stats) = DynamicBatcher::default().batch(&list);
+        assert_eq!(stats.draws_before, 3);
+        assert_eq!(stats.draws_after, 2);
+    }
+}
@@ -0,0 +1,110 @@
+use std::{fs::File, io::BufWriter, path::Path};
+
+use common::vs_error::VSError;
+use log::{error, info};
+
+/// An RGBA8 copy of a rendered frame, with the top row first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapturedFrame {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl CapturedFrame {
+    pub fn new(width: u32, height: u32, pixels: Vec<u8>) -> Self {
+        assert_eq!(
+            pixels.len(),
+            (width * height * 4) as usize,
+            "captured frame pixel data does not match its size"
+        );
+        CapturedFrame {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Read-backs arrive bottom row first; this puts them the right way up
+    pub fn flip_vertical(&mut self) {
+        let row_size = (self.width * 4) as usize;
+        let height = self.height as usize;
+        for row in 0..height / 2 {
+            let (top, bottom) = self.pixels.split_at_mut((height - row - 1) * row_size);
+            top[row * row_size..(row + 1) * row_size].swap_with_slice(&mut bottom[..row_size]);
+        }
+    }
+
+    /// Box-filters the frame down by `factor`, for supersampled captures
+    pub fn downsampled(&self, factor: u32) -> CapturedFrame {
+        if factor <= 1 {
+            return self.clone();
+        }
+
+        let width = self.width / factor;
+        let height = self.height / factor;
+        let samples = factor * factor;
+        let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let mut sum = [0u32; 4];
+                for sy in 0..factor {
+                    for sx in 0..factor {
+                        let source =
+                            (((y * factor + sy) * self.width + (x * factor + sx)) * 4) as usize;
+                        for (channel, total) in sum.iter_mut().enumerate() {
+                            *total += self.pixels[source + channel] as u32;
+                        }
+                    }
+                }
+                pixels.extend(sum.iter().map(|total| (total / samples) as u8));
+            }
+        }
+        CapturedFrame::new(width, height, pixels)
+    }
+
+    pub fn write_png(&self, path: &Path) -> Result<(), VSError> {
+        let file = File::create(path).map_err(|e| {
+            error!("unable to create capture file {}: {}", path.display(), e);
+            VSError::Render_CaptureWriteFailed
+        })?;
+
+        let mut encoder = png::Encoder::new(BufWriter::new(file), self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&self.pixels))
+            .map_err(|e| {
+                error!("unable to write capture png {}: {}", path.display(), e);
+                VSError::Render_CaptureWriteFailed
+            })?;
+
+        info!("Captured frame to {}", path.display());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flip_and_downsample() {
+        // 2x2: top row black, bottom row white
+        let mut frame = CapturedFrame::new(
+            2,
+            2,
+            vec![
+                255, 255, 255, 255, 255, 255, 255, 255, 0, 0, 0, 255, 0, 0, 0, 255,
+            ],
+        );
+        frame.flip_vertical();
+        assert_eq!(frame.pixels[0], 0);
+        assert_eq!(frame.pixels[8], 255);
+
+        let small = frame.downsampled(2);
+        assert_eq!((small.width, small.height), (1, 1));
+        assert_eq!(small.pixels, vec![127, 127, 127, 255]);
+    }
+}
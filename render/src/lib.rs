@@ -1,8 +1,23 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::screen::Screen;
+
+pub mod debug_draw;
 pub mod display_list;
+pub mod dynamic_batching;
+pub mod frame_capture;
+pub mod material;
+pub mod particles;
 pub mod renderer;
 pub mod renderer_opengl3;
 pub mod screen;
 pub mod shader_suite;
+pub mod sprite;
+pub mod sprite_sheet;
+pub mod texture;
+pub mod virtual_resolution;
+
+pub type RefScreen = Rc<RefCell<Screen>>;
 
 pub fn add(left: u64, right: u64) -> u64 {
     left + right
@@ -0,0 +1,42 @@
+use std::rc::Rc;
+
+use super::texture::Texture;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum DrawMode {
+    #[default]
+    Normal,
+    Additive,
+    Opaque,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Material {
+    pub texture: Option<Rc<Texture>>,
+    pub draw_mode: DrawMode,
+}
+
+impl Material {
+    pub fn new(texture: Option<Rc<Texture>>, draw_mode: DrawMode) -> Self {
+        Material { texture, draw_mode }
+    }
+
+    pub fn from_texture(texture: Rc<Texture>) -> Self {
+        Material {
+            texture: Some(texture),
+            draw_mode: DrawMode::Normal,
+        }
+    }
+}
+
+// materials are equal if they share the same loaded texture, not just identical pixels
+impl PartialEq for Material {
+    fn eq(&self, other: &Self) -> bool {
+        self.draw_mode == other.draw_mode
+            && match (&self.texture, &other.texture) {
+                (Some(a), Some(b)) => Rc::ptr_eq(a, b),
+                (None, None) => true,
+                _ => false,
+            }
+    }
+}
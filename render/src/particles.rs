@@ -0,0 +1,469 @@
+use common::{ColorSpline, Spline1D, Transform2D, VScolor, Vector2D, math::random::Random};
+
+use super::{
+    display_list::{DisplayList, Geometry, PrimitiveType},
+    material::{DrawMode, Material},
+};
+
+// keeps indices within a single u16-indexed draw
+const MAX_POINTS_PER_DRAW: usize = u16::MAX as usize;
+const MAX_LINES_PER_DRAW: usize = u16::MAX as usize / 2;
+const MAX_QUADS_PER_DRAW: usize = u16::MAX as usize / 4;
+
+/// Where new particles appear, in the emitter's local space
+#[derive(Debug, Clone, PartialEq)]
+pub enum EmitterShape {
+    Point,
+    Line {
+        start: Vector2D,
+        end: Vector2D,
+    },
+    Circle {
+        radius: f32,
+        edge_only: bool,
+    },
+    /// From a randomly chosen vertex, e.g. of a ship's model as it explodes
+    Vertices(Vec<Vector2D>),
+}
+
+impl EmitterShape {
+    pub fn from_geometry(geometry: &Geometry) -> Self {
+        EmitterShape::Vertices(geometry.vertices.clone())
+    }
+
+    fn sample(&self, random: &Random) -> Vector2D {
+        match self {
+            EmitterShape::Point => Vector2D::ZERO,
+            EmitterShape::Line { start, end } => start.lerp(*end, random.get_float(0.0, 1.0)),
+            EmitterShape::Circle { radius, edge_only } => {
+                let distance = if *edge_only {
+                    *radius
+                } else {
+                    // uniform over the disc's area, rather than bunched at the center
+                    radius * random.get_float(0.0, 1.0).sqrt()
+                };
+                random.get_unit_vector() * distance
+            }
+            EmitterShape::Vertices(vertices) => {
+                if vertices.is_empty() {
+                    Vector2D::ZERO
+                } else {
+                    vertices[random.get_int(0, vertices.len() as i32 - 1) as usize]
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ParticleStyle {
+    Points,
+    /// Streaks trailing behind each particle's direction of travel, `size` long
+    Lines,
+    #[default]
+    Quads,
+}
+
+/// Everything that describes how an emitter behaves.  Curves over a particle's
+/// life are sampled from 0 (birth) to 1 (death); the emission rate curve is
+/// sampled across the emitter's duration.
+#[derive(Debug, Clone)]
+pub struct EmitterSettings {
+    pub shape: EmitterShape,
+    pub style: ParticleStyle,
+    pub material: Material,
+
+    pub duration: f32, // seconds the emitter runs for
+    pub looping: bool,
+    pub emission_rate: Spline1D, // particles per second
+    pub max_particles: usize,
+
+    pub lifetime_min: f32,
+    pub lifetime_max: f32,
+    pub speed_min: f32,
+    pub speed_max: f32,
+    pub direction: f32, // radians, in emitter space
+    pub spread: f32,    // radians either side of direction
+    pub gravity: Vector2D,
+    pub drag: f32, // fraction of velocity lost per second
+
+    pub color: ColorSpline,
+    pub size: Spline1D,
+}
+
+impl Default for EmitterSettings {
+    fn default() -> Self {
+        EmitterSettings {
+            shape: EmitterShape::Point,
+            style: ParticleStyle::Quads,
+            material: Material::new(None, DrawMode::Additive),
+            duration: 1.0,
+            looping: true,
+            emission_rate: Spline1D::constant(50.0),
+            max_particles: 1000,
+            lifetime_min: 1.0,
+            lifetime_max: 1.0,
+            speed_min: 50.0,
+            speed_max: 50.0,
+            direction: 0.0,
+            spread: std::f32::consts::PI,
+            gravity: Vector2D::ZERO,
+            drag: 0.0,
+            color: ColorSpline::new(VScolor::WHITE, VScolor::WHITE, VScolor::WHITE),
+            size: Spline1D::constant(4.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Particle {
+    pub position: Vector2D, // world space
+    pub velocity: Vector2D,
+    pub age: f32,
+    pub lifetime: f32,
+}
+
+impl Particle {
+    pub fn life_fraction(&self) -> f32 {
+        (self.age / self.lifetime).clamp(0.0, 1.0)
+    }
+}
+
+/// Spawns and simulates particles.  Particles are emitted into world space, so
+/// moving the emitter's transform leaves a trail behind it.
+pub struct ParticleEmitter {
+    pub settings: EmitterSettings,
+    pub transform: Transform2D,
+    pub velocity: Vector2D, // of the emitter, inherited by new particles
+    particles: Vec<Particle>,
+    time: f32,
+    emit_accumulator: f32,
+    emitting: bool,
+    random: Random,
+}
+
+impl ParticleEmitter {
+    pub fn new(settings: EmitterSettings) -> Self {
+        ParticleEmitter::with_random(settings, Random::new())
+    }
+
+    /// An emitter whose particles come out the same every run
+    pub fn with_random(settings: EmitterSettings, random: Random) -> Self {
+        ParticleEmitter {
+            settings,
+            transform: Transform2D::IDENTITY,
+            velocity: Vector2D::ZERO,
+            particles: Vec::new(),
+            time: 0.0,
+            emit_accumulator: 0.0,
+            emitting: true,
+            random,
+        }
+    }
+
+    pub fn start(&mut self) {
+        self.time = 0.0;
+        self.emit_accumulator = 0.0;
+        self.emitting = true;
+    }
+
+    /// Stops new particles; existing ones live out their lives
+    pub fn stop(&mut self) {
+        self.emitting = false;
+    }
+
+    pub fn is_emitting(&self) -> bool {
+        self.emitting
+    }
+
+    /// Stopped, with no particles left alive
+    pub fn is_finished(&self) -> bool {
+        !self.emitting && self.particles.is_empty()
+    }
+
+    pub fn particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    /// Emits `count` particles immediately, e.g. for an explosion
+    pub fn burst(&mut self, count: usize) {
+        for _ in 0..count {
+            self.emit();
+        }
+    }
+
+    pub fn update(&mut self, delta: f32) {
+        let drag = (1.0 - self.settings.drag * delta).max(0.0);
+        let gravity = self.settings.gravity * delta;
+        for particle in self.particles.iter_mut() {
+            particle.age += delta;
+            particle.velocity = (particle.velocity + gravity) * drag;
+            particle.position += particle.velocity * delta;
+        }
+        self.particles
+            .retain(|particle| particle.age < particle.lifetime);
+
+        if self.emitting {
+            let duration = self.settings.duration.max(f32::EPSILON);
+            let progress = if self.settings.looping {
+                (self.time % duration) / duration
+            } else {
+                (self.time / duration).min(1.0)
+            };
+            let rate = self
+                .settings
+                .emission_rate
+                .position_at_time(progress)
+                .max(0.0);
+
+            self.emit_accumulator += rate * delta;
+            while self.emit_accumulator >= 1.0 {
+                self.emit_accumulator -= 1.0;
+                self.emit();
+            }
+
+            self.time += delta;
+            if !self.settings.looping && self.time >= duration {
+                self.emitting = false;
+            }
+        }
+    }
+
+    pub fn draw(&self, list: &mut DisplayList) {
+        if self.particles.is_empty() {
+            return;
+        }
+
+        list.set_material(self.settings.material.clone());
+        list.set_color(VScolor::WHITE);
+        match self.settings.style {
+            ParticleStyle::Points => {
+                for chunk in self.particles.chunks(MAX_POINTS_PER_DRAW) {
+                    list.draw(self.point_geometry(chunk));
+                }
+            }
+            ParticleStyle::Lines => {
+                for chunk in self.particles.chunks(MAX_LINES_PER_DRAW) {
+                    list.draw(self.line_geometry(chunk));
+                }
+            }
+            ParticleStyle::Quads => {
+                for chunk in self.particles.chunks(MAX_QUADS_PER_DRAW) {
+                    list.draw(self.quad_geometry(chunk));
+                }
+            }
+        }
+    }
+
+    fn emit(&mut self) {
+        if self.particles.len() >= self.settings.max_particles {
+            return;
+        }
+
+        let settings = &self.settings;
+        let random = &self.random;
+        let angle = settings.direction + random.get_float(-settings.spread, settings.spread);
+        let speed = random.get_float(settings.speed_min, settings.speed_max);
+        let local_velocity = Vector2D::from_angle(angle) * speed;
+
+        self.particles.push(Particle {
+            position: self.transform.apply_to(settings.shape.sample(random)),
+            velocity: self.transform.apply_to_direction(local_velocity) + self.velocity,
+            age: 0.0,
+            lifetime: random
+                .get_float(settings.lifetime_min, settings.lifetime_max)
+                .max(f32::EPSILON),
+        });
+    }
+
+    fn particle_look(&self, particle: &Particle) -> (VScolor, f32) {
+        let life = particle.life_fraction();
+        (
+            self.settings.color.color_at_time(life),
+            self.settings.size.position_at_time(life),
+        )
+    }
+
+    fn point_geometry(&self, particles: &[Particle]) -> Geometry {
+        let mut geometry = Geometry::new(PrimitiveType::Points);
+        for particle in particles {
+            let (color, _) = self.particle_look(particle);
+            geometry.indices.push(geometry.vertices.len() as u16);
+            geometry.vertices.push(particle.position);
+            geometry.colors.push(color);
+        }
+        geometry
+    }
+
+    fn line_geometry(&self, particles: &[Particle]) -> Geometry {
+        let mut geometry = Geometry::new(PrimitiveType::Lines);
+        for particle in particles {
+            let (color, size) = self.particle_look(particle);
+            let tail = particle.position - particle.velocity.normalize_or_zero() * size;
+            let base = geometry.vertices.len() as u16;
+            geometry.vertices.extend([tail, particle.position]);
+            geometry.colors.extend([color, color]);
+            geometry.indices.extend([base, base + 1]);
+        }
+        geometry
+    }
+
+    fn quad_geometry(&self, particles: &[Particle]) -> Geometry {
+        let mut geometry = Geometry::new(PrimitiveType::Triangles);
+        for particle in particles {
+            let (color, size) = self.particle_look(particle);
+            let half = size * 0.5;
+            let base = geometry.vertices.len() as u16;
+            geometry.vertices.extend([
+                particle.position + Vector2D::new(-half, -half),
+                particle.position + Vector2D::new(half, -half),
+                particle.position + Vector2D::new(half, half),
+                particle.position + Vector2D::new(-half, half),
+            ]);
+            geometry.colors.extend([color; 4]);
+            geometry.texels.extend([
+                Vector2D::new(0.0, 0.0),
+                Vector2D::new(1.0, 0.0),
+                Vector2D::new(1.0, 1.0),
+                Vector2D::new(0.0, 1.0),
+            ]);
+            geometry
+                .indices
+                .extend([base, base + 1, base + 2, base, base + 2, base + 3]);
+        }
+        geometry
+    }
+}
+
+/// Fire-and-forget emitters, such as explosions, which are dropped once finished.
+/// Emitters which need steering (thrust trails) are better owned by their game object.
+#[derive(Default)]
+pub struct ParticleSystem {
+    emitters: Vec<ParticleEmitter>,
+}
+
+impl ParticleSystem {
+    pub fn new() -> Self {
+        ParticleSystem {
+            emitters: Vec::new(),
+        }
+    }
+
+    pub fn spawn(&mut self, emitter: ParticleEmitter) {
+        self.emitters.push(emitter);
+    }
+
+    pub fn emitter_count(&self) -> usize {
+        self.emitters.len()
+    }
+
+    pub fn particle_count(&self) -> usize {
+        self.emitters.iter().map(|e| e.particles().len()).sum()
+    }
+
+    pub fn update(&mut self, delta: f32) {
+        for emitter in self.emitters.iter_mut() {
+            emitter.update(delta);
+        }
+        self.emitters.retain(|emitter| !emitter.is_finished());
+    }
+
+    pub fn draw(&self, list: &mut DisplayList) {
+        for emitter in self.emitters.iter() {
+            emitter.draw(list);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display_list::DisplayListOp;
+
+    #[test]
+    fn explosion_burst_lives_and_dies() {
+        let settings = EmitterSettings {
+            looping: false,
+            emission_rate: Spline1D::constant(0.0),
+            lifetime_min: 0.5,
+            lifetime_max: 1.0,
+            gravity: Vector2D::new(0.0, 100.0),
+            color: ColorSpline::new(VScolor::WHITE, VScolor::RED, VScolor::BLACK),
+            ..Default::default()
+        };
+        let mut emitter = ParticleEmitter::with_random(settings, Random::new_from_seed(1));
+        emitter.transform = Transform2D::from_position(Vector2D::new(10.0, 0.0));
+        emitter.burst(20);
+        assert!(
+            emitter
+                .particles()
+                .iter()
+                .all(|p| p.position == Vector2D::new(10.0, 0.0))
+        );
+
+        let mut system = ParticleSystem::new();
+        system.spawn(emitter);
+        system.update(0.25);
+        assert_eq!(system.particle_count(), 20);
+
+        let mut list = DisplayList::new();
+        system.draw(&mut list);
+        let Some(DisplayListOp::Draw(geometry)) = list.ops().last() else {
+            panic!("particles should draw");
+        };
+        assert_eq!(geometry.vertices.len(), 80);
+
+        system.update(1.0);
+        assert_eq!(system.emitter_count(), 0);
+    }
+
+    #[test]
+    fn emission_rate_curve() {
+        let settings = EmitterSettings {
+            duration: 1.0,
+            looping: false,
+            emission_rate: Spline1D::linear(100.0, 0.0),
+            lifetime_min: 10.0,
+            lifetime_max: 10.0,
+            ..Default::default()
+        };
+        let mut emitter = ParticleEmitter::with_random(settings, Random::new_from_seed(2));
+        for _ in 0..=100 {
+            emitter.update(0.01);
+        }
+        // the area under the rate curve
+        assert!((49..=51).contains(&emitter.particles().len()));
+        assert!(!emitter.is_emitting());
+    }
+
+    #[test]
+    fn large_emitters_split_their_draws() {
+        let count = u16::MAX as usize + 10;
+        for style in [ParticleStyle::Points, ParticleStyle::Lines] {
+            let settings = EmitterSettings {
+                looping: false,
+                emission_rate: Spline1D::constant(0.0),
+                max_particles: count,
+                style,
+                ..Default::default()
+            };
+            let mut emitter = ParticleEmitter::with_random(settings, Random::new_from_seed(3));
+            emitter.burst(count);
+            assert_eq!(emitter.particles().len(), count);
+
+            let mut list = DisplayList::new();
+            emitter.draw(&mut list);
+            let mut drawn = 0;
+            for op in list.ops() {
+                if let DisplayListOp::Draw(geometry) = op {
+                    assert!(geometry.vertices.len() <= u16::MAX as usize);
+                    let last = geometry.vertices.len() - 1;
+                    assert_eq!(*geometry.indices.last().unwrap() as usize, last);
+                    drawn += geometry.indices.len();
+                }
+            }
+            let per_particle = if style == ParticleStyle::Points { 1 } else { 2 };
+            assert_eq!(drawn, count * per_particle);
+        }
+    }
+}
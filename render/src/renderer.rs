@@ -2,10 +2,16 @@ use std::{cell::RefCell, rc::Rc};
 
 use common::{
     backend::Backend,
-    window::{WindowBuffers, WindowDepth, WindowExtent, WindowType},
+    vs_error::VSError,
+    window::{WindowBuffers, WindowDepth, WindowExtent, WindowSize, WindowType},
 };
 
-use super::{display_list::DisplayList, shader_suite::ShaderSuite};
+use super::{
+    display_list::{DisplayList, Geometry, PrimitiveType},
+    frame_capture::CapturedFrame,
+    shader_suite::ShaderSuite,
+    virtual_resolution::ViewportLayout,
+};
 
 pub trait Renderer: Sized {
     #[allow(clippy::too_many_arguments)]
@@ -18,6 +24,7 @@ pub trait Renderer: Sized {
         buffer_count: WindowBuffers,
         antialias: bool,
         vsync: bool,
+        high_dpi: bool,
     ) -> Self;
     fn check_video_mode(&self) -> bool;
     #[allow(clippy::too_many_arguments)]
@@ -31,22 +38,28 @@ pub trait Renderer: Sized {
         antialias: bool,
         vsync: bool,
     );
-    // notify_resized ( width: u16, height: u16 );
+    fn notify_resized(&mut self, size: WindowSize);
     fn pre_render(&mut self, settings: &RenderSettings);
     fn render_display_list(&mut self, display_list: &DisplayList);
     // fn raw_render_display_list ( display_list: &DisplayList );
     fn post_render(&mut self);
 
     fn get_render_data(&self) -> Option<Rc<RefCell<RendererData>>>;
+    fn get_render_stats(&self) -> RenderStats;
+
+    /// Renders at `factor` times the window resolution, for captures
+    fn set_supersample(&mut self, factor: u8);
+    /// Reads back the frame rendered so far; call before post_render presents it
+    fn read_framebuffer(&mut self) -> Result<CapturedFrame, VSError>;
 }
 
 #[allow(dead_code)]
 #[derive(Debug)]
 pub struct RenderSettings {
     shader_suite: Option<ShaderSuite>,
-    aspect_ratio: f32,
+    pub(crate) aspect_ratio: f32,
     polygon_offset_units: f32,
-    use_custom_aspect_ratio: bool,
+    pub(crate) use_custom_aspect_ratio: bool,
     write_color: bool,
     write_depth: bool,
     invert_cull: bool,
@@ -69,17 +82,23 @@ impl Default for RenderSettings {
 #[derive(Debug, Default)]
 pub struct RendererData {
     pub settings: RenderSettings,
+    // window and viewport sizes in points
     pub width: WindowExtent,
     pub height: WindowExtent,
     pub viewport_width: WindowExtent,
     pub viewport_height: WindowExtent,
 
+    // the same in pixels, which are smaller than points on high-dpi displays
     pub width_pixels: WindowExtent,
     pub height_pixels: WindowExtent,
     pub viewport_width_pixels: WindowExtent,
     pub viewport_height_pixels: WindowExtent,
+    pub viewport_x_pixels: WindowExtent, // from the left
+    pub viewport_y_pixels: WindowExtent, // from the top
 
     pub refresh_rate: u16, // ?? could this be u8
+
+    pub stats: RenderStats, // for the most recently rendered frame
 }
 
 impl RendererData {
@@ -87,5 +106,101 @@ impl RendererData {
         &self.settings
     }
 
+    pub fn get_render_stats(&self) -> RenderStats {
+        self.stats
+    }
+
+    pub fn get_window_size(&self) -> WindowSize {
+        WindowSize {
+            width: self.width,
+            height: self.height,
+            width_pixels: self.width_pixels,
+            height_pixels: self.height_pixels,
+        }
+    }
+
+    /// Resizes the window, resetting the viewport to cover all of it
+    pub fn set_window_size(&mut self, size: WindowSize) {
+        self.width = size.width;
+        self.height = size.height;
+        self.width_pixels = size.width_pixels;
+        self.height_pixels = size.height_pixels;
+        self.set_viewport(&ViewportLayout::full_window(size));
+    }
+
+    pub fn set_viewport(&mut self, layout: &ViewportLayout) {
+        let pixel_scale = self.get_window_size().pixel_scale();
+        self.viewport_x_pixels = layout.x;
+        self.viewport_y_pixels = layout.y;
+        self.viewport_width_pixels = layout.width;
+        self.viewport_height_pixels = layout.height;
+        self.viewport_width = (layout.width as f32 / pixel_scale).round() as WindowExtent;
+        self.viewport_height = (layout.height as f32 / pixel_scale).round() as WindowExtent;
+    }
+
     // TODO as needed
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GpuTimeSource {
+    #[default]
+    CpuClock, // no timer queries available; cpu time spent submitting the frame
+    TimerQuery,
+}
+
+/// What went into the last frame.  The counts tally the display lists
+/// submitted to the renderer, not calls made to the graphics api.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RenderStats {
+    pub geometries_submitted: u32,
+    pub vertices: u32,
+    pub triangles: u32,
+    pub lines: u32,
+    pub points: u32,
+    pub state_changes: u32, // color, material and transform changes in the lists
+    pub gpu_time: u64,      // microseconds, for the renderer's own gpu work
+    pub gpu_time_source: GpuTimeSource,
+}
+
+impl RenderStats {
+    /// Counts one submitted geometry
+    pub fn add_geometry(&mut self, geometry: &Geometry) {
+        let indices = geometry.indices.len() as u32;
+        self.geometries_submitted += 1;
+        self.vertices += geometry.vertices.len() as u32;
+        match geometry.primitive {
+            PrimitiveType::Points => self.points += indices,
+            PrimitiveType::Lines => self.lines += indices / 2,
+            PrimitiveType::Triangles => self.triangles += indices / 3,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::{VScolor, Vector2D};
+
+    use super::*;
+
+    fn geometry(primitive: PrimitiveType, vertices: usize, indices: &[u16]) -> Geometry {
+        let mut geometry = Geometry::new(primitive);
+        geometry.vertices = vec![Vector2D::ZERO; vertices];
+        geometry.colors = vec![VScolor::default(); vertices];
+        geometry.indices = indices.to_vec();
+        geometry
+    }
+
+    #[test]
+    fn stats_count_geometry() {
+        let mut stats = RenderStats::default();
+        stats.add_geometry(&geometry(PrimitiveType::Triangles, 4, &[0, 1, 2, 2, 1, 3]));
+        stats.add_geometry(&geometry(PrimitiveType::Lines, 3, &[0, 1, 1, 2]));
+        stats.add_geometry(&geometry(PrimitiveType::Points, 5, &[0, 1, 2, 3, 4]));
+
+        assert_eq!(stats.geometries_submitted, 3);
+        assert_eq!(stats.vertices, 12);
+        assert_eq!(stats.triangles, 2);
+        assert_eq!(stats.lines, 2);
+        assert_eq!(stats.points, 5);
+    }
+}
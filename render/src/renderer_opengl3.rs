@@ -1,17 +1,132 @@
+use common::VScolor;
 use common::{
     backend::Backend,
-    window::{WindowBuffers, WindowDepth, WindowExtent, WindowType},
+    vs_error::VSError,
+    window::{WindowBuffers, WindowDepth, WindowExtent, WindowSize, WindowType},
 };
-use log::{error, info};
-use std::{cell::RefCell, rc::Rc};
+use log::{error, info, warn};
+use sdl2_sys::{SDL_GL_GetCurrentWindow, SDL_GL_GetProcAddress, SDL_GL_SwapWindow};
+use std::{cell::RefCell, ffi::CString, rc::Rc, time::Instant};
 
-use super::renderer::{Renderer, RendererData};
+use super::{
+    display_list::{DisplayList, DisplayListOp},
+    frame_capture::CapturedFrame,
+    material::Material,
+    renderer::{GpuTimeSource, RenderSettings, RenderStats, Renderer, RendererData},
+};
+
+// timer queries in flight; results are read a frame or two after they're issued
+const GPU_TIMER_QUERIES: usize = 3;
+
+/// Times each frame on the gpu with GL_TIME_ELAPSED queries, which need
+/// GL 3.3 or ARB_timer_query
+struct GpuTimer {
+    queries: [u32; GPU_TIMER_QUERIES],
+    pending: [bool; GPU_TIMER_QUERIES],
+    next: usize,
+    timing: bool,
+    last_time: Option<u64>, // microseconds
+}
+
+impl GpuTimer {
+    fn new() -> Option<Self> {
+        if !(gl::GenQueries::is_loaded()
+            && gl::BeginQuery::is_loaded()
+            && gl::GetQueryObjectui64v::is_loaded()
+            && has_timer_queries())
+        {
+            return None;
+        }
+
+        let mut queries = [0; GPU_TIMER_QUERIES];
+        unsafe {
+            gl::GenQueries(GPU_TIMER_QUERIES as i32, queries.as_mut_ptr());
+        }
+        Some(GpuTimer {
+            queries,
+            pending: [false; GPU_TIMER_QUERIES],
+            next: 0,
+            timing: false,
+            last_time: None,
+        })
+    }
+
+    /// Collects finished results, oldest first, then starts timing this frame
+    /// unless its query is still in flight
+    fn begin(&mut self) {
+        for age in 0..GPU_TIMER_QUERIES {
+            let index = (self.next + age) % GPU_TIMER_QUERIES;
+            if !self.pending[index] {
+                continue;
+            }
+            let query = self.queries[index];
+            let mut available = 0;
+            unsafe {
+                gl::GetQueryObjectiv(query, gl::QUERY_RESULT_AVAILABLE, &mut available);
+            }
+            if available == 0 {
+                // results arrive in order, so later ones aren't ready either
+                break;
+            }
+            let mut nanoseconds = 0;
+            unsafe {
+                gl::GetQueryObjectui64v(query, gl::QUERY_RESULT, &mut nanoseconds);
+            }
+            self.pending[index] = false;
+            self.last_time = Some(nanoseconds / 1000);
+        }
+
+        self.timing = !self.pending[self.next];
+        if self.timing {
+            unsafe {
+                gl::BeginQuery(gl::TIME_ELAPSED, self.queries[self.next]);
+            }
+        }
+    }
+
+    fn end(&mut self) {
+        if self.timing {
+            unsafe {
+                gl::EndQuery(gl::TIME_ELAPSED);
+            }
+            self.pending[self.next] = true;
+            self.next = (self.next + 1) % GPU_TIMER_QUERIES;
+            self.timing = false;
+        }
+    }
+}
+
+impl Drop for GpuTimer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteQueries(GPU_TIMER_QUERIES as i32, self.queries.as_ptr());
+        }
+    }
+}
+
+/// Offscreen target used when rendering at a multiple of the window resolution
+struct SupersampleTarget {
+    framebuffer: u32,
+    renderbuffer: u32,
+    factor: u8,
+    width: i32,
+    height: i32,
+}
 
 #[allow(dead_code)]
 pub struct RendererOpenGL3 {
     renderer_data: Option<Rc<RefCell<RendererData>>>,
     // window: sdl2::video::Window,
     // sdl: Rc<Sdl>,
+    stats: RenderStats,
+    frame_start: Option<Instant>,
+    gpu_timer: Option<GpuTimer>,
+    current_material: Option<Material>,
+    current_color: Option<VScolor>,
+
+    gl_loaded: bool,
+    supersample: u8,
+    supersample_target: Option<SupersampleTarget>,
 }
 
 impl Renderer for RendererOpenGL3 {
@@ -24,6 +139,7 @@ impl Renderer for RendererOpenGL3 {
         buffer_count: WindowBuffers,
         antialias: bool,
         vsync: bool,
+        high_dpi: bool,
     ) -> Self {
         // let mut renderer_data = RendererData {
         //     width, height, viewport_width: width, viewport_height: height,
@@ -65,17 +181,21 @@ impl Renderer for RendererOpenGL3 {
             buffer_count,
             antialias,
             vsync,
+            high_dpi,
         ) {
             Ok(()) => {
-                let renderer_data = RendererData {
-                    // TODO: will need to be mut
-                    width,
-                    height,
-                    viewport_width: width,
-                    viewport_height: height,
+                let size = backend
+                    .window_size()
+                    .unwrap_or_else(|| WindowSize::from_points(width, height));
+                info!(
+                    "Window is {}x{} points, {}x{} pixels",
+                    size.width, size.height, size.width_pixels, size.height_pixels
+                );
+                let mut renderer_data = RendererData {
                     refresh_rate: 60,
                     ..Default::default()
                 };
+                renderer_data.set_window_size(size);
                 Some(Rc::new(RefCell::new(renderer_data)))
             }
 
@@ -88,7 +208,24 @@ impl Renderer for RendererOpenGL3 {
             }
         };
 
-        RendererOpenGL3 { renderer_data }
+        // the backend leaves its gl context current after creating the window
+        let gl_loaded = renderer_data.is_some() && load_gl();
+        let gpu_timer = if gl_loaded { GpuTimer::new() } else { None };
+        if gl_loaded && gpu_timer.is_none() {
+            info!("No gpu timer queries, timing frame submission on the cpu instead");
+        }
+
+        RendererOpenGL3 {
+            renderer_data,
+            stats: RenderStats::default(),
+            frame_start: None,
+            gpu_timer,
+            current_material: None,
+            current_color: None,
+            gl_loaded,
+            supersample: 1,
+            supersample_target: None,
+        }
     }
 
     fn check_video_mode(&self) -> bool {
@@ -108,13 +245,306 @@ impl Renderer for RendererOpenGL3 {
     ) {
     }
 
+    fn notify_resized(&mut self, size: WindowSize) {
+        if let Some(renderer_data) = &self.renderer_data {
+            renderer_data.borrow_mut().set_window_size(size);
+        }
+    }
+
     fn get_render_data(&self) -> Option<Rc<RefCell<RendererData>>> {
         self.renderer_data.clone()
     }
 
-    fn pre_render(&mut self, _settings: &super::renderer::RenderSettings) {}
+    fn get_render_stats(&self) -> RenderStats {
+        self.stats
+    }
+
+    fn set_supersample(&mut self, factor: u8) {
+        self.supersample = factor.max(1);
+    }
+
+    fn read_framebuffer(&mut self) -> Result<CapturedFrame, VSError> {
+        if !self.gl_loaded {
+            return Err(VSError::Render_NoFramebuffer);
+        }
+
+        let (width, height) = self.render_target_size();
+        let mut pixels = vec![0u8; (width * height * 4) as usize];
+        unsafe {
+            gl::ReadBuffer(if self.supersample_target.is_some() {
+                gl::COLOR_ATTACHMENT0
+            } else {
+                gl::BACK
+            });
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(
+                0,
+                0,
+                width,
+                height,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr() as *mut _,
+            );
+        }
+
+        let mut frame = CapturedFrame::new(width as u32, height as u32, pixels);
+        frame.flip_vertical();
+        Ok(frame.downsampled(self.active_supersample() as u32))
+    }
+
+    fn pre_render(&mut self, _settings: &RenderSettings) {
+        self.stats = RenderStats::default();
+        self.current_material = None;
+        self.current_color = None;
+        self.frame_start = Some(Instant::now());
+
+        if self.gl_loaded {
+            if let Some(gpu_timer) = &mut self.gpu_timer {
+                gpu_timer.begin();
+            }
+            self.update_supersample_target();
+            let (width, height) = self.render_target_size();
+            let (x, y, viewport_width, viewport_height) = self.viewport_rect();
+            unsafe {
+                gl::BindFramebuffer(
+                    gl::FRAMEBUFFER,
+                    self.supersample_target
+                        .as_ref()
+                        .map_or(0, |target| target.framebuffer),
+                );
+                // clear the whole target, so any letterbox bars are black
+                gl::Viewport(0, 0, width, height);
+                gl::ClearColor(0.0, 0.0, 0.0, 1.0);
+                gl::Clear(gl::COLOR_BUFFER_BIT);
+                gl::Viewport(x, y, viewport_width, viewport_height);
+            }
+        }
+    }
+
+    fn post_render(&mut self) {
+        if self.gl_loaded {
+            let (width, height) = self.output_size();
+            unsafe {
+                if let Some(target) = &self.supersample_target {
+                    gl::BindFramebuffer(gl::READ_FRAMEBUFFER, target.framebuffer);
+                    gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, 0);
+                    gl::BlitFramebuffer(
+                        0,
+                        0,
+                        target.width,
+                        target.height,
+                        0,
+                        0,
+                        width,
+                        height,
+                        gl::COLOR_BUFFER_BIT,
+                        gl::LINEAR,
+                    );
+                }
+            }
+        }
+
+        // the gpu time covers the gl work done between pre_render and here,
+        // which is only clearing and the supersample blit until display lists
+        // are drawn; it's from a frame or two ago, once its query has finished.
+        // Without queries, time the frame on the cpu, before any vsync wait
+        let frame_start = self.frame_start.take();
+        match &mut self.gpu_timer {
+            Some(gpu_timer) => {
+                gpu_timer.end();
+                self.stats.gpu_time = gpu_timer.last_time.unwrap_or(0);
+                self.stats.gpu_time_source = GpuTimeSource::TimerQuery;
+            }
+            None => {
+                if let Some(frame_start) = frame_start {
+                    self.stats.gpu_time = frame_start.elapsed().as_micros() as u64;
+                    self.stats.gpu_time_source = GpuTimeSource::CpuClock;
+                }
+            }
+        }
+
+        if self.gl_loaded {
+            unsafe {
+                SDL_GL_SwapWindow(SDL_GL_GetCurrentWindow());
+            }
+        }
+
+        if let Some(renderer_data) = &self.renderer_data {
+            renderer_data.borrow_mut().stats = self.stats;
+        }
+    }
+
+    /// Only tallies the display list into the frame's stats for now; nothing
+    /// is drawn with gl yet
+    fn render_display_list(&mut self, display_list: &DisplayList) {
+        for op in display_list.ops() {
+            match op {
+                DisplayListOp::SetColor(color) => {
+                    if self.current_color != Some(*color) {
+                        self.current_color = Some(*color);
+                        self.stats.state_changes += 1;
+                    }
+                }
+                DisplayListOp::SetMaterial(material) => {
+                    if self.current_material.as_ref() != Some(material) {
+                        self.current_material = Some(material.clone());
+                        self.stats.state_changes += 1;
+                    }
+                }
+                DisplayListOp::PushTransform(..) | DisplayListOp::PopTransform => {
+                    self.stats.state_changes += 1;
+                }
+                DisplayListOp::Draw(geometry) => self.stats.add_geometry(geometry),
+            }
+        }
+    }
+}
+
+impl RendererOpenGL3 {
+    fn output_size(&self) -> (i32, i32) {
+        self.renderer_data.as_ref().map_or((0, 0), |renderer_data| {
+            let renderer_data = renderer_data.borrow();
+            (
+                renderer_data.width_pixels as i32,
+                renderer_data.height_pixels as i32,
+            )
+        })
+    }
 
-    fn post_render(&mut self) {}
+    /// The game's viewport in the current render target, in gl's bottom-up coordinates
+    fn viewport_rect(&self) -> (i32, i32, i32, i32) {
+        let scale = self.active_supersample() as i32;
+        self.renderer_data
+            .as_ref()
+            .map_or((0, 0, 0, 0), |renderer_data| {
+                let data = renderer_data.borrow();
+                let bottom = data.height_pixels as i32
+                    - data.viewport_y_pixels as i32
+                    - data.viewport_height_pixels as i32;
+                (
+                    data.viewport_x_pixels as i32 * scale,
+                    bottom * scale,
+                    data.viewport_width_pixels as i32 * scale,
+                    data.viewport_height_pixels as i32 * scale,
+                )
+            })
+    }
+
+    fn active_supersample(&self) -> u8 {
+        self.supersample_target
+            .as_ref()
+            .map_or(1, |target| target.factor)
+    }
 
-    fn render_display_list(&mut self, _display_list: &super::display_list::DisplayList) {}
+    fn render_target_size(&self) -> (i32, i32) {
+        match &self.supersample_target {
+            Some(target) => (target.width, target.height),
+            None => self.output_size(),
+        }
+    }
+
+    fn update_supersample_target(&mut self) {
+        let (width, height) = self.output_size();
+        let wanted = (self.supersample > 1).then(|| {
+            (
+                width * self.supersample as i32,
+                height * self.supersample as i32,
+            )
+        });
+        let current = self
+            .supersample_target
+            .as_ref()
+            .map(|target| (target.width, target.height));
+        if wanted == current {
+            return;
+        }
+
+        if let Some(target) = self.supersample_target.take() {
+            unsafe {
+                gl::DeleteFramebuffers(1, &target.framebuffer);
+                gl::DeleteRenderbuffers(1, &target.renderbuffer);
+            }
+        }
+
+        if let Some((target_width, target_height)) = wanted {
+            let mut target = SupersampleTarget {
+                framebuffer: 0,
+                renderbuffer: 0,
+                factor: self.supersample,
+                width: target_width,
+                height: target_height,
+            };
+            let complete = unsafe {
+                gl::GenFramebuffers(1, &mut target.framebuffer);
+                gl::BindFramebuffer(gl::FRAMEBUFFER, target.framebuffer);
+                gl::GenRenderbuffers(1, &mut target.renderbuffer);
+                gl::BindRenderbuffer(gl::RENDERBUFFER, target.renderbuffer);
+                gl::RenderbufferStorage(gl::RENDERBUFFER, gl::RGBA8, target_width, target_height);
+                gl::FramebufferRenderbuffer(
+                    gl::FRAMEBUFFER,
+                    gl::COLOR_ATTACHMENT0,
+                    gl::RENDERBUFFER,
+                    target.renderbuffer,
+                );
+                let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+                gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+                status == gl::FRAMEBUFFER_COMPLETE
+            };
+
+            if complete {
+                info!(
+                    "Created {}x supersample target ({}x{})",
+                    target.factor, target_width, target_height
+                );
+                self.supersample_target = Some(target);
+            } else {
+                warn!(
+                    "unable to create {}x supersample target, rendering at window resolution",
+                    target.factor
+                );
+                unsafe {
+                    gl::DeleteFramebuffers(1, &target.framebuffer);
+                    gl::DeleteRenderbuffers(1, &target.renderbuffer);
+                }
+                self.supersample = 1;
+            }
+        }
+    }
+}
+
+fn has_timer_queries() -> bool {
+    let (mut major, mut minor) = (0, 0);
+    unsafe {
+        gl::GetIntegerv(gl::MAJOR_VERSION, &mut major);
+        gl::GetIntegerv(gl::MINOR_VERSION, &mut minor);
+    }
+    if (major, minor) >= (3, 3) {
+        return true;
+    }
+
+    let mut count = 0;
+    unsafe {
+        gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut count);
+    }
+    (0..count.max(0) as u32).any(|index| {
+        let name = unsafe { gl::GetStringi(gl::EXTENSIONS, index) };
+        !name.is_null()
+            && unsafe { std::ffi::CStr::from_ptr(name as *const _) }.to_bytes()
+                == b"GL_ARB_timer_query"
+    })
+}
+
+fn load_gl() -> bool {
+    gl::load_with(|name| match CString::new(name) {
+        Ok(name) => unsafe { SDL_GL_GetProcAddress(name.as_ptr()) as *const _ },
+        Err(_) => std::ptr::null(),
+    });
+
+    if gl::ReadPixels::is_loaded() && gl::BlitFramebuffer::is_loaded() {
+        true
+    } else {
+        error!("unable to load opengl functions, while creating opengl3 renderer");
+        false
+    }
 }
@@ -1,10 +1,20 @@
 use common::{
+    Affine2D, Vector2D,
     backend::Backend,
-    window::{WindowBuffers, WindowDepth, WindowExtent, WindowType},
+    vs_error::VSError,
+    window::{WindowBuffers, WindowDepth, WindowExtent, WindowSize, WindowType},
 };
 use log::{debug, error, info};
 
-use super::{display_list::DisplayList, renderer::Renderer, renderer_opengl3::RendererOpenGL3};
+use super::{
+    debug_draw,
+    display_list::DisplayList,
+    dynamic_batching::{BatchStats, DynamicBatcher},
+    frame_capture::CapturedFrame,
+    renderer::{RenderSettings, RenderStats, Renderer},
+    renderer_opengl3::RendererOpenGL3,
+    virtual_resolution::{ViewportLayout, VirtualResolution},
+};
 
 #[allow(dead_code)]
 pub struct Screen {
@@ -15,10 +25,19 @@ pub struct Screen {
     window_type: WindowType,
     vsync: bool,
     antialias: bool,
+    high_dpi: bool,
 
     aspect_ratio: f32,
+    virtual_resolution: Option<VirtualResolution>,
+    layout: ViewportLayout,
 
     fifo: DisplayList,
+    settings: RenderSettings,
+    dynamic_batching: bool,
+    batcher: DynamicBatcher,
+    batch_stats: BatchStats,
+    capture_requested: bool,
+    captured_frame: Option<Result<CapturedFrame, VSError>>,
     // sdl: Rc<Sdl>,
     renderer: RendererOpenGL3, // how can we make this more independant?
 }
@@ -34,7 +53,8 @@ impl Screen {
         buffer_count: WindowBuffers,
         vsync: bool,
         antialias: bool,
-        _high_dpi: bool,
+        high_dpi: bool,
+        dynamic_batching: bool,
     ) -> Self {
         info!("Creating Screen...");
 
@@ -48,37 +68,175 @@ impl Screen {
             buffer_count,
             antialias,
             vsync,
+            high_dpi,
         );
 
-        let (new_width, new_height) = if let Some(renderer_data) = renderer.get_render_data() {
-            let new_width = renderer_data.borrow().width;
-            let new_height = renderer_data.borrow().height;
-
-            (new_width, new_height)
+        let size = if let Some(renderer_data) = renderer.get_render_data() {
+            renderer_data.borrow().get_window_size()
         } else {
             error!("no render data available, when creating Screen");
-            (1, 1) // bad default
+            WindowSize::from_points(1, 1) // bad default
         };
 
-        debug!("Width after: {}", new_width);
+        debug!("Width after: {}", size.width);
 
-        let aspect_ratio = new_width as f32 / new_height as f32;
+        let layout = ViewportLayout::full_window(size);
+        let aspect_ratio = layout.aspect_ratio();
         debug!("Screen Ratio: {}", aspect_ratio);
 
         let fifo = DisplayList::new();
         // fifo.set_resizable();
 
-        Screen {
-            width: new_width,
-            height: new_height,
+        let mut screen = Screen {
+            width: size.width,
+            height: size.height,
             depth,
             buffer_count,
             window_type,
             vsync,
             antialias,
+            high_dpi,
             aspect_ratio,
+            virtual_resolution: None,
+            layout,
             fifo,
+            settings: RenderSettings::default(),
+            dynamic_batching,
+            batcher: DynamicBatcher::default(),
+            batch_stats: BatchStats::default(),
+            capture_requested: false,
+            captured_frame: None,
             renderer,
+        };
+        screen.update_layout();
+        screen
+    }
+
+    /// The display list the game draws into this frame
+    pub fn get_fifo(&mut self) -> &mut DisplayList {
+        &mut self.fifo
+    }
+
+    /// Lets the game draw at a fixed logical size, however large the window is.
+    /// With None, logical coordinates are window points.
+    pub fn set_virtual_resolution(&mut self, virtual_resolution: Option<VirtualResolution>) {
+        self.virtual_resolution = virtual_resolution;
+        self.update_layout();
+    }
+
+    pub fn get_virtual_resolution(&self) -> Option<VirtualResolution> {
+        self.virtual_resolution
+    }
+
+    pub fn get_layout(&self) -> ViewportLayout {
+        self.layout
+    }
+
+    /// Of the logical area the game draws into
+    pub fn get_aspect_ratio(&self) -> f32 {
+        self.aspect_ratio
+    }
+
+    pub fn get_logical_size(&self) -> Vector2D {
+        self.layout.logical_size
+    }
+
+    pub fn get_window_size(&self) -> WindowSize {
+        self.renderer
+            .get_render_data()
+            .map_or(WindowSize::from_points(self.width, self.height), |data| {
+                data.borrow().get_window_size()
+            })
+    }
+
+    /// Converts a window position in points (as mouse positions arrive) to logical coordinates
+    pub fn window_to_logical(&self, point: Vector2D) -> Vector2D {
+        let pixel_scale = self.get_window_size().pixel_scale();
+        self.layout.pixels_to_logical(point * pixel_scale)
+    }
+
+    /// The same conversion as window_to_logical, for handing to other systems
+    pub fn window_to_logical_transform(&self) -> Affine2D {
+        let pixel_scale = self.get_window_size().pixel_scale();
+        let layout = &self.layout;
+        let viewport_size = Vector2D::new(layout.width.max(1) as f32, layout.height.max(1) as f32);
+        let scale = layout.logical_size / viewport_size;
+        Affine2D::from_translation(-Vector2D::new(layout.x as f32, layout.y as f32) * scale)
+            * Affine2D::from_scale(scale * pixel_scale)
+    }
+
+    /// Called when the window changes size
+    pub fn notify_resized(&mut self, size: WindowSize) {
+        info!(
+            "Screen resized to {}x{} points, {}x{} pixels",
+            size.width, size.height, size.width_pixels, size.height_pixels
+        );
+        self.width = size.width;
+        self.height = size.height;
+        self.renderer.notify_resized(size);
+        self.update_layout();
+    }
+
+    fn update_layout(&mut self) {
+        let size = self.get_window_size();
+        self.layout = match &self.virtual_resolution {
+            Some(virtual_resolution) => {
+                virtual_resolution.layout(size.width_pixels, size.height_pixels)
+            }
+            None => ViewportLayout::full_window(size),
+        };
+        self.aspect_ratio = self.layout.aspect_ratio();
+        self.settings.aspect_ratio = self.aspect_ratio;
+        self.settings.use_custom_aspect_ratio = self.virtual_resolution.is_some();
+
+        if let Some(renderer_data) = self.renderer.get_render_data() {
+            renderer_data.borrow_mut().set_viewport(&self.layout);
+        }
+    }
+
+    pub fn set_dynamic_batching(&mut self, dynamic_batching: bool) {
+        self.dynamic_batching = dynamic_batching;
+    }
+
+    pub fn get_batch_stats(&self) -> BatchStats {
+        self.batch_stats
+    }
+
+    pub fn get_render_stats(&self) -> RenderStats {
+        self.renderer.get_render_stats()
+    }
+
+    /// Reads back the next frame drawn; collect it with take_captured_frame
+    pub fn request_capture(&mut self) {
+        self.capture_requested = true;
+    }
+
+    pub fn take_captured_frame(&mut self) -> Option<Result<CapturedFrame, VSError>> {
+        self.captured_frame.take()
+    }
+
+    /// Renders at `factor` times the window resolution; captures are scaled back down
+    pub fn set_supersample(&mut self, factor: u8) {
+        self.renderer.set_supersample(factor);
+    }
+
+    /// Sends this frame's display list to the renderer, and clears it for the next frame
+    pub fn draw(&mut self) {
+        debug_draw::draw_overlay(&mut self.fifo);
+        self.renderer.pre_render(&self.settings);
+        if self.dynamic_batching {
+            let (batched, stats) = self.batcher.batch(&self.fifo);
+            self.batch_stats = stats;
+            self.renderer.render_display_list(&batched);
+        } else {
+            self.batch_stats = BatchStats::unbatched(&self.fifo);
+            self.renderer.render_display_list(&self.fifo);
+        }
+        if self.capture_requested {
+            self.capture_requested = false;
+            self.captured_frame = Some(self.renderer.read_framebuffer());
         }
+        self.renderer.post_render();
+        self.fifo.clear();
     }
 }
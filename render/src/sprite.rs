@@ -0,0 +1,146 @@
+use std::rc::Rc;
+
+use common::{Transform2D, VScolor, Vector2D};
+
+use super::{
+    display_list::{DisplayList, Geometry, PrimitiveType},
+    material::{DrawMode, Material},
+    texture::Texture,
+};
+
+/// A rectangle of texture coordinates, with (0,0) at the top left of the texture
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UvRect {
+    pub min: Vector2D,
+    pub max: Vector2D,
+}
+
+impl Default for UvRect {
+    fn default() -> Self {
+        Self::FULL
+    }
+}
+
+impl UvRect {
+    pub const FULL: UvRect = UvRect {
+        min: Vector2D::ZERO,
+        max: Vector2D::ONE,
+    };
+
+    pub fn new(min: Vector2D, max: Vector2D) -> Self {
+        UvRect { min, max }
+    }
+
+    /// Builds a rect from a region of the texture, measured in pixels
+    pub fn from_pixels(texture: &Texture, x: u32, y: u32, width: u32, height: u32) -> Self {
+        let texture_size = Vector2D::new(texture.width() as f32, texture.height() as f32);
+        UvRect {
+            min: Vector2D::new(x as f32, y as f32) / texture_size,
+            max: Vector2D::new((x + width) as f32, (y + height) as f32) / texture_size,
+        }
+    }
+
+    pub fn flipped(&self, flip_x: bool, flip_y: bool) -> Self {
+        let mut rect = *self;
+        if flip_x {
+            std::mem::swap(&mut rect.min.x, &mut rect.max.x);
+        }
+        if flip_y {
+            std::mem::swap(&mut rect.min.y, &mut rect.max.y);
+        }
+        rect
+    }
+}
+
+/// A textured quad.  The pivot is the point the sprite is positioned and rotated
+/// around, as a fraction of its size; (0.5, 0.5) is the center.
+#[derive(Debug, Clone)]
+pub struct Sprite {
+    pub material: Material,
+    pub transform: Transform2D,
+    pub size: Vector2D,
+    pub pivot: Vector2D,
+    pub flip_x: bool,
+    pub flip_y: bool,
+    pub tint: VScolor,
+    pub uv_rect: UvRect,
+    pub visible: bool,
+}
+
+impl Sprite {
+    /// Creates a sprite showing the whole texture, at the texture's pixel size
+    pub fn new(texture: Rc<Texture>) -> Self {
+        let size = Vector2D::new(texture.width() as f32, texture.height() as f32);
+        Sprite {
+            material: Material::from_texture(texture),
+            transform: Transform2D::IDENTITY,
+            size,
+            pivot: Vector2D::splat(0.5),
+            flip_x: false,
+            flip_y: false,
+            tint: VScolor::WHITE,
+            uv_rect: UvRect::FULL,
+            visible: true,
+        }
+    }
+
+    pub fn set_draw_mode(&mut self, draw_mode: DrawMode) {
+        self.material.draw_mode = draw_mode;
+    }
+
+    /// The sprite's quad in its local space
+    pub fn geometry(&self) -> Geometry {
+        let top_left = -self.pivot * self.size;
+        let bottom_right = top_left + self.size;
+        let uv = self.uv_rect.flipped(self.flip_x, self.flip_y);
+
+        Geometry {
+            primitive: PrimitiveType::Triangles,
+            vertices: vec![
+                top_left,
+                Vector2D::new(bottom_right.x, top_left.y),
+                bottom_right,
+                Vector2D::new(top_left.x, bottom_right.y),
+            ],
+            colors: Vec::new(),
+            texels: vec![
+                uv.min,
+                Vector2D::new(uv.max.x, uv.min.y),
+                uv.max,
+                Vector2D::new(uv.min.x, uv.max.y),
+            ],
+            indices: vec![0, 1, 2, 0, 2, 3],
+        }
+    }
+
+    pub fn draw(&self, list: &mut DisplayList) {
+        if !self.visible {
+            return;
+        }
+
+        list.push_transform(self.transform);
+        list.set_material(self.material.clone());
+        list.set_color(self.tint);
+        list.draw(self.geometry());
+        list.pop_transform();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flipped_uvs() {
+        let texture = Rc::new(Texture::from_pixels("test", 4, 2, vec![0; 4 * 2 * 4]));
+        let mut sprite = Sprite::new(texture.clone());
+        sprite.uv_rect = UvRect::from_pixels(&texture, 0, 0, 2, 2);
+        assert_eq!(sprite.uv_rect.max, Vector2D::new(0.5, 1.0));
+
+        sprite.flip_x = true;
+        let geometry = sprite.geometry();
+        assert_eq!(geometry.texels[0], Vector2D::new(0.5, 0.0));
+        assert_eq!(geometry.texels[2], Vector2D::new(0.0, 1.0));
+        assert_eq!(geometry.vertices[0], Vector2D::new(-2.0, -1.0));
+    }
+}
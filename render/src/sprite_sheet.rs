@@ -0,0 +1,388 @@
+use std::rc::Rc;
+
+use common::Vector2D;
+use log::warn;
+
+use super::{
+    display_list::DisplayList,
+    sprite::{Sprite, UvRect},
+    texture::Texture,
+};
+
+// guards against zero-length frames spinning forever in update
+const MIN_FRAME_DURATION: f32 = 0.001;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LoopMode {
+    Once,
+    #[default]
+    Loop,
+    PingPong,
+}
+
+/// One cell of a sprite sheet
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpriteFrame {
+    pub uv_rect: UvRect,
+    pub size: Vector2D,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnimationFrame {
+    pub frame: usize, // index into the sheet's frames
+    pub duration: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameEvent {
+    pub step: usize, // index into the animation's frames
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpriteAnimation {
+    pub name: String,
+    pub frames: Vec<AnimationFrame>,
+    pub loop_mode: LoopMode,
+    pub events: Vec<FrameEvent>,
+}
+
+impl SpriteAnimation {
+    pub fn new(name: &str, loop_mode: LoopMode) -> Self {
+        SpriteAnimation {
+            name: name.to_string(),
+            frames: Vec::new(),
+            loop_mode,
+            events: Vec::new(),
+        }
+    }
+
+    /// Convenience for animations where every frame lasts the same time
+    pub fn from_frames(name: &str, loop_mode: LoopMode, frames: &[usize], duration: f32) -> Self {
+        let mut animation = SpriteAnimation::new(name, loop_mode);
+        for frame in frames {
+            animation.add_frame(*frame, duration);
+        }
+        animation
+    }
+
+    pub fn add_frame(&mut self, frame: usize, duration: f32) -> &mut Self {
+        assert!(!duration.is_nan());
+        self.frames.push(AnimationFrame { frame, duration });
+        self
+    }
+
+    /// Fires `name` every time the animation arrives at `step`
+    pub fn add_event(&mut self, step: usize, name: &str) -> &mut Self {
+        self.events.push(FrameEvent {
+            step,
+            name: name.to_string(),
+        });
+        self
+    }
+
+    pub fn total_duration(&self) -> f32 {
+        self.frames.iter().map(|f| f.duration).sum()
+    }
+}
+
+#[derive(Debug)]
+pub struct SpriteSheet {
+    texture: Rc<Texture>,
+    frames: Vec<SpriteFrame>,
+    animations: Vec<SpriteAnimation>,
+}
+
+impl SpriteSheet {
+    pub fn new(texture: Rc<Texture>) -> Self {
+        SpriteSheet {
+            texture,
+            frames: Vec::new(),
+            animations: Vec::new(),
+        }
+    }
+
+    /// Cuts the texture into equally sized frames, numbered left to right, top to bottom
+    pub fn from_grid(texture: Rc<Texture>, columns: u32, rows: u32) -> Self {
+        assert!(
+            columns > 0 && rows > 0,
+            "sprite sheet grid must not be empty"
+        );
+        let mut sheet = SpriteSheet::new(texture);
+        let cell_width = sheet.texture.width() / columns;
+        let cell_height = sheet.texture.height() / rows;
+        for row in 0..rows {
+            for column in 0..columns {
+                sheet.add_frame_pixels(
+                    column * cell_width,
+                    row * cell_height,
+                    cell_width,
+                    cell_height,
+                );
+            }
+        }
+        sheet
+    }
+
+    pub fn add_frame_pixels(&mut self, x: u32, y: u32, width: u32, height: u32) -> usize {
+        self.frames.push(SpriteFrame {
+            uv_rect: UvRect::from_pixels(&self.texture, x, y, width, height),
+            size: Vector2D::new(width as f32, height as f32),
+        });
+        self.frames.len() - 1
+    }
+
+    pub fn add_animation(&mut self, animation: SpriteAnimation) -> usize {
+        if let Some(bad) = animation
+            .frames
+            .iter()
+            .find(|f| f.frame >= self.frames.len())
+        {
+            warn!(
+                "animation {} refers to missing sprite sheet frame {}",
+                animation.name, bad.frame
+            );
+        }
+        self.animations.push(animation);
+        self.animations.len() - 1
+    }
+
+    pub fn find_animation(&self, name: &str) -> Option<usize> {
+        self.animations.iter().position(|a| a.name == name)
+    }
+
+    pub fn texture(&self) -> Rc<Texture> {
+        Rc::clone(&self.texture)
+    }
+
+    pub fn frame(&self, frame: usize) -> Option<&SpriteFrame> {
+        self.frames.get(frame)
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn animation(&self, animation: usize) -> Option<&SpriteAnimation> {
+        self.animations.get(animation)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationEvent {
+    pub name: String,
+    pub step: usize,
+}
+
+/// A sprite whose image is driven by an animation from a sprite sheet
+#[derive(Debug, Clone)]
+pub struct AnimatedSprite {
+    pub sprite: Sprite,
+    pub speed: f32,
+    sheet: Rc<SpriteSheet>,
+    animation: Option<usize>,
+    step: usize,
+    time: f32,
+    forwards: bool,
+    playing: bool,
+}
+
+impl AnimatedSprite {
+    pub fn new(sheet: Rc<SpriteSheet>) -> Self {
+        let mut sprite = AnimatedSprite {
+            sprite: Sprite::new(sheet.texture()),
+            speed: 1.0,
+            sheet,
+            animation: None,
+            step: 0,
+            time: 0.0,
+            forwards: true,
+            playing: false,
+        };
+        sprite.apply_frame(0);
+        sprite
+    }
+
+    /// Starts the named animation from its first frame.  Any event on the first
+    /// frame is returned immediately.
+    pub fn play(&mut self, name: &str) -> Vec<AnimationEvent> {
+        match self.sheet.find_animation(name) {
+            Some(animation) => self.play_index(animation),
+            None => {
+                warn!("no animation named {} in sprite sheet", name);
+                Vec::new()
+            }
+        }
+    }
+
+    pub fn play_index(&mut self, animation: usize) -> Vec<AnimationEvent> {
+        self.animation = Some(animation);
+        self.step = 0;
+        self.time = 0.0;
+        self.forwards = true;
+        self.playing = self
+            .current_animation()
+            .is_some_and(|a| !a.frames.is_empty());
+        self.enter_step()
+    }
+
+    pub fn stop(&mut self) {
+        self.playing = false;
+    }
+
+    pub fn resume(&mut self) {
+        self.playing = self.animation.is_some();
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    pub fn current_step(&self) -> usize {
+        self.step
+    }
+
+    pub fn current_animation(&self) -> Option<&SpriteAnimation> {
+        self.animation.and_then(|a| self.sheet.animation(a))
+    }
+
+    /// Advances the animation, returning the events of every frame reached
+    pub fn update(&mut self, delta: f32) -> Vec<AnimationEvent> {
+        let mut events = Vec::new();
+        if !self.playing {
+            return events;
+        }
+
+        self.time += delta * self.speed;
+        while let Some(animation) = self.current_animation() {
+            let duration = animation.frames[self.step].duration.max(MIN_FRAME_DURATION);
+            if self.time < duration {
+                break;
+            }
+            self.time -= duration;
+
+            match self.next_step() {
+                Some(step) => {
+                    self.step = step;
+                    events.append(&mut self.enter_step());
+                }
+                None => {
+                    self.playing = false;
+                    self.time = 0.0;
+                    break;
+                }
+            }
+        }
+        events
+    }
+
+    pub fn draw(&self, list: &mut DisplayList) {
+        self.sprite.draw(list);
+    }
+
+    fn next_step(&mut self) -> Option<usize> {
+        let animation = self.current_animation()?;
+        let count = animation.frames.len();
+        match animation.loop_mode {
+            LoopMode::Once => (self.step + 1 < count).then_some(self.step + 1),
+            LoopMode::Loop => Some((self.step + 1) % count),
+            LoopMode::PingPong => {
+                if count == 1 {
+                    Some(0)
+                } else {
+                    if (self.forwards && self.step + 1 >= count)
+                        || (!self.forwards && self.step == 0)
+                    {
+                        self.forwards = !self.forwards;
+                    }
+                    Some(if self.forwards {
+                        self.step + 1
+                    } else {
+                        self.step - 1
+                    })
+                }
+            }
+        }
+    }
+
+    fn enter_step(&mut self) -> Vec<AnimationEvent> {
+        let Some(animation) = self.current_animation() else {
+            return Vec::new();
+        };
+        let Some(frame) = animation.frames.get(self.step).map(|f| f.frame) else {
+            return Vec::new();
+        };
+        let events = animation
+            .events
+            .iter()
+            .filter(|e| e.step == self.step)
+            .map(|e| AnimationEvent {
+                name: e.name.clone(),
+                step: e.step,
+            })
+            .collect();
+        self.apply_frame(frame);
+        events
+    }
+
+    fn apply_frame(&mut self, frame: usize) {
+        if let Some(sheet_frame) = self.sheet.frame(frame) {
+            self.sprite.uv_rect = sheet_frame.uv_rect;
+            self.sprite.size = sheet_frame.size;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_sheet(loop_mode: LoopMode) -> Rc<SpriteSheet> {
+        let texture = Rc::new(Texture::from_pixels("sheet", 4, 1, vec![0; 4 * 4]));
+        let mut sheet = SpriteSheet::from_grid(texture, 4, 1);
+        let mut animation = SpriteAnimation::from_frames("walk", loop_mode, &[0, 1, 2], 0.1);
+        animation.add_event(2, "footstep");
+        sheet.add_animation(animation);
+        Rc::new(sheet)
+    }
+
+    #[test]
+    fn looping_fires_events() {
+        let mut sprite = AnimatedSprite::new(test_sheet(LoopMode::Loop));
+        assert!(sprite.play("walk").is_empty());
+        assert!(sprite.update(0.15).is_empty());
+        assert_eq!(sprite.current_step(), 1);
+
+        let events = sprite.update(0.1);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].name, "footstep");
+        assert_eq!(sprite.sprite.uv_rect.min.x, 0.5);
+
+        sprite.update(0.1);
+        assert_eq!(sprite.current_step(), 0);
+        assert!(sprite.is_playing());
+    }
+
+    #[test]
+    fn once_stops_on_last_frame() {
+        let mut sprite = AnimatedSprite::new(test_sheet(LoopMode::Once));
+        sprite.play("walk");
+        let events = sprite.update(1.0);
+        assert_eq!(events.len(), 1);
+        assert_eq!(sprite.current_step(), 2);
+        assert!(!sprite.is_playing());
+    }
+
+    #[test]
+    fn ping_pong_reverses() {
+        let mut sprite = AnimatedSprite::new(test_sheet(LoopMode::PingPong));
+        sprite.play("walk");
+        let steps: Vec<usize> = (0..5)
+            .map(|_| {
+                sprite.update(0.1);
+                sprite.current_step()
+            })
+            .collect();
+        assert_eq!(steps, vec![1, 2, 1, 0, 1]);
+    }
+}
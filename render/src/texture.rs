@@ -0,0 +1,146 @@
+use std::{collections::HashMap, fs::File, io::BufReader, rc::Rc};
+
+use common::vs_error::VSError;
+use log::{error, info};
+
+/// An RGBA8 image, kept in CPU memory
+#[derive(Debug)]
+pub struct Texture {
+    name: String,
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Texture {
+    pub fn from_pixels(name: &str, width: u32, height: u32, pixels: Vec<u8>) -> Self {
+        assert_eq!(
+            pixels.len(),
+            (width * height * 4) as usize,
+            "texture pixel data does not match its size"
+        );
+        Texture {
+            name: name.to_string(),
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn load_png(path: &str) -> Result<Self, VSError> {
+        let file = File::open(path).map_err(|e| {
+            error!("unable to open texture file {}: {}", path, e);
+            VSError::Render_TextureNotFound
+        })?;
+
+        let mut decoder = png::Decoder::new(BufReader::new(file));
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info().map_err(|e| {
+            error!("unable to read png header of {}: {}", path, e);
+            VSError::Render_TextureDecodeFailed
+        })?;
+        let Some(buffer_size) = reader.output_buffer_size() else {
+            error!("png {} is too large to decode", path);
+            return Err(VSError::Render_TextureDecodeFailed);
+        };
+        let mut buffer = vec![0; buffer_size];
+        let frame = reader.next_frame(&mut buffer).map_err(|e| {
+            error!("unable to decode png {}: {}", path, e);
+            VSError::Render_TextureDecodeFailed
+        })?;
+        buffer.truncate(frame.buffer_size());
+
+        let pixels = match frame.color_type {
+            png::ColorType::Rgba => buffer,
+            png::ColorType::Rgb => buffer
+                .chunks_exact(3)
+                .flat_map(|p| [p[0], p[1], p[2], u8::MAX])
+                .collect(),
+            png::ColorType::GrayscaleAlpha => buffer
+                .chunks_exact(2)
+                .flat_map(|p| [p[0], p[0], p[0], p[1]])
+                .collect(),
+            png::ColorType::Grayscale => {
+                buffer.iter().flat_map(|g| [*g, *g, *g, u8::MAX]).collect()
+            }
+            png::ColorType::Indexed => {
+                error!("png {} was not expanded from indexed color", path);
+                return Err(VSError::Render_TextureDecodeFailed);
+            }
+        };
+
+        info!("Loaded texture {} ({}x{})", path, frame.width, frame.height);
+        Ok(Texture::from_pixels(
+            path,
+            frame.width,
+            frame.height,
+            pixels,
+        ))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+}
+
+/// Caches loaded textures by name, so each image is only loaded once
+#[derive(Default)]
+pub struct TextureManager {
+    textures: HashMap<String, Rc<Texture>>,
+}
+
+impl TextureManager {
+    pub fn new() -> Self {
+        TextureManager {
+            textures: HashMap::new(),
+        }
+    }
+
+    pub fn load(&mut self, path: &str) -> Result<Rc<Texture>, VSError> {
+        if let Some(texture) = self.textures.get(path) {
+            return Ok(Rc::clone(texture));
+        }
+
+        let texture = Rc::new(Texture::load_png(path)?);
+        self.textures.insert(path.to_string(), Rc::clone(&texture));
+        Ok(texture)
+    }
+
+    /// Adds a texture created in code, replacing any existing texture of the same name
+    pub fn insert(&mut self, texture: Texture) -> Rc<Texture> {
+        let texture = Rc::new(texture);
+        self.textures
+            .insert(texture.name().to_string(), Rc::clone(&texture));
+        texture
+    }
+
+    pub fn get(&self, name: &str) -> Option<Rc<Texture>> {
+        self.textures.get(name).cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.textures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.textures.is_empty()
+    }
+
+    /// Drops any texture which is no longer referenced outside the manager
+    pub fn release_unused(&mut self) {
+        self.textures
+            .retain(|_, texture| Rc::strong_count(texture) > 1);
+    }
+}
@@ -0,0 +1,152 @@
+use common::{
+    Vector2D,
+    window::{WindowExtent, WindowSize},
+};
+
+/// How a fixed logical resolution is fitted into a window of a different shape
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AspectPolicy {
+    /// Fills the window, distorting the image if the shapes differ
+    Stretch,
+    /// Scales to fit, with black bars on the sides (pillarbox) or top and bottom (letterbox)
+    #[default]
+    Letterbox,
+    /// As Letterbox, but only at whole-number scales, for crisp pixel art;
+    /// a window too small for even 1x falls back to Letterbox
+    IntegerScale,
+    /// Scales to fit, then grows the logical size along the longer axis to fill the window
+    Expand,
+}
+
+/// A logical size the game is designed for, independent of the window's size
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VirtualResolution {
+    pub width: f32,
+    pub height: f32,
+    pub policy: AspectPolicy,
+}
+
+impl VirtualResolution {
+    pub fn new(width: f32, height: f32, policy: AspectPolicy) -> Self {
+        assert!(
+            width > 0.0 && height > 0.0,
+            "virtual resolution must not be empty"
+        );
+        VirtualResolution {
+            width,
+            height,
+            policy,
+        }
+    }
+
+    /// Where the game's image goes in a drawable area of the given pixel size
+    pub fn layout(
+        &self,
+        width_pixels: WindowExtent,
+        height_pixels: WindowExtent,
+    ) -> ViewportLayout {
+        let window = Vector2D::new(width_pixels as f32, height_pixels as f32);
+        let design = Vector2D::new(self.width, self.height);
+        let fit = (window / design).min_element();
+
+        let (size, logical_size) = match self.policy {
+            AspectPolicy::Stretch => (window, design),
+            AspectPolicy::Letterbox => (design * fit, design),
+            AspectPolicy::IntegerScale if fit < 1.0 => (design * fit, design),
+            AspectPolicy::IntegerScale => (design * fit.floor(), design),
+            AspectPolicy::Expand => (window, window / fit),
+        };
+        let size = size.round().min(window);
+        let offset = ((window - size) * 0.5).floor();
+
+        ViewportLayout {
+            x: offset.x as WindowExtent,
+            y: offset.y as WindowExtent,
+            width: size.x as WindowExtent,
+            height: size.y as WindowExtent,
+            logical_size,
+        }
+    }
+}
+
+/// The region of the drawable area the game renders into, in pixels from the
+/// top left, along with the logical size that region represents.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ViewportLayout {
+    pub x: WindowExtent,
+    pub y: WindowExtent,
+    pub width: WindowExtent,
+    pub height: WindowExtent,
+    pub logical_size: Vector2D,
+}
+
+impl ViewportLayout {
+    /// The whole window, measured in points
+    pub fn full_window(size: WindowSize) -> Self {
+        ViewportLayout {
+            x: 0,
+            y: 0,
+            width: size.width_pixels,
+            height: size.height_pixels,
+            logical_size: Vector2D::new(size.width as f32, size.height as f32),
+        }
+    }
+
+    pub fn aspect_ratio(&self) -> f32 {
+        self.logical_size.x / self.logical_size.y
+    }
+
+    /// Is there any of the window left uncovered?
+    pub fn has_bars(&self, size: WindowSize) -> bool {
+        self.width < size.width_pixels || self.height < size.height_pixels
+    }
+
+    /// Converts a position in window pixels to logical coordinates.  Positions on
+    /// the bars fall outside 0..logical_size.
+    pub fn pixels_to_logical(&self, pixels: Vector2D) -> Vector2D {
+        let viewport_size = Vector2D::new(self.width.max(1) as f32, self.height.max(1) as f32);
+        (pixels - Vector2D::new(self.x as f32, self.y as f32)) / viewport_size * self.logical_size
+    }
+
+    pub fn logical_to_pixels(&self, logical: Vector2D) -> Vector2D {
+        let viewport_size = Vector2D::new(self.width as f32, self.height as f32);
+        logical / self.logical_size * viewport_size + Vector2D::new(self.x as f32, self.y as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn policies_on_wide_window() {
+        // 4:3 design on a 16:9 window
+        let design = |policy| VirtualResolution::new(320.0, 240.0, policy).layout(1920, 1080);
+
+        let letterbox = design(AspectPolicy::Letterbox);
+        assert_eq!((letterbox.x, letterbox.y), (240, 0));
+        assert_eq!((letterbox.width, letterbox.height), (1440, 1080));
+        assert_eq!(
+            letterbox.pixels_to_logical(Vector2D::new(960.0, 540.0)),
+            Vector2D::new(160.0, 120.0)
+        );
+
+        let integer = design(AspectPolicy::IntegerScale);
+        assert_eq!((integer.x, integer.y), (320, 60));
+        assert_eq!((integer.width, integer.height), (1280, 960));
+
+        let expand = design(AspectPolicy::Expand);
+        assert_eq!((expand.width, expand.height), (1920, 1080));
+        assert_eq!(expand.logical_size, Vector2D::new(426.66666, 240.0));
+
+        let stretch = design(AspectPolicy::Stretch);
+        assert_eq!(stretch.logical_size, Vector2D::new(320.0, 240.0));
+        assert_eq!(stretch.width, 1920);
+
+        // too small for 1x, so scaled down keeping its shape
+        let small =
+            VirtualResolution::new(320.0, 240.0, AspectPolicy::IntegerScale).layout(200, 300);
+        assert_eq!((small.x, small.y), (0, 75));
+        assert_eq!((small.width, small.height), (200, 150));
+    }
+}
@@ -0,0 +1,48 @@
+use common::audio::{AudioOutput, AudioSource, AudioSpec};
+use sdl2::audio::{AudioCallback, AudioDevice};
+
+#[allow(non_camel_case_types)]
+pub struct SDL2_AudioCallback {
+    source: Box<dyn AudioSource>,
+    channels: u8,
+}
+
+impl AudioCallback for SDL2_AudioCallback {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        self.source.fill(out, self.channels);
+    }
+}
+
+impl SDL2_AudioCallback {
+    pub fn new(source: Box<dyn AudioSource>, channels: u8) -> Self {
+        SDL2_AudioCallback { source, channels }
+    }
+}
+
+#[allow(non_camel_case_types)]
+pub struct SDL2_AudioOutput {
+    device: AudioDevice<SDL2_AudioCallback>,
+    spec: AudioSpec,
+}
+
+impl SDL2_AudioOutput {
+    pub fn new(device: AudioDevice<SDL2_AudioCallback>, spec: AudioSpec) -> Self {
+        SDL2_AudioOutput { device, spec }
+    }
+}
+
+impl AudioOutput for SDL2_AudioOutput {
+    fn spec(&self) -> AudioSpec {
+        self.spec
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        if paused {
+            self.device.pause();
+        } else {
+            self.device.resume();
+        }
+    }
+}
@@ -0,0 +1,379 @@
+use std::collections::HashMap;
+
+use common::{
+    Vector2D,
+    input::{
+        FingerId, GamepadAxis, GamepadButton, GamepadId, InputBackend, InputEvent, KeyModifiers,
+        Keycode, MouseButton, Scancode, WheelSource,
+    },
+    window::{WindowExtent, WindowSize},
+};
+use log::{info, warn};
+use sdl2::{
+    EventPump, GameControllerSubsystem, VideoSubsystem,
+    controller::{self, GameController},
+    event::{Event, WindowEvent},
+    keyboard::{self, Mod},
+    mouse,
+    rect::Rect,
+};
+use sdl2_sys::{
+    SDL_GL_GetDrawableSize, SDL_GetKeyboardFocus, SDL_GetMouseFocus, SDL_GetWindowFromID,
+    SDL_GetWindowSize,
+};
+
+// sdl keycodes for keys without a character are their scancode with this bit set
+const SCANCODE_MASK: i32 = 1 << 30;
+
+#[allow(non_camel_case_types)]
+pub struct SDL2_InputBackend {
+    event_pump: EventPump,
+    video: VideoSubsystem,
+    controller_subsystem: Option<GameControllerSubsystem>,
+    controllers: HashMap<GamepadId, GameController>,
+}
+
+impl SDL2_InputBackend {
+    pub fn new(
+        event_pump: EventPump,
+        video: VideoSubsystem,
+        controller_subsystem: Option<GameControllerSubsystem>,
+    ) -> Self {
+        SDL2_InputBackend {
+            event_pump,
+            video,
+            controller_subsystem,
+            controllers: HashMap::new(),
+        }
+    }
+
+    // sdl reports controllers which were plugged in before startup as being added, too
+    fn open_controller(&mut self, joystick_index: u32) -> Option<InputEvent> {
+        let subsystem = self.controller_subsystem.as_ref()?;
+        match subsystem.open(joystick_index) {
+            Ok(controller) => {
+                let id = controller.instance_id();
+                let name = controller.name();
+                info!("Gamepad {} connected: {}", id, name);
+                self.controllers.insert(id, controller);
+                Some(InputEvent::GamepadAdded { id, name })
+            }
+            Err(e) => {
+                warn!("unable to open gamepad {}: {}", joystick_index, e);
+                None
+            }
+        }
+    }
+
+    fn convert_event(&mut self, event: Event) -> Option<InputEvent> {
+        match event {
+            Event::Quit { .. } => Some(InputEvent::Quit),
+            Event::Window {
+                window_id,
+                win_event,
+                ..
+            } => match win_event {
+                WindowEvent::SizeChanged(width, height) => Some(InputEvent::WindowResized(
+                    window_size(window_id, width, height),
+                )),
+                WindowEvent::FocusGained => Some(InputEvent::WindowFocus(true)),
+                WindowEvent::FocusLost => Some(InputEvent::WindowFocus(false)),
+                _ => None,
+            },
+
+            Event::KeyDown {
+                keycode,
+                scancode,
+                keymod,
+                repeat,
+                ..
+            } => Some(InputEvent::KeyDown {
+                scancode: convert_scancode(scancode),
+                keycode: convert_keycode(keycode),
+                modifiers: convert_modifiers(keymod),
+                repeat,
+            }),
+            Event::KeyUp {
+                keycode,
+                scancode,
+                keymod,
+                ..
+            } => Some(InputEvent::KeyUp {
+                scancode: convert_scancode(scancode),
+                keycode: convert_keycode(keycode),
+                modifiers: convert_modifiers(keymod),
+            }),
+            Event::TextInput { text, .. } => Some(InputEvent::TextInput(text)),
+            Event::TextEditing {
+                text,
+                start,
+                length,
+                ..
+            } => Some(InputEvent::TextEditing {
+                text,
+                cursor: start,
+                selection: length,
+            }),
+
+            Event::MouseMotion {
+                x, y, xrel, yrel, ..
+            } => Some(InputEvent::MouseMotion {
+                position: Vector2D::new(x as f32, y as f32),
+                delta: Vector2D::new(xrel as f32, yrel as f32),
+            }),
+            Event::MouseButtonDown {
+                mouse_btn,
+                clicks,
+                x,
+                y,
+                ..
+            } => convert_button(mouse_btn).map(|button| InputEvent::MouseButtonDown {
+                button,
+                position: Vector2D::new(x as f32, y as f32),
+                clicks,
+            }),
+            Event::MouseButtonUp {
+                mouse_btn, x, y, ..
+            } => convert_button(mouse_btn).map(|button| InputEvent::MouseButtonUp {
+                button,
+                position: Vector2D::new(x as f32, y as f32),
+            }),
+            Event::MouseWheel {
+                direction,
+                precise_x,
+                precise_y,
+                ..
+            } => {
+                let flip = if direction == mouse::MouseWheelDirection::Flipped {
+                    -1.0
+                } else {
+                    1.0
+                };
+                // sdl doesn't say which device scrolled, but only trackpads send part notches
+                let source = if precise_x.fract() != 0.0 || precise_y.fract() != 0.0 {
+                    WheelSource::Trackpad
+                } else {
+                    WheelSource::Wheel
+                };
+                Some(InputEvent::MouseWheel {
+                    delta: Vector2D::new(precise_x, precise_y) * flip,
+                    source,
+                })
+            }
+
+            Event::FingerDown {
+                touch_id,
+                finger_id,
+                x,
+                y,
+                pressure,
+                ..
+            } => Some(InputEvent::TouchDown {
+                finger: finger(touch_id, finger_id),
+                position: touch_to_window(x, y),
+                pressure,
+            }),
+            Event::FingerMotion {
+                touch_id,
+                finger_id,
+                x,
+                y,
+                dx,
+                dy,
+                pressure,
+                ..
+            } => Some(InputEvent::TouchMotion {
+                finger: finger(touch_id, finger_id),
+                position: touch_to_window(x, y),
+                delta: touch_to_window(dx, dy),
+                pressure,
+            }),
+            Event::FingerUp {
+                touch_id,
+                finger_id,
+                x,
+                y,
+                ..
+            } => Some(InputEvent::TouchUp {
+                finger: finger(touch_id, finger_id),
+                position: touch_to_window(x, y),
+            }),
+
+            Event::ControllerDeviceAdded { which, .. } => self.open_controller(which),
+            Event::ControllerDeviceRemoved { which, .. } => {
+                info!("Gamepad {} disconnected", which);
+                self.controllers.remove(&which);
+                Some(InputEvent::GamepadRemoved { id: which })
+            }
+            Event::ControllerButtonDown { which, button, .. } => convert_gamepad_button(button)
+                .map(|button| InputEvent::GamepadButtonDown { id: which, button }),
+            Event::ControllerButtonUp { which, button, .. } => convert_gamepad_button(button)
+                .map(|button| InputEvent::GamepadButtonUp { id: which, button }),
+            Event::ControllerAxisMotion {
+                which, axis, value, ..
+            } => Some(InputEvent::GamepadAxisMotion {
+                id: which,
+                axis: convert_gamepad_axis(axis),
+                value: (value as f32 / i16::MAX as f32).clamp(-1.0, 1.0),
+            }),
+
+            _ => None,
+        }
+    }
+}
+
+impl InputBackend for SDL2_InputBackend {
+    fn poll_events(&mut self) -> Vec<InputEvent> {
+        let events: Vec<Event> = self.event_pump.poll_iter().collect();
+        events
+            .into_iter()
+            .filter_map(|event| self.convert_event(event))
+            .collect()
+    }
+
+    fn start_text_input(&mut self) {
+        self.video.text_input().start();
+    }
+
+    fn stop_text_input(&mut self) {
+        self.video.text_input().stop();
+    }
+
+    fn set_text_input_rect(&mut self, position: Vector2D, size: Vector2D) {
+        self.video.text_input().set_rect(Rect::new(
+            position.x as i32,
+            position.y as i32,
+            size.x.max(1.0) as u32,
+            size.y.max(1.0) as u32,
+        ));
+    }
+
+    fn set_rumble(
+        &mut self,
+        gamepad: GamepadId,
+        low_frequency: f32,
+        high_frequency: f32,
+        seconds: f32,
+    ) {
+        let Some(controller) = self.controllers.get_mut(&gamepad) else {
+            warn!("no gamepad {} to rumble", gamepad);
+            return;
+        };
+        let strength = |s: f32| (s.clamp(0.0, 1.0) * u16::MAX as f32) as u16;
+        if let Err(e) = controller.set_rumble(
+            strength(low_frequency),
+            strength(high_frequency),
+            (seconds.max(0.0) * 1000.0) as u32,
+        ) {
+            // plenty of gamepads have no motors
+            warn!("unable to rumble gamepad {}: {}", gamepad, e);
+        }
+    }
+}
+
+// resize events arrive in points; the drawable size in pixels has to be asked for
+fn window_size(window_id: u32, width: i32, height: i32) -> WindowSize {
+    let mut size = WindowSize::from_points(width as WindowExtent, height as WindowExtent);
+    unsafe {
+        let window = SDL_GetWindowFromID(window_id);
+        if !window.is_null() {
+            let (mut width_pixels, mut height_pixels) = (0, 0);
+            SDL_GL_GetDrawableSize(window, &mut width_pixels, &mut height_pixels);
+            if width_pixels > 0 && height_pixels > 0 {
+                size.width_pixels = width_pixels as WindowExtent;
+                size.height_pixels = height_pixels as WindowExtent;
+            }
+        }
+    }
+    size
+}
+
+// finger ids are only unique on their own touch device
+fn finger(touch_id: i64, finger_id: i64) -> FingerId {
+    touch_id.wrapping_mul(1_000_003).wrapping_add(finger_id)
+}
+
+// touch positions arrive normalised to 0..1 across the window the finger is on
+fn touch_to_window(x: f32, y: f32) -> Vector2D {
+    let (mut width, mut height) = (0, 0);
+    unsafe {
+        let mut window = SDL_GetMouseFocus();
+        if window.is_null() {
+            window = SDL_GetKeyboardFocus();
+        }
+        if !window.is_null() {
+            SDL_GetWindowSize(window, &mut width, &mut height);
+        }
+    }
+    Vector2D::new(x * width as f32, y * height as f32)
+}
+
+fn convert_scancode(scancode: Option<keyboard::Scancode>) -> Scancode {
+    scancode.map_or(Scancode::Unknown, |scancode| {
+        Scancode::from_usb_hid(scancode as u32)
+    })
+}
+
+fn convert_keycode(keycode: Option<keyboard::Keycode>) -> Keycode {
+    let Some(keycode) = keycode else {
+        return Keycode::Unknown;
+    };
+    let code = keycode.into_i32();
+    if code & SCANCODE_MASK != 0 {
+        Keycode::from_scancode(Scancode::from_usb_hid((code & !SCANCODE_MASK) as u32))
+    } else {
+        char::from_u32(code as u32).map_or(Keycode::Unknown, Keycode::from_char)
+    }
+}
+
+fn convert_modifiers(keymod: Mod) -> KeyModifiers {
+    KeyModifiers {
+        shift: keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD),
+        ctrl: keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD),
+        alt: keymod.intersects(Mod::LALTMOD | Mod::RALTMOD),
+        gui: keymod.intersects(Mod::LGUIMOD | Mod::RGUIMOD),
+    }
+}
+
+fn convert_button(button: mouse::MouseButton) -> Option<MouseButton> {
+    match button {
+        mouse::MouseButton::Left => Some(MouseButton::Left),
+        mouse::MouseButton::Middle => Some(MouseButton::Middle),
+        mouse::MouseButton::Right => Some(MouseButton::Right),
+        mouse::MouseButton::X1 => Some(MouseButton::X1),
+        mouse::MouseButton::X2 => Some(MouseButton::X2),
+        mouse::MouseButton::Unknown => None,
+    }
+}
+
+fn convert_gamepad_button(button: controller::Button) -> Option<GamepadButton> {
+    match button {
+        controller::Button::A => Some(GamepadButton::A),
+        controller::Button::B => Some(GamepadButton::B),
+        controller::Button::X => Some(GamepadButton::X),
+        controller::Button::Y => Some(GamepadButton::Y),
+        controller::Button::Back => Some(GamepadButton::Back),
+        controller::Button::Guide => Some(GamepadButton::Guide),
+        controller::Button::Start => Some(GamepadButton::Start),
+        controller::Button::LeftStick => Some(GamepadButton::LeftStick),
+        controller::Button::RightStick => Some(GamepadButton::RightStick),
+        controller::Button::LeftShoulder => Some(GamepadButton::LeftShoulder),
+        controller::Button::RightShoulder => Some(GamepadButton::RightShoulder),
+        controller::Button::DPadUp => Some(GamepadButton::DPadUp),
+        controller::Button::DPadDown => Some(GamepadButton::DPadDown),
+        controller::Button::DPadLeft => Some(GamepadButton::DPadLeft),
+        controller::Button::DPadRight => Some(GamepadButton::DPadRight),
+        _ => None,
+    }
+}
+
+fn convert_gamepad_axis(axis: controller::Axis) -> GamepadAxis {
+    match axis {
+        controller::Axis::LeftX => GamepadAxis::LeftX,
+        controller::Axis::LeftY => GamepadAxis::LeftY,
+        controller::Axis::RightX => GamepadAxis::RightX,
+        controller::Axis::RightY => GamepadAxis::RightY,
+        controller::Axis::TriggerLeft => GamepadAxis::TriggerLeft,
+        controller::Axis::TriggerRight => GamepadAxis::TriggerRight,
+    }
+}
@@ -1,19 +1,35 @@
+use std::collections::{HashMap, hash_map::Entry};
+
 use common::{
+    audio::{AudioOutput, AudioSource, AudioSpec},
     backend::{Backend, BackendVersion, DisplayBoundsInfo, DisplayModeInfo},
+    input::{CursorType, InputBackend},
     vs_error::VSError,
-    window::{WindowBuffers, WindowDepth, WindowExtent, WindowType},
+    window::{WindowBuffers, WindowDepth, WindowExtent, WindowSize, WindowType},
 };
 use log::{error, info, warn};
 use sdl2::{
     Sdl,
-    video::{GLProfile, Window},
+    audio::AudioSpecDesired,
+    mouse::{Cursor, SystemCursor},
+    video::{GLContext, GLProfile, SwapInterval, Window},
 };
 use sdl2_sys::SDL_WindowFlags;
 
+use crate::{
+    audio_backend::{SDL2_AudioCallback, SDL2_AudioOutput},
+    input_backend::SDL2_InputBackend,
+};
+
+pub mod audio_backend;
+pub mod input_backend;
+
 #[allow(non_camel_case_types)]
 pub struct SDL2_Backend {
     sdl_context: Sdl,
     sdl_window: Option<Window>,
+    sdl_gl_context: Option<GLContext>,
+    cursors: HashMap<CursorType, Cursor>,
 }
 
 impl Backend for SDL2_Backend {
@@ -27,6 +43,8 @@ impl Backend for SDL2_Backend {
         SDL2_Backend {
             sdl_context,
             sdl_window: None,
+            sdl_gl_context: None,
+            cursors: HashMap::new(),
         }
     }
 
@@ -132,7 +150,8 @@ impl Backend for SDL2_Backend {
         window_type: WindowType,
         _buffer_count: WindowBuffers,
         _antialiass: bool,
-        _vsyncnc: bool,
+        vsync: bool,
+        high_dpi: bool,
     ) -> Result<(), VSError> {
         match &self.sdl_context.video() {
             Ok(video_subsystem) => {
@@ -150,7 +169,13 @@ impl Backend for SDL2_Backend {
                             info!("videoFlag added: Resizable");
                             SDL_WindowFlags::SDL_WINDOW_RESIZABLE
                         }
-                    } as u32;
+                    } as u32
+                    | if high_dpi {
+                        info!("videoFlag added: Allow High DPI");
+                        SDL_WindowFlags::SDL_WINDOW_ALLOW_HIGHDPI as u32
+                    } else {
+                        0
+                    };
 
                 let attributes = video_subsystem.gl_attr();
                 attributes.set_double_buffer(true);
@@ -175,6 +200,26 @@ impl Backend for SDL2_Backend {
                     .build()
                 {
                     Ok(sdl_window) => {
+                        // the renderer finds this context through SDL once it is current
+                        let gl_context = match sdl_window.gl_create_context() {
+                            Ok(gl_context) => gl_context,
+                            Err(e) => {
+                                error!(
+                                    "unable to create gl context during sdl2 window creation: {e}"
+                                );
+                                return Err(VSError::Backend_NoGLContext);
+                            }
+                        };
+                        let swap_interval = if vsync {
+                            SwapInterval::VSync
+                        } else {
+                            SwapInterval::Immediate
+                        };
+                        if let Err(e) = video_subsystem.gl_set_swap_interval(swap_interval) {
+                            warn!("unable to set swap interval during sdl2 window creation: {e}");
+                        }
+
+                        self.sdl_gl_context = Some(gl_context);
                         self.sdl_window = Some(sdl_window);
                         Ok(())
                     }
@@ -196,9 +241,111 @@ impl Backend for SDL2_Backend {
         }
     }
 
+    fn window_size(&self) -> Option<WindowSize> {
+        self.sdl_window.as_ref().map(|window| {
+            let (width, height) = window.size();
+            let (width_pixels, height_pixels) = window.drawable_size();
+            WindowSize {
+                width: width as WindowExtent,
+                height: height as WindowExtent,
+                width_pixels: width_pixels as WindowExtent,
+                height_pixels: height_pixels as WindowExtent,
+            }
+        })
+    }
+
     fn show_cursor(&mut self, show: bool) {
         self.sdl_context.mouse().show_cursor(show);
     }
+
+    fn set_cursor(&mut self, cursor: CursorType) {
+        let sdl_cursor = match self.cursors.entry(cursor) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => match Cursor::from_system(system_cursor(cursor)) {
+                Ok(sdl_cursor) => entry.insert(sdl_cursor),
+                Err(e) => {
+                    error!("unable to create {:?} cursor in sdl2 backend: {e}", cursor);
+                    return;
+                }
+            },
+        };
+        sdl_cursor.set();
+    }
+
+    fn input_backend(&mut self) -> Result<Box<dyn InputBackend>, VSError> {
+        let video = self.sdl_context.video().map_err(|e| {
+            error!("no video subsystem for sdl2 input backend: {e}");
+            VSError::Backend_NoVideo
+        })?;
+        let event_pump = self.sdl_context.event_pump().map_err(|e| {
+            error!("unable to get sdl2 event pump: {e}");
+            VSError::Backend_NoEventPump
+        })?;
+        // without gamepads, we still have a keyboard and mouse
+        let controller_subsystem = self
+            .sdl_context
+            .game_controller()
+            .map_err(|e| warn!("no sdl2 game controller subsystem: {e}"))
+            .ok();
+        Ok(Box::new(SDL2_InputBackend::new(
+            event_pump,
+            video,
+            controller_subsystem,
+        )))
+    }
+
+    fn open_audio(
+        &mut self,
+        desired: AudioSpec,
+        source: Box<dyn AudioSource>,
+    ) -> Result<Box<dyn AudioOutput>, VSError> {
+        let audio = self.sdl_context.audio().map_err(|e| {
+            error!("no audio subsystem in sdl2 backend: {e}");
+            VSError::Backend_NoAudio
+        })?;
+        let desired_spec = AudioSpecDesired {
+            freq: Some(desired.sample_rate as i32),
+            channels: Some(desired.channels),
+            samples: Some(desired.buffer_frames),
+        };
+
+        let mut spec = desired;
+        let device = audio
+            .open_playback(None, &desired_spec, |obtained| {
+                spec = AudioSpec {
+                    sample_rate: obtained.freq as u32,
+                    channels: obtained.channels,
+                    buffer_frames: obtained.samples,
+                };
+                SDL2_AudioCallback::new(source, obtained.channels)
+            })
+            .map_err(|e| {
+                error!("unable to open sdl2 audio device: {e}");
+                VSError::Backend_NoAudio
+            })?;
+        info!(
+            "Opened audio: {} Hz, {} channels, {} frame buffer",
+            spec.sample_rate, spec.channels, spec.buffer_frames
+        );
+        Ok(Box::new(SDL2_AudioOutput::new(device, spec)))
+    }
+}
+
+fn system_cursor(cursor: CursorType) -> SystemCursor {
+    match cursor {
+        CursorType::Arrow => SystemCursor::Arrow,
+        CursorType::IBeam => SystemCursor::IBeam,
+        CursorType::Wait => SystemCursor::Wait,
+        CursorType::Crosshair => SystemCursor::Crosshair,
+        CursorType::WaitArrow => SystemCursor::WaitArrow,
+        CursorType::SizeNWSE => SystemCursor::SizeNWSE,
+        CursorType::SizeNESW => SystemCursor::SizeNESW,
+        CursorType::SizeWE => SystemCursor::SizeWE,
+        CursorType::SizeNS => SystemCursor::SizeNS,
+        CursorType::SizeAll => SystemCursor::SizeAll,
+        CursorType::No => SystemCursor::No,
+        CursorType::Hand => SystemCursor::Hand,
+    }
 }
 
 // ====================================
@@ -0,0 +1,136 @@
+use crate::effects::EffectChain;
+
+/// Identifies a bus in the mixer
+pub type BusId = usize;
+
+/// Everything ends up here
+pub const MASTER_BUS: BusId = 0;
+pub const MUSIC_BUS: BusId = 1;
+/// Where sounds play unless told otherwise
+pub const EFFECTS_BUS: BusId = 2;
+/// Inside the effects bus, so menu sounds follow the effect volume
+pub const UI_BUS: BusId = 3;
+
+/// Turns a bus down while another bus is playing anything, e.g. music under
+/// dialogue.  Times are in seconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ducking {
+    pub trigger: BusId,
+    /// The bus's gain while ducked
+    pub gain: f32,
+    pub attack: f32,
+    pub release: f32,
+}
+
+/// A group of sounds mixed together, with one volume.  Each bus feeds its
+/// parent, so its volume is scaled by theirs.
+#[derive(Debug)]
+pub(crate) struct Bus {
+    name: String,
+    parent: Option<BusId>,
+    volume: f32,
+    muted: bool,
+    ducking: Option<Ducking>,
+    duck_gain: f32,
+    gain: f32, // as applied at the end of the last block
+    pub(crate) buffer: Vec<f32>,
+    pub(crate) effects: EffectChain,
+}
+
+impl Bus {
+    pub(crate) fn new(name: &str, parent: Option<BusId>) -> Self {
+        Bus {
+            name: name.to_string(),
+            parent,
+            volume: 1.0,
+            muted: false,
+            ducking: None,
+            duck_gain: 1.0,
+            gain: 1.0,
+            buffer: Vec::new(),
+            effects: EffectChain::default(),
+        }
+    }
+
+    /// Master, music, effects and UI
+    pub(crate) fn standard() -> Vec<Bus> {
+        vec![
+            Bus::new("master", None),
+            Bus::new("music", Some(MASTER_BUS)),
+            Bus::new("effects", Some(MASTER_BUS)),
+            Bus::new("ui", Some(EFFECTS_BUS)),
+        ]
+    }
+
+    pub(crate) fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub(crate) fn get_parent(&self) -> Option<BusId> {
+        self.parent
+    }
+
+    pub(crate) fn get_volume(&self) -> f32 {
+        self.volume
+    }
+
+    pub(crate) fn set_volume(&mut self, volume: f32) {
+        self.volume = volume.max(0.0);
+    }
+
+    pub(crate) fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    pub(crate) fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+    }
+
+    pub(crate) fn get_ducking(&self) -> Option<Ducking> {
+        self.ducking
+    }
+
+    pub(crate) fn set_ducking(&mut self, ducking: Option<Ducking>) {
+        self.ducking = ducking;
+        if ducking.is_none() {
+            self.duck_gain = 1.0;
+        }
+    }
+
+    /// How far the bus is ducked right now, from 1 down to the ducking gain
+    pub(crate) fn get_duck_gain(&self) -> f32 {
+        self.duck_gain
+    }
+
+    /// Moves the duck gain along for a block of `seconds`
+    pub(crate) fn update_ducking(&mut self, triggered: bool, seconds: f32) {
+        let Some(ducking) = self.ducking else {
+            return;
+        };
+        let depth = (1.0 - ducking.gain).max(f32::EPSILON);
+        if triggered {
+            let step = depth * seconds / ducking.attack.max(f32::EPSILON);
+            self.duck_gain = (self.duck_gain - step).max(ducking.gain);
+        } else {
+            let step = depth * seconds / ducking.release.max(f32::EPSILON);
+            self.duck_gain = (self.duck_gain + step).min(1.0);
+        }
+    }
+
+    /// Scales the buffer, ramping from last block's gain to avoid clicks
+    pub(crate) fn apply_gain(&mut self) {
+        let target = if self.muted {
+            0.0
+        } else {
+            self.volume * self.duck_gain
+        };
+        let frames = (self.buffer.len() / 2).max(1);
+        let step = (target - self.gain) / frames as f32;
+        for frame in self.buffer.chunks_exact_mut(2) {
+            self.gain += step;
+            frame[0] *= self.gain;
+            frame[1] *= self.gain;
+        }
+        self.gain = target;
+    }
+}
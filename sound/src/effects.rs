@@ -0,0 +1,606 @@
+//! Insert effects, run on a bus's mix before its volume is applied.  Each
+//! effect is described by plain parameters; changing them can be spread over
+//! time, so a game can, say, close a low-pass filter on the music as it pauses.
+
+use std::f32::consts::TAU;
+
+/// Identifies an effect on a bus
+pub type EffectId = u32;
+
+// effects change parameters this often while automating
+const AUTOMATION_FRAMES: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterKind {
+    LowPass,
+    HighPass,
+    BandPass,
+}
+
+/// Times are in seconds and levels in decibels.  Wet/dry `mix` runs from 0,
+/// only the dry sound, to 1, only the effect.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Effect {
+    /// A two pole filter; `q` of 0.707 is flat, higher rings at the cutoff
+    Filter {
+        kind: FilterKind,
+        cutoff: f32,
+        q: f32,
+    },
+    /// `room_size` and `damping` run from 0 to 1
+    Reverb {
+        room_size: f32,
+        damping: f32,
+        mix: f32,
+    },
+    /// An echo every `time`, each `feedback` times as loud as the last
+    Delay { time: f32, feedback: f32, mix: f32 },
+    /// Turns down anything louder than `threshold` by `ratio`
+    Compressor {
+        threshold: f32,
+        ratio: f32,
+        attack: f32,
+        release: f32,
+        makeup: f32,
+    },
+    /// Keeps peaks under `ceiling`, pulling the gain down at once and letting
+    /// it back up over `release`
+    Limiter { ceiling: f32, release: f32 },
+}
+
+impl Effect {
+    /// The limiter every master bus starts with, to stop clipping
+    pub const MASTER_LIMITER: Effect = Effect::Limiter {
+        ceiling: -0.1,
+        release: 0.1,
+    };
+
+    /// Part way from `self` to `other`.  Effects of different kinds can't be
+    /// blended, so it's one or the other.
+    pub fn lerp(&self, other: &Effect, t: f32) -> Effect {
+        let mix = |a: f32, b: f32| a + (b - a) * t;
+        // frequencies sweep evenly by octave rather than by Hz
+        let sweep = |a: f32, b: f32| a * (b / a.max(f32::EPSILON)).powf(t);
+        match (*self, *other) {
+            (
+                Effect::Filter { kind, cutoff, q },
+                Effect::Filter {
+                    kind: other_kind,
+                    cutoff: other_cutoff,
+                    q: other_q,
+                },
+            ) if kind == other_kind => Effect::Filter {
+                kind,
+                cutoff: sweep(cutoff, other_cutoff),
+                q: mix(q, other_q),
+            },
+            (
+                Effect::Reverb {
+                    room_size,
+                    damping,
+                    mix: wet,
+                },
+                Effect::Reverb {
+                    room_size: other_room_size,
+                    damping: other_damping,
+                    mix: other_wet,
+                },
+            ) => Effect::Reverb {
+                room_size: mix(room_size, other_room_size),
+                damping: mix(damping, other_damping),
+                mix: mix(wet, other_wet),
+            },
+            (
+                Effect::Delay {
+                    time,
+                    feedback,
+                    mix: wet,
+                },
+                Effect::Delay {
+                    time: other_time,
+                    feedback: other_feedback,
+                    mix: other_wet,
+                },
+            ) => Effect::Delay {
+                time: mix(time, other_time),
+                feedback: mix(feedback, other_feedback),
+                mix: mix(wet, other_wet),
+            },
+            (
+                Effect::Compressor {
+                    threshold,
+                    ratio,
+                    attack,
+                    release,
+                    makeup,
+                },
+                Effect::Compressor {
+                    threshold: other_threshold,
+                    ratio: other_ratio,
+                    attack: other_attack,
+                    release: other_release,
+                    makeup: other_makeup,
+                },
+            ) => Effect::Compressor {
+                threshold: mix(threshold, other_threshold),
+                ratio: mix(ratio, other_ratio),
+                attack: mix(attack, other_attack),
+                release: mix(release, other_release),
+                makeup: mix(makeup, other_makeup),
+            },
+            (
+                Effect::Limiter { ceiling, release },
+                Effect::Limiter {
+                    ceiling: other_ceiling,
+                    release: other_release,
+                },
+            ) => Effect::Limiter {
+                ceiling: mix(ceiling, other_ceiling),
+                release: mix(release, other_release),
+            },
+            _ if t < 1.0 => *self,
+            _ => *other,
+        }
+    }
+
+    fn same_kind(&self, other: &Effect) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+}
+
+pub(crate) fn db_to_gain(db: f32) -> f32 {
+    10.0f32.powf(db / 20.0)
+}
+
+fn gain_to_db(gain: f32) -> f32 {
+    20.0 * gain.max(1e-9).log10()
+}
+
+// how much of the way to a target a smoothed value moves each frame
+fn smoothing(seconds: f32, rate: f32) -> f32 {
+    if seconds <= 0.0 {
+        1.0
+    } else {
+        1.0 - (-1.0 / (seconds * rate)).exp()
+    }
+}
+
+/// One effect in a bus's chain, and what it remembers between blocks
+#[derive(Debug)]
+pub(crate) struct EffectSlot {
+    id: EffectId,
+    from: Effect,
+    target: Effect,
+    ramp: usize, // frames to go
+    ramp_length: usize,
+    state: EffectState,
+}
+
+impl EffectSlot {
+    pub(crate) fn new(id: EffectId, effect: Effect) -> Self {
+        EffectSlot {
+            id,
+            from: effect,
+            target: effect,
+            ramp: 0,
+            ramp_length: 0,
+            state: EffectState::default(),
+        }
+    }
+
+    pub(crate) fn get_id(&self) -> EffectId {
+        self.id
+    }
+
+    /// Where it's heading, if it's being automated
+    pub(crate) fn get_effect(&self) -> Effect {
+        self.target
+    }
+
+    pub(crate) fn current(&self) -> Effect {
+        if self.ramp == 0 {
+            self.target
+        } else {
+            let t = 1.0 - self.ramp as f32 / self.ramp_length as f32;
+            self.from.lerp(&self.target, t)
+        }
+    }
+
+    /// Moves to new parameters over `frames`
+    pub(crate) fn set_effect(&mut self, effect: Effect, frames: usize) {
+        if !self.target.same_kind(&effect) {
+            self.state = EffectState::default();
+            self.from = effect;
+            self.ramp = 0;
+        } else {
+            self.from = self.current();
+            self.ramp = frames;
+        }
+        self.target = effect;
+        self.ramp_length = frames;
+    }
+
+    pub(crate) fn process(&mut self, buffer: &mut [f32], rate: f32) {
+        for chunk in buffer.chunks_mut(AUTOMATION_FRAMES * 2) {
+            let effect = self.current();
+            self.state.process(&effect, chunk, rate);
+            self.ramp = self.ramp.saturating_sub(chunk.len() / 2);
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct EffectState {
+    biquad: [Biquad; 2],
+    reverb: Option<Box<Reverb>>,
+    delay: [Vec<f32>; 2],
+    delay_position: usize,
+    reduction: f32, // compressor gain reduction in dB
+    limiter_gain: Option<f32>,
+}
+
+impl EffectState {
+    fn process(&mut self, effect: &Effect, buffer: &mut [f32], rate: f32) {
+        match *effect {
+            Effect::Filter { kind, cutoff, q } => {
+                let coefficients = BiquadCoefficients::new(kind, cutoff, q, rate);
+                for frame in buffer.chunks_exact_mut(2) {
+                    for (channel, sample) in frame.iter_mut().enumerate() {
+                        *sample = self.biquad[channel].process(&coefficients, *sample);
+                    }
+                }
+            }
+            Effect::Reverb {
+                room_size,
+                damping,
+                mix,
+            } => {
+                let reverb = self
+                    .reverb
+                    .get_or_insert_with(|| Box::new(Reverb::new(rate)));
+                reverb.process(buffer, room_size, damping, mix);
+            }
+            Effect::Delay {
+                time,
+                feedback,
+                mix,
+            } => {
+                let length = ((time * rate) as usize).max(1);
+                for line in self.delay.iter_mut() {
+                    if line.len() != length {
+                        line.resize(length, 0.0);
+                    }
+                }
+                for frame in buffer.chunks_exact_mut(2) {
+                    self.delay_position %= length;
+                    for (channel, sample) in frame.iter_mut().enumerate() {
+                        let echo = self.delay[channel][self.delay_position];
+                        self.delay[channel][self.delay_position] = *sample + echo * feedback;
+                        *sample = *sample * (1.0 - mix) + echo * mix;
+                    }
+                    self.delay_position += 1;
+                }
+            }
+            Effect::Compressor {
+                threshold,
+                ratio,
+                attack,
+                release,
+                makeup,
+            } => {
+                let attack = smoothing(attack, rate);
+                let release = smoothing(release, rate);
+                let makeup = db_to_gain(makeup);
+                for frame in buffer.chunks_exact_mut(2) {
+                    let level = gain_to_db(frame[0].abs().max(frame[1].abs()));
+                    let over = (level - threshold).max(0.0);
+                    let reduction = over * (1.0 - 1.0 / ratio.max(1.0));
+                    let speed = if reduction > self.reduction {
+                        attack
+                    } else {
+                        release
+                    };
+                    self.reduction += (reduction - self.reduction) * speed;
+                    let gain = db_to_gain(-self.reduction) * makeup;
+                    frame[0] *= gain;
+                    frame[1] *= gain;
+                }
+            }
+            Effect::Limiter { ceiling, release } => {
+                let ceiling = db_to_gain(ceiling);
+                let release = smoothing(release, rate);
+                let mut gain = self.limiter_gain.unwrap_or(1.0);
+                for frame in buffer.chunks_exact_mut(2) {
+                    let peak = frame[0].abs().max(frame[1].abs());
+                    let needed = if peak > ceiling { ceiling / peak } else { 1.0 };
+                    gain = needed.min(gain + (1.0 - gain) * release);
+                    frame[0] *= gain;
+                    frame[1] *= gain;
+                }
+                self.limiter_gain = Some(gain);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct BiquadCoefficients {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+}
+
+impl BiquadCoefficients {
+    // from the Audio EQ Cookbook
+    fn new(kind: FilterKind, cutoff: f32, q: f32, rate: f32) -> Self {
+        let omega = TAU * cutoff.clamp(10.0, rate * 0.49) / rate;
+        let (sin, cos) = omega.sin_cos();
+        let alpha = sin / (2.0 * q.max(0.01));
+        let (b0, b1, b2) = match kind {
+            FilterKind::LowPass => ((1.0 - cos) / 2.0, 1.0 - cos, (1.0 - cos) / 2.0),
+            FilterKind::HighPass => ((1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0),
+            FilterKind::BandPass => (alpha, 0.0, -alpha),
+        };
+        let a0 = 1.0 + alpha;
+        BiquadCoefficients {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: -2.0 * cos / a0,
+            a2: (1.0 - alpha) / a0,
+        }
+    }
+}
+
+// transposed direct form II
+#[derive(Debug, Default, Clone, Copy)]
+struct Biquad {
+    z1: f32,
+    z2: f32,
+}
+
+impl Biquad {
+    fn process(&mut self, c: &BiquadCoefficients, input: f32) -> f32 {
+        let output = c.b0 * input + self.z1;
+        self.z1 = c.b1 * input - c.a1 * output + self.z2;
+        self.z2 = c.b2 * input - c.a2 * output;
+        output
+    }
+}
+
+// tunings from Freeverb, in samples at 44.1kHz
+const COMB_LENGTHS: [usize; 4] = [1116, 1188, 1277, 1356];
+const ALLPASS_LENGTHS: [usize; 2] = [556, 441];
+const STEREO_SPREAD: usize = 23;
+
+#[derive(Debug)]
+struct Comb {
+    buffer: Vec<f32>,
+    position: usize,
+    filtered: f32,
+}
+
+#[derive(Debug)]
+struct Allpass {
+    buffer: Vec<f32>,
+    position: usize,
+}
+
+/// A small Schroeder-Moorer reverb, as in Freeverb
+#[derive(Debug)]
+struct Reverb {
+    combs: [Vec<Comb>; 2],
+    allpasses: [Vec<Allpass>; 2],
+}
+
+impl Reverb {
+    fn new(rate: f32) -> Self {
+        let scale = |length: usize, channel: usize| {
+            (((length + channel * STEREO_SPREAD) as f32 * rate / 44100.0) as usize).max(1)
+        };
+        let combs = |channel| {
+            COMB_LENGTHS
+                .iter()
+                .map(|length| Comb {
+                    buffer: vec![0.0; scale(*length, channel)],
+                    position: 0,
+                    filtered: 0.0,
+                })
+                .collect()
+        };
+        let allpasses = |channel| {
+            ALLPASS_LENGTHS
+                .iter()
+                .map(|length| Allpass {
+                    buffer: vec![0.0; scale(*length, channel)],
+                    position: 0,
+                })
+                .collect()
+        };
+        Reverb {
+            combs: [combs(0), combs(1)],
+            allpasses: [allpasses(0), allpasses(1)],
+        }
+    }
+
+    fn process(&mut self, buffer: &mut [f32], room_size: f32, damping: f32, mix: f32) {
+        let feedback = 0.7 + 0.28 * room_size.clamp(0.0, 1.0);
+        let damping = damping.clamp(0.0, 1.0) * 0.4;
+        for frame in buffer.chunks_exact_mut(2) {
+            let input = (frame[0] + frame[1]) * 0.015;
+            for (channel, sample) in frame.iter_mut().enumerate() {
+                let mut wet = 0.0;
+                for comb in self.combs[channel].iter_mut() {
+                    let out = comb.buffer[comb.position];
+                    comb.filtered = out * (1.0 - damping) + comb.filtered * damping;
+                    comb.buffer[comb.position] = input + comb.filtered * feedback;
+                    comb.position = (comb.position + 1) % comb.buffer.len();
+                    wet += out;
+                }
+                for allpass in self.allpasses[channel].iter_mut() {
+                    let delayed = allpass.buffer[allpass.position];
+                    allpass.buffer[allpass.position] = wet + delayed * 0.5;
+                    allpass.position = (allpass.position + 1) % allpass.buffer.len();
+                    wet = delayed - wet;
+                }
+                *sample = *sample * (1.0 - mix) + wet * 3.0 * mix;
+            }
+        }
+    }
+}
+
+/// Runs every effect on a bus in turn
+#[derive(Debug, Default)]
+pub(crate) struct EffectChain {
+    slots: Vec<EffectSlot>,
+}
+
+impl EffectChain {
+    pub(crate) fn add(&mut self, id: EffectId, effect: Effect) {
+        self.slots.push(EffectSlot::new(id, effect));
+    }
+
+    pub(crate) fn remove(&mut self, id: EffectId) -> bool {
+        let count = self.slots.len();
+        self.slots.retain(|slot| slot.get_id() != id);
+        self.slots.len() != count
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.slots.clear();
+    }
+
+    pub(crate) fn get(&self, id: EffectId) -> Option<&EffectSlot> {
+        self.slots.iter().find(|slot| slot.get_id() == id)
+    }
+
+    pub(crate) fn get_mut(&mut self, id: EffectId) -> Option<&mut EffectSlot> {
+        self.slots.iter_mut().find(|slot| slot.get_id() == id)
+    }
+
+    pub(crate) fn ids(&self) -> Vec<EffectId> {
+        self.slots.iter().map(|slot| slot.get_id()).collect()
+    }
+
+    pub(crate) fn process(&mut self, buffer: &mut [f32], rate: f32) {
+        for slot in self.slots.iter_mut() {
+            slot.process(buffer, rate);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(frequency: f32, rate: f32, frames: usize) -> Vec<f32> {
+        (0..frames)
+            .flat_map(|i| {
+                let s = (TAU * frequency * i as f32 / rate).sin();
+                [s, s]
+            })
+            .collect()
+    }
+
+    fn peak(buffer: &[f32]) -> f32 {
+        buffer.iter().fold(0.0, |peak, s| peak.max(s.abs()))
+    }
+
+    #[test]
+    fn effects_shape_the_sound() {
+        let rate = 8000.0;
+        let lowpass = Effect::Filter {
+            kind: FilterKind::LowPass,
+            cutoff: 200.0,
+            q: 0.707,
+        };
+        let mut low = sine(50.0, rate, 4000);
+        let mut high = sine(2000.0, rate, 4000);
+        EffectSlot::new(0, lowpass).process(&mut low, rate);
+        EffectSlot::new(0, lowpass).process(&mut high, rate);
+        assert!(peak(&low[4000..]) > 0.9);
+        assert!(peak(&high[4000..]) < 0.02);
+
+        // an echo a quarter second later at full volume; feedback only
+        // scales the repeats after it
+        let mut click = vec![0.0; 8000];
+        click[0] = 1.0;
+        click[1] = 1.0;
+        let delay = Effect::Delay {
+            time: 0.25,
+            feedback: 0.5,
+            mix: 1.0,
+        };
+        EffectSlot::new(0, delay).process(&mut click, rate);
+        assert_eq!((click[0], click[4000], click[4001]), (0.0, 1.0, 1.0));
+        assert_eq!(click.iter().sum::<f32>(), 2.0);
+
+        let mut loud = sine(100.0, rate, 1000);
+        loud.iter_mut().for_each(|s| *s *= 4.0);
+        EffectSlot::new(0, Effect::MASTER_LIMITER).process(&mut loud, rate);
+        assert!(peak(&loud) <= db_to_gain(-0.1) + 1e-6);
+
+        // halfway through a two octave sweep, the cutoff is an octave along
+        let mut slot = EffectSlot::new(0, lowpass);
+        let closed = Effect::Filter {
+            kind: FilterKind::LowPass,
+            cutoff: 800.0,
+            q: 0.707,
+        };
+        slot.set_effect(closed, 1280);
+        slot.process(&mut vec![0.0; 1280], rate);
+        let Effect::Filter { cutoff, .. } = slot.current() else {
+            panic!("automation changed the kind of effect");
+        };
+        assert!((cutoff - 400.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn compressor_turns_down_loud_sounds() {
+        let rate = 8000.0;
+        let compressor = |makeup| Effect::Compressor {
+            threshold: -20.0,
+            ratio: 4.0,
+            attack: 0.001,
+            release: 0.2,
+            makeup,
+        };
+
+        // 20dB over the threshold comes out 5dB over it, once it settles
+        let mut loud = sine(100.0, rate, 4000);
+        EffectSlot::new(0, compressor(0.0)).process(&mut loud, rate);
+        let settled = gain_to_db(peak(&loud[4000..]));
+        assert!((-16.0..=-14.0).contains(&settled), "{}", settled);
+
+        // quieter than the threshold is left alone, apart from the makeup gain
+        let mut quiet = sine(100.0, rate, 4000);
+        quiet.iter_mut().for_each(|s| *s *= 0.05);
+        EffectSlot::new(0, compressor(6.0)).process(&mut quiet, rate);
+        assert!((peak(&quiet) - 0.05 * db_to_gain(6.0)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn reverb_leaves_a_tail() {
+        let rate = 8000.0;
+        let mut click = vec![0.0; 8000];
+        click[0] = 1.0;
+        click[1] = 1.0;
+        let dry = click.clone();
+        let reverb = |mix| Effect::Reverb {
+            room_size: 0.5,
+            damping: 0.5,
+            mix,
+        };
+
+        let mut unmixed = click.clone();
+        EffectSlot::new(0, reverb(0.0)).process(&mut unmixed, rate);
+        assert_eq!(unmixed, dry);
+
+        EffectSlot::new(0, reverb(0.5)).process(&mut click, rate);
+        // still ringing a quarter of a second after the click
+        assert!(peak(&click[4000..]) > 1e-4);
+        assert!(peak(&click[2..]) < 1.0);
+    }
+}
@@ -2,7 +2,15 @@ use std::{cell::RefCell, rc::Rc};
 
 use crate::sound_system::SoundSystem;
 
+pub mod bus;
+pub mod effects;
+pub mod mixer;
+pub mod music;
+pub mod sound_buffer;
+pub mod sound_manager;
 pub mod sound_system;
+pub mod spatial;
+pub mod synth;
 
 pub type RefSoundSystem = Rc<RefCell<SoundSystem>>;
 
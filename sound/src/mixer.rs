@@ -0,0 +1,631 @@
+use std::sync::{Arc, Mutex};
+
+use common::audio::AudioSource;
+use log::warn;
+
+use crate::{
+    bus::{Bus, BusId, Ducking, EFFECTS_BUS, MASTER_BUS, MUSIC_BUS},
+    effects::{Effect, EffectId},
+    music::{MusicId, MusicVoice},
+    sound_buffer::SoundBuffer,
+};
+
+pub const DEFAULT_SAMPLE_RATE: u32 = 48000;
+pub const DEFAULT_VOICE_COUNT: usize = 32;
+
+/// Identifies one playing sound.  Once the sound stops, its voice may be
+/// reused; the old handle then no longer refers to anything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VoiceHandle {
+    index: usize,
+    generation: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlayParams {
+    pub volume: f32,
+    /// -1 is fully left, 1 fully right
+    pub pan: f32,
+    /// A playback speed; 2 is an octave up
+    pub pitch: f32,
+    /// When every voice is busy, a new sound takes over the lowest priority
+    /// voice, as long as it's no higher than the new sound's
+    pub priority: i32,
+    pub looping: bool,
+    pub bus: BusId,
+}
+
+impl Default for PlayParams {
+    fn default() -> Self {
+        PlayParams {
+            volume: 1.0,
+            pan: 0.0,
+            pitch: 1.0,
+            priority: 0,
+            looping: false,
+            bus: EFFECTS_BUS,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Voice {
+    buffer: Option<Arc<SoundBuffer>>,
+    generation: u32,
+    params: Option<PlayParams>,
+    position: f64, // in the buffer's frames
+    gains: (f32, f32),
+    started: u64, // play order, so the oldest voice is stolen first
+}
+
+impl Voice {
+    fn is_playing(&self) -> bool {
+        self.buffer.is_some()
+    }
+
+    fn stop(&mut self) {
+        self.buffer = None;
+        self.params = None;
+    }
+
+    fn target_gains(params: &PlayParams) -> (f32, f32) {
+        // constant power, so a sound doesn't dip in the middle as it pans across
+        let angle = (params.pan.clamp(-1.0, 1.0) + 1.0) * std::f32::consts::FRAC_PI_4;
+        let volume = params.volume.max(0.0);
+        (volume * angle.cos(), volume * angle.sin())
+    }
+
+    // adds into `out`, which is stereo; gains ramp across the block to avoid clicks
+    fn mix(&mut self, out: &mut [f32], output_rate: u32) {
+        let (Some(buffer), Some(params)) = (&self.buffer, &self.params) else {
+            return;
+        };
+        let frames = buffer.frame_count();
+        if frames == 0 {
+            self.stop();
+            return;
+        }
+
+        let step =
+            params.pitch.max(0.0) as f64 * buffer.get_sample_rate() as f64 / output_rate as f64;
+        let target = Voice::target_gains(params);
+        let block = out.len() / 2;
+        let ramp = (
+            (target.0 - self.gains.0) / block.max(1) as f32,
+            (target.1 - self.gains.1) / block.max(1) as f32,
+        );
+
+        let mut finished = false;
+        for frame in out.chunks_exact_mut(2) {
+            self.gains.0 += ramp.0;
+            self.gains.1 += ramp.1;
+
+            let index = self.position as usize;
+            let fraction = (self.position - index as f64) as f32;
+            let next = if index + 1 < frames {
+                index + 1
+            } else if params.looping {
+                0
+            } else {
+                index
+            };
+            let (l0, r0) = buffer.frame(index);
+            let (l1, r1) = buffer.frame(next);
+            frame[0] += (l0 + (l1 - l0) * fraction) * self.gains.0;
+            frame[1] += (r0 + (r1 - r0) * fraction) * self.gains.1;
+
+            self.position += step;
+            if self.position >= frames as f64 {
+                if params.looping {
+                    self.position %= frames as f64;
+                } else {
+                    finished = true;
+                    break;
+                }
+            }
+        }
+        self.gains = target;
+        if finished {
+            self.stop();
+        }
+    }
+}
+
+/// Mixes every playing voice into stereo output.  The mixer is shared between
+/// the game, which starts and changes sounds, and the audio thread, which
+/// pulls the mixed samples.
+#[derive(Debug)]
+pub struct Mixer {
+    sample_rate: u32,
+    voices: Vec<Voice>,
+    plays: u64,
+    music: Vec<MusicVoice>,
+    buses: Vec<Bus>,   // parents always come before their children
+    active: Vec<bool>, // which buses have anything playing in them, for ducking
+    next_effect: EffectId,
+}
+
+impl Mixer {
+    pub fn new(sample_rate: u32, voice_count: usize) -> Self {
+        let buses = Bus::standard();
+        let mut mixer = Mixer {
+            sample_rate,
+            voices: (0..voice_count).map(|_| Voice::default()).collect(),
+            plays: 0,
+            music: Vec::new(),
+            active: vec![false; buses.len()],
+            buses,
+            next_effect: 0,
+        };
+        mixer.add_effect(MASTER_BUS, Effect::MASTER_LIMITER);
+        mixer
+    }
+
+    pub fn get_sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate.max(1);
+    }
+
+    pub fn get_voice_count(&self) -> usize {
+        self.voices.len()
+    }
+
+    pub fn get_playing_count(&self) -> usize {
+        self.voices.iter().filter(|v| v.is_playing()).count()
+    }
+
+    pub fn get_master_volume(&self) -> f32 {
+        self.buses[MASTER_BUS].get_volume()
+    }
+
+    pub fn set_master_volume(&mut self, volume: f32) {
+        self.buses[MASTER_BUS].set_volume(volume);
+    }
+
+    /// A new bus feeding `parent`
+    pub fn add_bus(&mut self, name: &str, parent: BusId) -> BusId {
+        let parent = if parent < self.buses.len() {
+            parent
+        } else {
+            warn!(
+                "no bus {} to add bus {} to; adding it to master",
+                parent, name
+            );
+            MASTER_BUS
+        };
+        self.buses.push(Bus::new(name, Some(parent)));
+        self.active.push(false);
+        self.buses.len() - 1
+    }
+
+    pub fn find_bus(&self, name: &str) -> Option<BusId> {
+        self.buses.iter().position(|bus| bus.get_name() == name)
+    }
+
+    pub fn get_bus_count(&self) -> usize {
+        self.buses.len()
+    }
+
+    pub fn get_bus_name(&self, bus: BusId) -> Option<&str> {
+        self.buses.get(bus).map(|bus| bus.get_name())
+    }
+
+    pub fn get_bus_parent(&self, bus: BusId) -> Option<BusId> {
+        self.buses.get(bus).and_then(|bus| bus.get_parent())
+    }
+
+    pub fn get_bus_volume(&self, bus: BusId) -> f32 {
+        self.buses.get(bus).map_or(0.0, |bus| bus.get_volume())
+    }
+
+    pub fn set_bus_volume(&mut self, bus: BusId, volume: f32) {
+        if let Some(bus) = self.buses.get_mut(bus) {
+            bus.set_volume(volume);
+        }
+    }
+
+    pub fn is_bus_muted(&self, bus: BusId) -> bool {
+        self.buses.get(bus).is_some_and(|bus| bus.is_muted())
+    }
+
+    pub fn set_bus_muted(&mut self, bus: BusId, muted: bool) {
+        if let Some(bus) = self.buses.get_mut(bus) {
+            bus.set_muted(muted);
+        }
+    }
+
+    pub fn get_ducking(&self, bus: BusId) -> Option<Ducking> {
+        self.buses.get(bus).and_then(|bus| bus.get_ducking())
+    }
+
+    pub fn set_ducking(&mut self, bus: BusId, ducking: Option<Ducking>) {
+        if let Some(bus) = self.buses.get_mut(bus) {
+            bus.set_ducking(ducking);
+        }
+    }
+
+    /// 1 unless the bus is ducked
+    pub fn get_duck_gain(&self, bus: BusId) -> f32 {
+        self.buses.get(bus).map_or(1.0, |bus| bus.get_duck_gain())
+    }
+
+    /// Adds an effect to the end of a bus's chain; None if there's no such bus
+    pub fn add_effect(&mut self, bus: BusId, effect: Effect) -> Option<EffectId> {
+        let bus = self.buses.get_mut(bus)?;
+        let id = self.next_effect;
+        self.next_effect += 1;
+        bus.effects.add(id, effect);
+        Some(id)
+    }
+
+    pub fn remove_effect(&mut self, bus: BusId, effect: EffectId) -> bool {
+        self.buses
+            .get_mut(bus)
+            .is_some_and(|bus| bus.effects.remove(effect))
+    }
+
+    pub fn clear_effects(&mut self, bus: BusId) {
+        if let Some(bus) = self.buses.get_mut(bus) {
+            bus.effects.clear();
+        }
+    }
+
+    /// The bus's effects, in the order they're applied
+    pub fn get_effects(&self, bus: BusId) -> Vec<EffectId> {
+        self.buses
+            .get(bus)
+            .map_or(Vec::new(), |bus| bus.effects.ids())
+    }
+
+    /// The effect's parameters, or those it's moving towards
+    pub fn get_effect(&self, bus: BusId, effect: EffectId) -> Option<Effect> {
+        self.buses
+            .get(bus)
+            .and_then(|bus| bus.effects.get(effect))
+            .map(|slot| slot.get_effect())
+    }
+
+    /// Changes an effect's parameters, moving smoothly to them over `seconds`
+    pub fn set_effect(&mut self, bus: BusId, id: EffectId, effect: Effect, seconds: f32) -> bool {
+        let frames = (seconds.max(0.0) * self.sample_rate as f32) as usize;
+        match self
+            .buses
+            .get_mut(bus)
+            .and_then(|bus| bus.effects.get_mut(id))
+        {
+            Some(slot) => {
+                slot.set_effect(effect, frames);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// None if every voice is playing something of a higher priority
+    pub fn play(
+        &mut self,
+        buffer: &Arc<SoundBuffer>,
+        mut params: PlayParams,
+    ) -> Option<VoiceHandle> {
+        if params.bus >= self.buses.len() {
+            warn!(
+                "no bus {} to play sound on; playing it on effects",
+                params.bus
+            );
+            params.bus = EFFECTS_BUS;
+        }
+        let index = match self.voices.iter().position(|v| !v.is_playing()) {
+            Some(index) => index,
+            None => {
+                let (index, victim) = self
+                    .voices
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, v)| v.params.map(|p| (i, p.priority, v.started)))
+                    .min_by_key(|(_, priority, started)| (*priority, *started))
+                    .map(|(i, priority, _)| (i, priority))?;
+                if victim > params.priority {
+                    return None;
+                }
+                index
+            }
+        };
+
+        self.plays += 1;
+        let voice = &mut self.voices[index];
+        voice.generation = voice.generation.wrapping_add(1);
+        voice.buffer = Some(Arc::clone(buffer));
+        voice.params = Some(params);
+        voice.position = 0.0;
+        // start at full volume; ramping up from silence would soften every attack
+        voice.gains = Voice::target_gains(&params);
+        voice.started = self.plays;
+        Some(VoiceHandle {
+            index,
+            generation: voice.generation,
+        })
+    }
+
+    pub fn is_playing(&self, handle: VoiceHandle) -> bool {
+        self.voice(handle).is_some_and(|v| v.is_playing())
+    }
+
+    pub fn stop(&mut self, handle: VoiceHandle) {
+        if let Some(voice) = self.voice_mut(handle) {
+            voice.stop();
+        }
+    }
+
+    pub fn stop_all(&mut self) {
+        for voice in self.voices.iter_mut() {
+            voice.stop();
+        }
+    }
+
+    pub fn get_params(&self, handle: VoiceHandle) -> Option<PlayParams> {
+        self.voice(handle).and_then(|v| v.params)
+    }
+
+    /// Changes a playing sound; volume and pan changes are smoothed over the next block
+    pub fn set_params(&mut self, handle: VoiceHandle, mut params: PlayParams) {
+        let bus_count = self.buses.len();
+        if let Some(voice) = self.voice_mut(handle)
+            && let Some(current) = voice.params
+        {
+            if params.bus >= bus_count {
+                warn!("no bus {} to move sound to", params.bus);
+                params.bus = current.bus;
+            }
+            voice.params = Some(params);
+        }
+    }
+
+    pub fn get_music_volume(&self) -> f32 {
+        self.buses[MUSIC_BUS].get_volume()
+    }
+
+    pub fn set_music_volume(&mut self, volume: f32) {
+        self.buses[MUSIC_BUS].set_volume(volume);
+    }
+
+    pub(crate) fn add_music(&mut self, voice: MusicVoice) {
+        self.music.push(voice);
+    }
+
+    pub(crate) fn music(&self, id: MusicId) -> Option<&MusicVoice> {
+        self.music.iter().find(|m| m.get_id() == id)
+    }
+
+    pub(crate) fn music_mut(&mut self, id: MusicId) -> Option<&mut MusicVoice> {
+        self.music.iter_mut().find(|m| m.get_id() == id)
+    }
+
+    /// Mixes the next `out.len() / 2` frames into `out`, as interleaved stereo
+    pub fn mix(&mut self, out: &mut [f32]) {
+        self.active.fill(false);
+        for bus in self.buses.iter_mut() {
+            bus.buffer.clear();
+            bus.buffer.resize(out.len(), 0.0);
+        }
+        for voice in self.voices.iter_mut() {
+            if let Some(params) = voice.params {
+                self.active[params.bus] = true;
+                voice.mix(&mut self.buses[params.bus].buffer, self.sample_rate);
+            }
+        }
+        self.active[MUSIC_BUS] |= !self.music.is_empty();
+        for music in self.music.iter_mut() {
+            music.mix(&mut self.buses[MUSIC_BUS].buffer);
+        }
+        self.music.retain(|m| !m.is_finished());
+
+        for index in (1..self.buses.len()).rev() {
+            if let Some(parent) = self.buses[index].get_parent() {
+                self.active[parent] |= self.active[index];
+            }
+        }
+        let seconds = (out.len() / 2) as f32 / self.sample_rate as f32;
+        for index in 0..self.buses.len() {
+            let triggered = self.buses[index]
+                .get_ducking()
+                .is_some_and(|ducking| self.active.get(ducking.trigger).copied().unwrap_or(false));
+            self.buses[index].update_ducking(triggered, seconds);
+        }
+
+        // children are after their parents, so each is finished before it's added in
+        for index in (0..self.buses.len()).rev() {
+            let bus = &mut self.buses[index];
+            bus.effects
+                .process(&mut bus.buffer, self.sample_rate as f32);
+            bus.apply_gain();
+            match self.buses[index].get_parent() {
+                Some(parent) => {
+                    let (parents, children) = self.buses.split_at_mut(index);
+                    for (sum, sample) in parents[parent]
+                        .buffer
+                        .iter_mut()
+                        .zip(children[0].buffer.iter())
+                    {
+                        *sum += sample;
+                    }
+                }
+                None => {
+                    for (out, sample) in out.iter_mut().zip(self.buses[index].buffer.iter()) {
+                        *out = sample.clamp(-1.0, 1.0);
+                    }
+                }
+            }
+        }
+    }
+
+    fn voice(&self, handle: VoiceHandle) -> Option<&Voice> {
+        self.voices
+            .get(handle.index)
+            .filter(|v| v.generation == handle.generation)
+    }
+
+    fn voice_mut(&mut self, handle: VoiceHandle) -> Option<&mut Voice> {
+        self.voices
+            .get_mut(handle.index)
+            .filter(|v| v.generation == handle.generation)
+    }
+}
+
+/// Feeds a shared mixer to the backend's audio output
+pub struct MixerSource {
+    mixer: Arc<Mutex<Mixer>>,
+    stereo: Vec<f32>,
+}
+
+impl MixerSource {
+    pub fn new(mixer: Arc<Mutex<Mixer>>) -> Self {
+        MixerSource {
+            mixer,
+            stereo: Vec::new(),
+        }
+    }
+}
+
+impl AudioSource for MixerSource {
+    fn fill(&mut self, out: &mut [f32], channels: u8) {
+        let channels = channels.max(1) as usize;
+        if channels == 2 {
+            self.mixer
+                .lock()
+                .expect("unable to lock mixer in audio callback")
+                .mix(out);
+            return;
+        }
+
+        self.stereo.resize(out.len() / channels * 2, 0.0);
+        self.mixer
+            .lock()
+            .expect("unable to lock mixer in audio callback")
+            .mix(&mut self.stereo);
+        for (frame, stereo) in out
+            .chunks_exact_mut(channels)
+            .zip(self.stereo.chunks_exact(2))
+        {
+            if channels == 1 {
+                frame[0] = (stereo[0] + stereo[1]) * 0.5;
+            } else {
+                frame.fill(0.0);
+                frame[0] = stereo[0];
+                frame[1] = stereo[1];
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::UI_BUS;
+
+    #[test]
+    fn voices_mix_and_steal() {
+        let mut mixer = Mixer::new(100, 2);
+        let buffer = Arc::new(SoundBuffer::new(100, 1, vec![0.5; 4]));
+
+        let low = mixer.play(
+            &buffer,
+            PlayParams {
+                pan: -1.0,
+                priority: 0,
+                ..Default::default()
+            },
+        );
+        let high = mixer.play(
+            &buffer,
+            PlayParams {
+                pan: 1.0,
+                priority: 5,
+                ..Default::default()
+            },
+        );
+        let mut out = [0.0; 4];
+        mixer.mix(&mut out);
+        assert!((out[0] - 0.5).abs() < 1e-6 && (out[1] - 0.5).abs() < 1e-6);
+
+        // a third sound takes over the lowest priority voice, but not a higher one
+        let stealer = mixer.play(&buffer, PlayParams::default());
+        assert!(stealer.is_some());
+        assert!(!mixer.is_playing(low.unwrap()));
+        assert!(mixer.is_playing(high.unwrap()));
+        let none = mixer.play(
+            &buffer,
+            PlayParams {
+                priority: -1,
+                ..Default::default()
+            },
+        );
+        assert!(none.is_none());
+
+        // each runs out after four frames
+        let mut out = [0.0; 4];
+        mixer.mix(&mut out);
+        assert_eq!(mixer.get_playing_count(), 1);
+        mixer.mix(&mut out);
+        assert_eq!(mixer.get_playing_count(), 0);
+    }
+
+    #[test]
+    fn buses_mute_and_duck() {
+        let mut mixer = Mixer::new(100, 4);
+        let tone = Arc::new(SoundBuffer::new(100, 2, vec![0.5; 200]));
+        let dialogue = mixer.add_bus("dialogue", MASTER_BUS);
+        mixer.set_ducking(
+            MUSIC_BUS,
+            Some(Ducking {
+                trigger: dialogue,
+                gain: 0.25,
+                attack: 0.05,
+                release: 0.1,
+            }),
+        );
+        let music = PlayParams {
+            bus: MUSIC_BUS,
+            looping: true,
+            ..Default::default()
+        };
+        let pan = std::f32::consts::FRAC_1_SQRT_2;
+        mixer.play(&tone, music);
+        let mut out = [0.0; 20];
+        mixer.mix(&mut out);
+        assert!((out[19] - 0.5 * pan).abs() < 1e-6);
+
+        // music drops while anyone speaks, and comes back after
+        let line = mixer.play(
+            &tone,
+            PlayParams {
+                bus: dialogue,
+                volume: 0.0,
+                looping: true,
+                ..Default::default()
+            },
+        );
+        mixer.mix(&mut out);
+        assert_eq!(mixer.get_duck_gain(MUSIC_BUS), 0.25);
+        assert!((out[19] - 0.125 * pan).abs() < 1e-6);
+        mixer.stop(line.unwrap());
+        mixer.mix(&mut out);
+        assert_eq!(mixer.get_duck_gain(MUSIC_BUS), 1.0);
+
+        // muting effects silences the UI bus inside it too
+        mixer.stop_all();
+        mixer.play(
+            &tone,
+            PlayParams {
+                bus: UI_BUS,
+                ..Default::default()
+            },
+        );
+        mixer.set_bus_muted(EFFECTS_BUS, true);
+        mixer.mix(&mut out);
+        mixer.mix(&mut out);
+        assert_eq!(out, [0.0; 20]);
+    }
+}
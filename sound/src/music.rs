@@ -0,0 +1,451 @@
+//! Music is streamed from disk rather than decoded up front.  The sound
+//! system decodes a little ahead of the audio thread each frame, and hands
+//! the mixer ready-to-play stereo at its own rate; the mixer only fades and
+//! sums what it has been given.
+
+use std::{collections::VecDeque, fs::File, io::BufReader, sync::Arc};
+
+use common::vs_error::VSError;
+use lewton::inside_ogg::OggStreamReader;
+use log::error;
+
+use crate::sound_buffer::{SoundBuffer, downmix};
+
+/// Identifies one playing music track in the mixer
+pub type MusicId = u64;
+
+/// Where a track loops, in frames of the source file.  Without an end, it
+/// loops from the end of the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoopPoints {
+    pub start: u64,
+    pub end: Option<u64>,
+}
+
+/// A piece of music to play, and how
+#[derive(Debug, Clone, PartialEq)]
+pub struct MusicTrack {
+    path: String,
+    volume: f32,
+    loop_points: Option<LoopPoints>,
+}
+
+impl MusicTrack {
+    /// An Ogg Vorbis file, played through once
+    pub fn new(path: &str) -> Self {
+        MusicTrack {
+            path: path.to_string(),
+            volume: 1.0,
+            loop_points: None,
+        }
+    }
+
+    /// Loops the whole track
+    pub fn looping(self) -> Self {
+        self.with_loop_points(0, None)
+    }
+
+    /// Plays from the start, then loops between `start` and `end`, so an
+    /// intro only plays once
+    pub fn with_loop_points(mut self, start: u64, end: Option<u64>) -> Self {
+        self.loop_points = Some(LoopPoints { start, end });
+        self
+    }
+
+    pub fn with_volume(mut self, volume: f32) -> Self {
+        self.volume = volume;
+        self
+    }
+
+    pub fn get_path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn get_volume(&self) -> f32 {
+        self.volume
+    }
+
+    pub fn get_loop_points(&self) -> Option<LoopPoints> {
+        self.loop_points
+    }
+}
+
+/// Decodes a track a packet at a time
+pub trait MusicDecoder {
+    fn sample_rate(&self) -> u32;
+    /// Interleaved stereo frames, or None at the end of the track
+    fn next_packet(&mut self) -> Result<Option<Vec<f32>>, VSError>;
+    /// The next packet starts at this frame
+    fn seek(&mut self, frame: u64) -> Result<(), VSError>;
+}
+
+/// Streams an Ogg Vorbis file
+pub struct OggDecoder {
+    path: String,
+    reader: OggStreamReader<BufReader<File>>,
+    // after a seek, the frame we want next; packets decode from the page before it
+    seeking_to: Option<u64>,
+    pending: Vec<f32>,
+}
+
+impl OggDecoder {
+    pub fn open(path: &str) -> Result<Self, VSError> {
+        let file = File::open(path).map_err(|e| {
+            error!("unable to open music file {}: {}", path, e);
+            VSError::Sound_FileNotFound
+        })?;
+        let reader = OggStreamReader::new(BufReader::new(file)).map_err(|e| {
+            error!("unable to read ogg headers of {}: {}", path, e);
+            VSError::Sound_DecodeFailed
+        })?;
+        Ok(OggDecoder {
+            path: path.to_string(),
+            reader,
+            seeking_to: None,
+            pending: Vec::new(),
+        })
+    }
+
+    fn decode(&mut self) -> Result<Option<Vec<f32>>, VSError> {
+        let packet = self.reader.read_dec_packet_itl().map_err(|e| {
+            error!("unable to decode music {}: {}", self.path, e);
+            VSError::Sound_DecodeFailed
+        })?;
+        let channels = self.reader.ident_hdr.audio_channels as usize;
+        Ok(packet.map(|packet| {
+            let samples: Vec<f32> = packet.iter().map(|s| *s as f32 / 32768.0).collect();
+            match channels {
+                1 => samples.iter().flat_map(|s| [*s, *s]).collect(),
+                2 => samples,
+                _ => downmix(&samples, channels),
+            }
+        }))
+    }
+}
+
+impl MusicDecoder for OggDecoder {
+    fn sample_rate(&self) -> u32 {
+        self.reader.ident_hdr.audio_sample_rate
+    }
+
+    fn next_packet(&mut self) -> Result<Option<Vec<f32>>, VSError> {
+        let Some(target) = self.seeking_to else {
+            return self.decode();
+        };
+
+        // the granule position is only known at the end of a page; until then
+        // we can't tell where the decoded frames are, so hold on to them
+        loop {
+            let Some(packet) = self.decode()? else {
+                self.seeking_to = None;
+                return Ok(Some(std::mem::take(&mut self.pending)));
+            };
+            self.pending.extend(packet);
+            if let Some(end) = self.reader.get_last_absgp() {
+                self.seeking_to = None;
+                let start = end.saturating_sub(self.pending.len() as u64 / 2);
+                let skip = (target.saturating_sub(start) as usize * 2).min(self.pending.len());
+                let mut packet = std::mem::take(&mut self.pending);
+                packet.drain(..skip);
+                return Ok(Some(packet));
+            }
+        }
+    }
+
+    fn seek(&mut self, frame: u64) -> Result<(), VSError> {
+        self.reader.seek_absgp_pg(frame).map_err(|e| {
+            error!("unable to seek music {}: {}", self.path, e);
+            VSError::Sound_DecodeFailed
+        })?;
+        self.seeking_to = Some(frame);
+        self.pending.clear();
+        Ok(())
+    }
+}
+
+/// Plays a sound already in memory as music, a second at a time
+pub struct BufferDecoder {
+    buffer: Arc<SoundBuffer>,
+    position: usize,
+}
+
+impl BufferDecoder {
+    pub fn new(buffer: Arc<SoundBuffer>) -> Self {
+        BufferDecoder {
+            buffer,
+            position: 0,
+        }
+    }
+}
+
+impl MusicDecoder for BufferDecoder {
+    fn sample_rate(&self) -> u32 {
+        self.buffer.get_sample_rate()
+    }
+
+    fn next_packet(&mut self) -> Result<Option<Vec<f32>>, VSError> {
+        let frames = self.buffer.frame_count();
+        if self.position >= frames {
+            return Ok(None);
+        }
+        let end = (self.position + self.buffer.get_sample_rate() as usize).min(frames);
+        let packet = (self.position..end)
+            .flat_map(|i| {
+                let (left, right) = self.buffer.frame(i);
+                [left, right]
+            })
+            .collect();
+        self.position = end;
+        Ok(Some(packet))
+    }
+
+    fn seek(&mut self, frame: u64) -> Result<(), VSError> {
+        self.position = frame as usize;
+        Ok(())
+    }
+}
+
+/// The game's side of a playing track: decodes, loops, and converts to the mixer's rate
+pub(crate) struct MusicStream {
+    id: MusicId,
+    track: MusicTrack,
+    decoder: Box<dyn MusicDecoder>,
+    frame: u64, // of the source, where the next packet starts
+    ended: bool,
+    looping: bool, // cleared when another track is queued to follow
+
+    // source frames waiting to be resampled, and how far into them we are
+    source: VecDeque<f32>,
+    position: f64,
+}
+
+impl MusicStream {
+    pub(crate) fn new(id: MusicId, track: MusicTrack, decoder: Box<dyn MusicDecoder>) -> Self {
+        let looping = track.loop_points.is_some();
+        MusicStream {
+            id,
+            track,
+            decoder,
+            frame: 0,
+            ended: false,
+            looping,
+            source: VecDeque::new(),
+            position: 0.0,
+        }
+    }
+
+    pub(crate) fn get_id(&self) -> MusicId {
+        self.id
+    }
+
+    pub(crate) fn get_track(&self) -> &MusicTrack {
+        &self.track
+    }
+
+    /// Finished decoding; what's left is in the mixer
+    pub(crate) fn is_ended(&self) -> bool {
+        self.ended
+    }
+
+    /// Stops decoding, e.g. after an error
+    pub(crate) fn end(&mut self) {
+        self.ended = true;
+    }
+
+    pub(crate) fn set_looping(&mut self, looping: bool) {
+        self.looping = looping && self.track.loop_points.is_some();
+    }
+
+    /// Up to `frames` frames at `sample_rate`, fewer once the track ends
+    pub(crate) fn read(&mut self, frames: usize, sample_rate: u32) -> Result<Vec<f32>, VSError> {
+        let step = self.decoder.sample_rate() as f64 / sample_rate as f64;
+        let mut out = Vec::with_capacity(frames * 2);
+        while out.len() < frames * 2 {
+            let index = self.position as usize;
+            if (index + 1) * 2 >= self.source.len() {
+                if self.ended {
+                    break;
+                }
+                self.decode()?;
+                continue;
+            }
+            let t = (self.position - index as f64) as f32;
+            for channel in 0..2 {
+                let a = self.source[index * 2 + channel];
+                let b = self.source[index * 2 + 2 + channel];
+                out.push(a + (b - a) * t);
+            }
+            self.position += step;
+            let used = self.position as usize;
+            self.source.drain(..used * 2);
+            self.position -= used as f64;
+        }
+        // the very last frame has nothing to interpolate towards
+        if self.ended && out.len() < frames * 2 && self.source.len() >= 2 {
+            out.extend(self.source.drain(..2));
+        }
+        Ok(out)
+    }
+
+    fn decode(&mut self) -> Result<(), VSError> {
+        let loop_end = self
+            .track
+            .loop_points
+            .and_then(|points| points.end)
+            .filter(|_| self.looping);
+        let packet = self.decoder.next_packet()?;
+
+        let reached_end = match (packet, loop_end) {
+            (Some(mut packet), Some(end)) => {
+                let frames = packet.len() as u64 / 2;
+                let remaining = end.saturating_sub(self.frame);
+                packet.truncate((remaining.min(frames) * 2) as usize);
+                self.frame += frames.min(remaining);
+                self.source.extend(packet);
+                frames >= remaining
+            }
+            (Some(packet), None) => {
+                self.frame += packet.len() as u64 / 2;
+                self.source.extend(packet);
+                false
+            }
+            (None, _) => true,
+        };
+
+        if reached_end {
+            match self.track.loop_points.filter(|_| self.looping) {
+                Some(points) => {
+                    self.decoder.seek(points.start)?;
+                    self.frame = points.start;
+                }
+                None => self.ended = true,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A volume ramp, in gain per frame
+#[derive(Debug, Clone, Copy)]
+struct Fade {
+    gain: f32,
+    target: f32,
+    step: f32,
+    stop: bool, // once the target is reached
+}
+
+/// The mixer's side of a playing track
+#[derive(Debug)]
+pub(crate) struct MusicVoice {
+    id: MusicId,
+    volume: f32,
+    samples: VecDeque<f32>,
+    fade: Fade,
+    ended: bool, // no more samples are coming
+}
+
+impl MusicVoice {
+    pub(crate) fn new(id: MusicId, volume: f32, fade_frames: usize) -> Self {
+        let fade = match fade_frames {
+            0 => Fade {
+                gain: 1.0,
+                target: 1.0,
+                step: 0.0,
+                stop: false,
+            },
+            frames => Fade {
+                gain: 0.0,
+                target: 1.0,
+                step: 1.0 / frames as f32,
+                stop: false,
+            },
+        };
+        MusicVoice {
+            id,
+            volume,
+            samples: VecDeque::new(),
+            fade,
+            ended: false,
+        }
+    }
+
+    pub(crate) fn get_id(&self) -> MusicId {
+        self.id
+    }
+
+    pub(crate) fn queued_frames(&self) -> usize {
+        self.samples.len() / 2
+    }
+
+    pub(crate) fn push(&mut self, samples: &[f32]) {
+        self.samples.extend(samples);
+    }
+
+    pub(crate) fn end(&mut self) {
+        self.ended = true;
+    }
+
+    pub(crate) fn set_volume(&mut self, volume: f32) {
+        self.volume = volume;
+    }
+
+    /// Fades to silence and stops; with no frames, stops at once
+    pub(crate) fn fade_out(&mut self, frames: usize) {
+        self.fade.target = 0.0;
+        self.fade.stop = true;
+        self.fade.step = self.fade.gain / frames.max(1) as f32;
+    }
+
+    pub(crate) fn is_fading_out(&self) -> bool {
+        self.fade.stop
+    }
+
+    pub(crate) fn is_finished(&self) -> bool {
+        (self.ended && self.samples.is_empty()) || (self.fade.stop && self.fade.gain <= 0.0)
+    }
+
+    pub(crate) fn mix(&mut self, out: &mut [f32]) {
+        for frame in out.chunks_exact_mut(2) {
+            if self.samples.len() < 2 || (self.fade.stop && self.fade.gain <= 0.0) {
+                break;
+            }
+            if self.fade.gain < self.fade.target {
+                self.fade.gain = (self.fade.gain + self.fade.step).min(self.fade.target);
+            } else if self.fade.gain > self.fade.target {
+                self.fade.gain = (self.fade.gain - self.fade.step).max(self.fade.target);
+            }
+            let gain = self.fade.gain * self.volume;
+            frame[0] += self.samples.pop_front().unwrap_or(0.0) * gain;
+            frame[1] += self.samples.pop_front().unwrap_or(0.0) * gain;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loop_points_repeat_the_middle() {
+        let ramp: Vec<f32> = (0..10).map(|i| i as f32).collect();
+        let buffer = Arc::new(SoundBuffer::new(10, 1, ramp));
+        let track = MusicTrack::new("ramp").with_loop_points(4, Some(7));
+        let mut stream = MusicStream::new(1, track, Box::new(BufferDecoder::new(buffer)));
+
+        let left: Vec<f32> = stream
+            .read(13, 10)
+            .unwrap()
+            .chunks_exact(2)
+            .map(|frame| frame[0])
+            .collect();
+        assert_eq!(left, [0., 1., 2., 3., 4., 5., 6., 4., 5., 6., 4., 5., 6.]);
+
+        // once something else is queued, it plays on to the end instead,
+        // after the loop it had already decoded
+        stream.set_looping(false);
+        let rest = stream.read(100, 10).unwrap();
+        assert!(stream.is_ended());
+        assert_eq!(rest.len() / 2, 9);
+        assert_eq!(rest[rest.len() - 2], 9.0);
+    }
+}
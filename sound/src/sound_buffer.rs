@@ -0,0 +1,379 @@
+use std::{fs::File, io::BufReader, path::Path};
+
+use common::vs_error::VSError;
+use lewton::inside_ogg::OggStreamReader;
+use log::{error, info};
+
+/// Decoded sound, held in memory as interleaved float samples
+#[derive(Debug, Clone, PartialEq)]
+pub struct SoundBuffer {
+    sample_rate: u32,
+    channels: u8,
+    samples: Vec<f32>,
+}
+
+impl SoundBuffer {
+    /// `channels` is 1 or 2; `samples` are interleaved when there are two
+    pub fn new(sample_rate: u32, channels: u8, samples: Vec<f32>) -> Self {
+        debug_assert!(channels == 1 || channels == 2);
+        SoundBuffer {
+            sample_rate,
+            channels: channels.clamp(1, 2),
+            samples,
+        }
+    }
+
+    /// Any number of channels; more than two are mixed down to stereo
+    pub fn from_interleaved(sample_rate: u32, channels: usize, samples: Vec<f32>) -> Self {
+        match channels {
+            1 | 2 => SoundBuffer::new(sample_rate, channels as u8, samples),
+            _ => SoundBuffer::new(sample_rate, 2, downmix(&samples, channels)),
+        }
+    }
+
+    /// A WAV or Ogg Vorbis file, chosen by its extension
+    pub fn load(path: &str) -> Result<Self, VSError> {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("wav") => SoundBuffer::load_wav(path),
+            Some("ogg") => SoundBuffer::load_ogg(path),
+            _ => {
+                error!("unknown sound file type {}", path);
+                Err(VSError::Sound_UnsupportedFormat)
+            }
+        }
+    }
+
+    /// 8, 16, 24 or 32-bit PCM, or 32-bit float
+    pub fn load_wav(path: &str) -> Result<Self, VSError> {
+        let mut reader = hound::WavReader::open(path).map_err(|e| {
+            error!("unable to open wav file {}: {}", path, e);
+            match e {
+                hound::Error::IoError(_) => VSError::Sound_FileNotFound,
+                _ => VSError::Sound_DecodeFailed,
+            }
+        })?;
+        let spec = reader.spec();
+        let samples: Result<Vec<f32>, hound::Error> =
+            match (spec.sample_format, spec.bits_per_sample) {
+                (hound::SampleFormat::Float, 32) => reader.samples::<f32>().collect(),
+                (hound::SampleFormat::Int, bits @ (8 | 16 | 24 | 32)) => {
+                    let scale = 1.0 / (1u64 << (bits - 1)) as f32;
+                    reader
+                        .samples::<i32>()
+                        .map(|s| s.map(|s| s as f32 * scale))
+                        .collect()
+                }
+                (format, bits) => {
+                    error!(
+                        "unsupported wav format in {}: {:?} {}-bit",
+                        path, format, bits
+                    );
+                    return Err(VSError::Sound_UnsupportedFormat);
+                }
+            };
+        let samples = samples.map_err(|e| {
+            error!("unable to decode wav {}: {}", path, e);
+            VSError::Sound_DecodeFailed
+        })?;
+
+        info!(
+            "Loaded sound {} ({} Hz, {} channels)",
+            path, spec.sample_rate, spec.channels
+        );
+        Ok(SoundBuffer::from_interleaved(
+            spec.sample_rate,
+            spec.channels as usize,
+            samples,
+        ))
+    }
+
+    pub fn load_ogg(path: &str) -> Result<Self, VSError> {
+        let file = File::open(path).map_err(|e| {
+            error!("unable to open ogg file {}: {}", path, e);
+            VSError::Sound_FileNotFound
+        })?;
+        let mut reader = OggStreamReader::new(BufReader::new(file)).map_err(|e| {
+            error!("unable to read ogg headers of {}: {}", path, e);
+            VSError::Sound_DecodeFailed
+        })?;
+        let sample_rate = reader.ident_hdr.audio_sample_rate;
+        let channels = reader.ident_hdr.audio_channels as usize;
+
+        let mut samples = Vec::new();
+        loop {
+            match reader.read_dec_packet_itl() {
+                Ok(Some(packet)) => samples.extend(packet.iter().map(|s| *s as f32 / 32768.0)),
+                Ok(None) => break,
+                Err(e) => {
+                    error!("unable to decode ogg {}: {}", path, e);
+                    return Err(VSError::Sound_DecodeFailed);
+                }
+            }
+        }
+
+        info!(
+            "Loaded sound {} ({} Hz, {} channels)",
+            path, sample_rate, channels
+        );
+        Ok(SoundBuffer::from_interleaved(
+            sample_rate,
+            channels,
+            samples,
+        ))
+    }
+
+    /// As 32-bit float, so nothing is lost
+    pub fn save_wav(&self, path: &str) -> Result<(), VSError> {
+        let spec = hound::WavSpec {
+            channels: self.channels as u16,
+            sample_rate: self.sample_rate,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let write = || -> Result<(), hound::Error> {
+            let mut writer = hound::WavWriter::create(path, spec)?;
+            for sample in self.samples.iter() {
+                writer.write_sample(*sample)?;
+            }
+            writer.finalize()
+        };
+        write().map_err(|e| {
+            error!("unable to write wav file {}: {}", path, e);
+            VSError::Sound_WriteFailed
+        })?;
+        info!("Saved sound {}", path);
+        Ok(())
+    }
+
+    pub fn get_sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn get_channels(&self) -> u8 {
+        self.channels
+    }
+
+    pub fn samples(&self) -> &[f32] {
+        &self.samples
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.samples.len() / self.channels as usize
+    }
+
+    /// In seconds
+    pub fn duration(&self) -> f32 {
+        self.frame_count() as f32 / self.sample_rate as f32
+    }
+
+    /// Left and right; mono sounds give the same sample for both
+    pub fn frame(&self, index: usize) -> (f32, f32) {
+        match self.channels {
+            1 => {
+                let sample = self.samples[index];
+                (sample, sample)
+            }
+            _ => (self.samples[index * 2], self.samples[index * 2 + 1]),
+        }
+    }
+
+    pub fn to_mono(&self) -> SoundBuffer {
+        match self.channels {
+            1 => self.clone(),
+            _ => SoundBuffer::new(
+                self.sample_rate,
+                1,
+                self.samples
+                    .chunks_exact(2)
+                    .map(|frame| (frame[0] + frame[1]) * 0.5)
+                    .collect(),
+            ),
+        }
+    }
+
+    pub fn to_stereo(&self) -> SoundBuffer {
+        match self.channels {
+            1 => SoundBuffer::new(
+                self.sample_rate,
+                2,
+                self.samples.iter().flat_map(|s| [*s, *s]).collect(),
+            ),
+            _ => self.clone(),
+        }
+    }
+
+    /// The same sound at another rate, so the mixer doesn't have to convert
+    /// it every time it plays.  Uses cubic interpolation between samples.
+    pub fn resampled(&self, sample_rate: u32) -> SoundBuffer {
+        if sample_rate == self.sample_rate || self.samples.is_empty() {
+            return SoundBuffer {
+                sample_rate,
+                ..self.clone()
+            };
+        }
+
+        let channels = self.channels as usize;
+        let frames = self.frame_count();
+        let step = self.sample_rate as f64 / sample_rate as f64;
+        let new_frames = ((frames as f64) / step).ceil() as usize;
+        let sample = |frame: isize, channel: usize| {
+            self.samples[frame.clamp(0, frames as isize - 1) as usize * channels + channel]
+        };
+
+        let mut samples = Vec::with_capacity(new_frames * channels);
+        for frame in 0..new_frames {
+            let position = frame as f64 * step;
+            let index = position as isize;
+            let t = (position - index as f64) as f32;
+            for channel in 0..channels {
+                samples.push(catmull_rom(
+                    sample(index - 1, channel),
+                    sample(index, channel),
+                    sample(index + 1, channel),
+                    sample(index + 2, channel),
+                    t,
+                ));
+            }
+        }
+        SoundBuffer::new(sample_rate, self.channels, samples)
+    }
+}
+
+fn catmull_rom(p0: f32, p1: f32, p2: f32, p3: f32, t: f32) -> f32 {
+    let a = -0.5 * p0 + 1.5 * p1 - 1.5 * p2 + 0.5 * p3;
+    let b = p0 - 2.5 * p1 + 2.0 * p2 - 0.5 * p3;
+    let c = -0.5 * p0 + 0.5 * p2;
+    ((a * t + b) * t + c) * t + p1
+}
+
+/// Surround sound to stereo: the front left and right channels, with the
+/// centre channel mixed into both at -3dB.  The rest are dropped.
+pub(crate) fn downmix(samples: &[f32], channels: usize) -> Vec<f32> {
+    samples
+        .chunks_exact(channels)
+        .flat_map(|frame| {
+            let centre = frame[2] * std::f32::consts::FRAC_1_SQRT_2;
+            [frame[0] + centre, frame[1] + centre]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wav_loads_and_converts() {
+        let path = std::env::temp_dir().join(format!("vs_sound_{}.wav", std::process::id()));
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 22050,
+            bits_per_sample: 24,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for _ in 0..100 {
+            writer.write_sample(1 << 22).unwrap(); // half of full scale
+            writer.write_sample(-(1 << 22)).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let sound = SoundBuffer::load(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(sound.get_channels(), 2);
+        assert_eq!(sound.frame_count(), 100);
+        assert_eq!(sound.frame(10), (0.5, -0.5));
+        assert_eq!(sound.to_mono().frame(10), (0.0, 0.0));
+
+        let resampled = sound.resampled(44100);
+        assert_eq!(resampled.frame_count(), 200);
+        assert!((resampled.frame(101).0 - 0.5).abs() < 1e-6);
+
+        assert!(SoundBuffer::load("missing.mp3").is_err());
+    }
+
+    fn write_wav(
+        name: &str,
+        spec: hound::WavSpec,
+        write: impl Fn(&mut hound::WavWriter<std::io::BufWriter<File>>),
+    ) -> String {
+        let path =
+            std::env::temp_dir().join(format!("vs_sound_{}_{}.wav", name, std::process::id()));
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        write(&mut writer);
+        writer.finalize().unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn wav_sample_formats() {
+        let spec = |bits_per_sample, sample_format| hound::WavSpec {
+            channels: 1,
+            sample_rate: 8000,
+            bits_per_sample,
+            sample_format,
+        };
+        let paths = [
+            write_wav("8bit", spec(8, hound::SampleFormat::Int), |writer| {
+                writer.write_sample(64i8).unwrap();
+                writer.write_sample(-128i8).unwrap();
+            }),
+            write_wav("16bit", spec(16, hound::SampleFormat::Int), |writer| {
+                writer.write_sample(16384i16).unwrap();
+                writer.write_sample(-32768i16).unwrap();
+            }),
+            write_wav("float", spec(32, hound::SampleFormat::Float), |writer| {
+                writer.write_sample(0.5f32).unwrap();
+                writer.write_sample(-1.0f32).unwrap();
+            }),
+        ];
+        for path in paths {
+            let sound = SoundBuffer::load(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            assert_eq!(sound.get_sample_rate(), 8000);
+            assert_eq!(sound.get_channels(), 1);
+            assert_eq!(sound.samples(), &[0.5, -1.0], "{}", path);
+        }
+    }
+
+    // a hand-built stereo Vorbis stream at 8kHz: 9 short blocks of silence,
+    // giving 1024 frames once the first block has been overlapped away
+    const SILENT_OGG: &[u8] = &[
+        0x4f, 0x67, 0x67, 0x53, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xb4, 0x01, 0x24, 0x02, 0x01, 0x1e, 0x01, 0x76,
+        0x6f, 0x72, 0x62, 0x69, 0x73, 0x00, 0x00, 0x00, 0x00, 0x02, 0x40, 0x1f, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x88, 0x01, 0x4f, 0x67,
+        0x67, 0x53, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00,
+        0x00, 0x01, 0x00, 0x00, 0x00, 0x8a, 0xa9, 0x29, 0x69, 0x02, 0x10, 0x34, 0x03, 0x76, 0x6f,
+        0x72, 0x62, 0x69, 0x73, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x05, 0x76,
+        0x6f, 0x72, 0x62, 0x69, 0x73, 0x00, 0x42, 0x43, 0x56, 0x01, 0x00, 0x02, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x40, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x01, 0x4f, 0x67, 0x67, 0x53, 0x00, 0x04, 0x00, 0x04, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x35, 0x08, 0xb5,
+        0x44, 0x09, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn ogg_decodes() {
+        let path = std::env::temp_dir().join(format!("vs_sound_{}.ogg", std::process::id()));
+        std::fs::write(&path, SILENT_OGG).unwrap();
+        let sound = SoundBuffer::load(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        let sound = sound.unwrap();
+        assert_eq!(sound.get_sample_rate(), 8000);
+        assert_eq!(sound.get_channels(), 2);
+        assert_eq!(sound.frame_count(), 1024);
+        assert!(sound.samples().iter().all(|sample| *sample == 0.0));
+
+        std::fs::write(&path, &SILENT_OGG[..40]).unwrap();
+        let truncated = SoundBuffer::load(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        assert!(truncated.is_err());
+    }
+}
@@ -0,0 +1,134 @@
+use std::{collections::HashMap, sync::Arc};
+
+use common::vs_error::VSError;
+
+use crate::sound_buffer::SoundBuffer;
+
+/// Caches loaded sounds by path, so each file is only decoded once.  Sounds
+/// are converted to the mixer's rate as they load.
+pub struct SoundManager {
+    sounds: HashMap<String, Arc<SoundBuffer>>,
+    sample_rate: u32,
+    channels: Option<u8>,
+}
+
+impl SoundManager {
+    pub fn new(sample_rate: u32) -> Self {
+        SoundManager {
+            sounds: HashMap::new(),
+            sample_rate,
+            channels: None,
+        }
+    }
+
+    pub fn get_sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Sounds already loaded keep their rate; the mixer still plays them at the right pitch
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate;
+    }
+
+    pub fn get_channels(&self) -> Option<u8> {
+        self.channels
+    }
+
+    /// Converts sounds loaded from now on to mono or stereo; None keeps each as it is
+    pub fn set_channels(&mut self, channels: Option<u8>) {
+        self.channels = channels;
+    }
+
+    pub fn load(&mut self, path: &str) -> Result<Arc<SoundBuffer>, VSError> {
+        if let Some(sound) = self.sounds.get(path) {
+            return Ok(Arc::clone(sound));
+        }
+
+        let sound = Arc::new(self.prepare(SoundBuffer::load(path)?));
+        self.sounds.insert(path.to_string(), Arc::clone(&sound));
+        Ok(sound)
+    }
+
+    /// Adds a sound created in code, replacing any existing sound of the same name
+    pub fn insert(&mut self, name: &str, sound: SoundBuffer) -> Arc<SoundBuffer> {
+        let sound = Arc::new(self.prepare(sound));
+        self.sounds.insert(name.to_string(), Arc::clone(&sound));
+        sound
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<SoundBuffer>> {
+        self.sounds.get(name).cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.sounds.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sounds.is_empty()
+    }
+
+    /// Drops any sound which is no longer referenced outside the manager,
+    /// including by a voice that is still playing it
+    pub fn release_unused(&mut self) {
+        self.sounds.retain(|_, sound| Arc::strong_count(sound) > 1);
+    }
+
+    fn prepare(&self, sound: SoundBuffer) -> SoundBuffer {
+        let sound = match self.channels {
+            Some(1) => sound.to_mono(),
+            Some(2) => sound.to_stereo(),
+            _ => sound,
+        };
+        sound.resampled(self.sample_rate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sounds_are_cached_by_path() {
+        let path = std::env::temp_dir().join(format!("vs_sound_cache_{}.wav", std::process::id()));
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 22050,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for _ in 0..100 {
+            writer.write_sample(0i16).unwrap();
+        }
+        writer.finalize().unwrap();
+        let path = path.to_str().unwrap();
+
+        let mut manager = SoundManager::new(44100);
+        manager.set_channels(Some(2));
+        let first = manager.load(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        // the file's gone, so this can only come from the cache
+        let second = manager.load(path).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(first.get_sample_rate(), 44100);
+        assert_eq!(first.get_channels(), 2);
+        assert_eq!(first.frame_count(), 200);
+
+        let beep = manager.insert("beep", SoundBuffer::new(44100, 1, vec![0.0; 10]));
+        assert!(Arc::ptr_eq(&manager.get("beep").unwrap(), &beep));
+        assert_eq!(manager.len(), 2);
+
+        // only sounds still held elsewhere survive
+        drop(first);
+        manager.release_unused();
+        assert_eq!(manager.len(), 2);
+        drop(second);
+        manager.release_unused();
+        assert_eq!(manager.len(), 1);
+        assert!(manager.get(path).is_none());
+        drop(beep);
+        manager.release_unused();
+        assert!(manager.is_empty());
+    }
+}
@@ -1,6 +1,794 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex, MutexGuard},
+};
 
+use common::{
+    audio::{AudioOutput, AudioSource},
+    vs_error::VSError,
+};
+use log::{info, warn};
 
-#[derive(Default)]
+use crate::{
+    bus::{BusId, Ducking, EFFECTS_BUS},
+    effects::{Effect, EffectId},
+    mixer::{
+        DEFAULT_SAMPLE_RATE, DEFAULT_VOICE_COUNT, Mixer, MixerSource, PlayParams, VoiceHandle,
+    },
+    music::{MusicDecoder, MusicId, MusicStream, MusicTrack, MusicVoice, OggDecoder},
+    sound_buffer::SoundBuffer,
+    sound_manager::SoundManager,
+    spatial::{Doppler, Emitter, Listener, spatialize},
+    synth::SynthParams,
+};
+
+// how far ahead of the audio thread music is decoded
+const MUSIC_BUFFER_SECONDS: f32 = 0.5;
+// offline rendering updates as a game running at this rate would
+const OFFLINE_UPDATES_PER_SECOND: u32 = 60;
+
+struct QueuedMusic {
+    track: MusicTrack,
+    decoder: Option<Box<dyn MusicDecoder>>, // opened from the track's file when it starts
+    crossfade: f32,
+}
+
+impl QueuedMusic {
+    fn open(self) -> Result<(MusicTrack, Box<dyn MusicDecoder>, f32), VSError> {
+        let decoder = match self.decoder {
+            Some(decoder) => decoder,
+            None => Box::new(OggDecoder::open(self.track.get_path())?),
+        };
+        Ok((self.track, decoder, self.crossfade))
+    }
+}
+
+/// Plays sounds through the backend's audio output.  Until an output is
+/// attached, sounds still start and stop, but nothing is heard.
 pub struct SoundSystem {
+    active: bool,
+    mixer: Arc<Mutex<Mixer>>,
+    output: Option<Box<dyn AudioOutput>>,
+    sounds: SoundManager,
+
+    listener: Listener,
+    doppler: Doppler,
+    positioned: HashMap<VoiceHandle, (PlayParams, Emitter)>, // as played, before spatializing
+
+    music: Vec<MusicStream>, // the current track is last; the others are fading out
+    music_queue: VecDeque<QueuedMusic>,
+    next_music_id: MusicId,
+}
+
+impl Default for SoundSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SoundSystem {
+    pub fn new() -> Self {
+        Self::with_voices(DEFAULT_VOICE_COUNT)
+    }
+
+    /// At most `voice_count` sounds play at once
+    pub fn with_voices(voice_count: usize) -> Self {
+        SoundSystem {
+            active: true,
+            mixer: Arc::new(Mutex::new(Mixer::new(DEFAULT_SAMPLE_RATE, voice_count))),
+            output: None,
+            sounds: SoundManager::new(DEFAULT_SAMPLE_RATE),
+            listener: Listener::default(),
+            doppler: Doppler::default(),
+            positioned: HashMap::new(),
+            music: Vec::new(),
+            music_queue: VecDeque::new(),
+            next_music_id: 0,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    pub fn set_active(&mut self, active: bool) {
+        self.active = active;
+    }
+
+    /// For the backend to pull mixed samples from, when opening its output
+    pub fn audio_source(&self) -> Box<dyn AudioSource> {
+        Box::new(MixerSource::new(Arc::clone(&self.mixer)))
+    }
+
+    /// Mixes at the output's rate and starts it playing
+    pub fn set_output(&mut self, mut output: Box<dyn AudioOutput>) {
+        let spec = output.spec();
+        self.mixer().set_sample_rate(spec.sample_rate);
+        self.sounds.set_sample_rate(spec.sample_rate);
+        output.set_paused(false);
+        info!("Sound system mixing at {} Hz", spec.sample_rate);
+        self.output = Some(output);
+    }
+
+    pub fn has_output(&self) -> bool {
+        self.output.is_some()
+    }
+
+    /// Mixes at this rate when there's no output, e.g. for offline rendering
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        if self.output.is_some() {
+            warn!("sound system can't change rate while it has an output");
+            return;
+        }
+        self.mixer().set_sample_rate(sample_rate);
+        self.sounds.set_sample_rate(sample_rate);
+    }
+
+    /// Mixes the next `seconds` of sound into a stereo buffer, without an
+    /// audio device, updating between blocks as a game would each frame.
+    /// The result depends only on what was played, so tests can check it.
+    pub fn render(&mut self, seconds: f32) -> SoundBuffer {
+        if self.output.is_some() {
+            warn!("rendering sound offline while an output is also pulling from the mixer");
+        }
+        let sample_rate = self.get_sample_rate();
+        let frames = self.seconds_to_frames(seconds);
+        let block = (sample_rate / OFFLINE_UPDATES_PER_SECOND).max(1) as usize;
+
+        let mut samples = vec![0.0; frames * 2];
+        for chunk in samples.chunks_mut(block * 2) {
+            self.update();
+            self.mixer().mix(chunk);
+        }
+        SoundBuffer::new(sample_rate, 2, samples)
+    }
+
+    /// As `render`, saved as a WAV file
+    pub fn render_to_wav(&mut self, seconds: f32, path: &str) -> Result<SoundBuffer, VSError> {
+        let sound = self.render(seconds);
+        sound.save_wav(path)?;
+        Ok(sound)
+    }
+
+    /// Pausing the output holds every sound where it is
+    pub fn set_paused(&mut self, paused: bool) {
+        if let Some(output) = self.output.as_mut() {
+            output.set_paused(paused);
+        }
+    }
+
+    /// A WAV or Ogg Vorbis file, decoded once and shared by everything that plays it
+    pub fn load_sound(&mut self, path: &str) -> Result<Arc<SoundBuffer>, VSError> {
+        self.sounds.load(path)
+    }
+
+    /// Renders a synthesized sound at the mixer's rate, and keeps it under `name`
+    pub fn synthesize(&mut self, name: &str, params: &SynthParams) -> Arc<SoundBuffer> {
+        let sound = params.render(self.get_sample_rate());
+        self.sounds.insert(name, sound)
+    }
+
+    pub fn sounds(&self) -> &SoundManager {
+        &self.sounds
+    }
+
+    pub fn sounds_mut(&mut self) -> &mut SoundManager {
+        &mut self.sounds
+    }
+
+    /// None if every voice is busy with something of a higher priority
+    pub fn play(&mut self, buffer: &Arc<SoundBuffer>, params: PlayParams) -> Option<VoiceHandle> {
+        self.mixer().play(buffer, params)
+    }
+
+    /// Plays a sound at a place in the world.  Its volume, pan and pitch
+    /// are taken as a starting point and adjusted from the listener each frame.
+    pub fn play_at(
+        &mut self,
+        buffer: &Arc<SoundBuffer>,
+        params: PlayParams,
+        emitter: Emitter,
+    ) -> Option<VoiceHandle> {
+        let voice = self.play(buffer, self.spatialized(&params, &emitter))?;
+        self.positioned.insert(voice, (params, emitter));
+        Some(voice)
+    }
+
+    /// Moves a sound played with `play_at`
+    pub fn set_emitter(&mut self, voice: VoiceHandle, emitter: Emitter) {
+        if let Some((_, current)) = self.positioned.get_mut(&voice) {
+            *current = emitter;
+        }
+    }
+
+    pub fn get_emitter(&self, voice: VoiceHandle) -> Option<Emitter> {
+        self.positioned.get(&voice).map(|(_, emitter)| *emitter)
+    }
+
+    pub fn get_listener(&self) -> Listener {
+        self.listener
+    }
+
+    /// Usually set each frame from the game's camera, with `Listener::from_camera`
+    pub fn set_listener(&mut self, listener: Listener) {
+        self.listener = listener;
+    }
+
+    pub fn get_doppler(&self) -> Doppler {
+        self.doppler
+    }
+
+    pub fn set_doppler(&mut self, doppler: Doppler) {
+        self.doppler = doppler;
+    }
+
+    pub fn stop(&mut self, voice: VoiceHandle) {
+        self.mixer().stop(voice);
+    }
+
+    pub fn stop_all(&mut self) {
+        self.mixer().stop_all();
+    }
+
+    pub fn is_playing(&self, voice: VoiceHandle) -> bool {
+        self.mixer().is_playing(voice)
+    }
+
+    pub fn set_voice_volume(&mut self, voice: VoiceHandle, volume: f32) {
+        self.change_voice(voice, |params| params.volume = volume);
+    }
+
+    /// Positioned sounds are panned from where they are, on top of this
+    pub fn set_voice_pan(&mut self, voice: VoiceHandle, pan: f32) {
+        self.change_voice(voice, |params| params.pan = pan);
+    }
+
+    pub fn set_voice_pitch(&mut self, voice: VoiceHandle, pitch: f32) {
+        self.change_voice(voice, |params| params.pitch = pitch);
+    }
+
+    pub fn get_master_volume(&self) -> f32 {
+        self.mixer().get_master_volume()
+    }
+
+    pub fn set_master_volume(&mut self, volume: f32) {
+        self.mixer().set_master_volume(volume);
+    }
+
+    /// Sounds play on the effects bus unless their `PlayParams` say otherwise
+    pub fn get_effects_volume(&self) -> f32 {
+        self.get_bus_volume(EFFECTS_BUS)
+    }
+
+    pub fn set_effects_volume(&mut self, volume: f32) {
+        self.set_bus_volume(EFFECTS_BUS, volume);
+    }
+
+    /// A new bus feeding `parent`, e.g. "dialogue" into the master bus
+    pub fn add_bus(&mut self, name: &str, parent: BusId) -> BusId {
+        self.mixer().add_bus(name, parent)
+    }
+
+    pub fn find_bus(&self, name: &str) -> Option<BusId> {
+        self.mixer().find_bus(name)
+    }
+
+    pub fn get_bus_volume(&self, bus: BusId) -> f32 {
+        self.mixer().get_bus_volume(bus)
+    }
+
+    pub fn set_bus_volume(&mut self, bus: BusId, volume: f32) {
+        self.mixer().set_bus_volume(bus, volume);
+    }
+
+    pub fn is_bus_muted(&self, bus: BusId) -> bool {
+        self.mixer().is_bus_muted(bus)
+    }
+
+    pub fn set_bus_muted(&mut self, bus: BusId, muted: bool) {
+        self.mixer().set_bus_muted(bus, muted);
+    }
+
+    pub fn get_ducking(&self, bus: BusId) -> Option<Ducking> {
+        self.mixer().get_ducking(bus)
+    }
+
+    /// Turns `bus` down while its ducking's trigger bus is playing anything
+    pub fn set_ducking(&mut self, bus: BusId, ducking: Option<Ducking>) {
+        self.mixer().set_ducking(bus, ducking);
+    }
+
+    /// Adds an insert effect after any the bus already has.  The master bus
+    /// starts with a limiter.
+    pub fn add_bus_effect(&mut self, bus: BusId, effect: Effect) -> Option<EffectId> {
+        self.mixer().add_effect(bus, effect)
+    }
+
+    pub fn remove_bus_effect(&mut self, bus: BusId, effect: EffectId) -> bool {
+        self.mixer().remove_effect(bus, effect)
+    }
+
+    pub fn clear_bus_effects(&mut self, bus: BusId) {
+        self.mixer().clear_effects(bus);
+    }
+
+    pub fn get_bus_effects(&self, bus: BusId) -> Vec<EffectId> {
+        self.mixer().get_effects(bus)
+    }
+
+    pub fn get_bus_effect(&self, bus: BusId, effect: EffectId) -> Option<Effect> {
+        self.mixer().get_effect(bus, effect)
+    }
+
+    /// Moves an effect to new parameters over `seconds`, e.g. closing a
+    /// low-pass filter on the music while the game is paused
+    pub fn set_bus_effect(
+        &mut self,
+        bus: BusId,
+        id: EffectId,
+        effect: Effect,
+        seconds: f32,
+    ) -> bool {
+        self.mixer().set_effect(bus, id, effect, seconds)
+    }
+
+    pub fn get_voice_count(&self) -> usize {
+        self.mixer().get_voice_count()
+    }
+
+    pub fn get_playing_count(&self) -> usize {
+        self.mixer().get_playing_count()
+    }
+
+    pub fn get_sample_rate(&self) -> u32 {
+        self.mixer().get_sample_rate()
+    }
+
+    /// Streams a track from disk, crossfading from whatever is playing over
+    /// `crossfade` seconds.  Anything queued is dropped.
+    pub fn play_music(&mut self, track: MusicTrack, crossfade: f32) -> Result<(), VSError> {
+        let decoder = OggDecoder::open(track.get_path())?;
+        self.play_music_from(track, Box::new(decoder), crossfade);
+        Ok(())
+    }
+
+    /// As `play_music`, decoding with `decoder` rather than from the track's file
+    pub fn play_music_from(
+        &mut self,
+        track: MusicTrack,
+        decoder: Box<dyn MusicDecoder>,
+        crossfade: f32,
+    ) {
+        self.music_queue.clear();
+        self.start_music(track, decoder, crossfade);
+    }
+
+    /// Plays a track once the current one ends, crossfading over its last
+    /// `crossfade` seconds.  The current track stops looping so it can end.
+    pub fn queue_music(&mut self, track: MusicTrack, crossfade: f32) -> Result<(), VSError> {
+        if !self.is_music_playing() {
+            return self.play_music(track, crossfade);
+        }
+        self.enqueue_music(QueuedMusic {
+            track,
+            decoder: None,
+            crossfade,
+        });
+        Ok(())
+    }
+
+    /// As `queue_music`, decoding with `decoder` rather than from the track's file
+    pub fn queue_music_from(
+        &mut self,
+        track: MusicTrack,
+        decoder: Box<dyn MusicDecoder>,
+        crossfade: f32,
+    ) {
+        if !self.is_music_playing() {
+            return self.play_music_from(track, decoder, crossfade);
+        }
+        self.enqueue_music(QueuedMusic {
+            track,
+            decoder: Some(decoder),
+            crossfade,
+        });
+    }
+
+    /// Moves straight on to the next queued track, or stops if there isn't one
+    pub fn skip_music(&mut self, crossfade: f32) -> Result<(), VSError> {
+        match self.music_queue.pop_front() {
+            Some(queued) => {
+                let (track, decoder, _) = queued.open()?;
+                self.start_music(track, decoder, crossfade);
+                Ok(())
+            }
+            None => {
+                self.stop_music(crossfade);
+                Ok(())
+            }
+        }
+    }
+
+    /// Fades out everything playing and drops the queue
+    pub fn stop_music(&mut self, fade_out: f32) {
+        self.music_queue.clear();
+        let frames = self.seconds_to_frames(fade_out);
+        let mut mixer = self.mixer();
+        for stream in self.music.iter() {
+            if let Some(voice) = mixer.music_mut(stream.get_id()) {
+                voice.fade_out(frames);
+            }
+        }
+    }
+
+    /// False once the current track has been told to stop, even while it fades out
+    pub fn is_music_playing(&self) -> bool {
+        self.music.last().is_some_and(|current| {
+            self.mixer()
+                .music(current.get_id())
+                .is_some_and(|voice| !voice.is_fading_out())
+        })
+    }
+
+    pub fn get_current_music(&self) -> Option<&MusicTrack> {
+        self.music
+            .last()
+            .filter(|_| self.is_music_playing())
+            .map(|current| current.get_track())
+    }
+
+    pub fn get_music_volume(&self) -> f32 {
+        self.mixer().get_music_volume()
+    }
+
+    /// The music bus's volume, which scales every track on top of its own
+    pub fn set_music_volume(&mut self, volume: f32) {
+        self.mixer().set_music_volume(volume);
+    }
+
+    pub fn set_current_music_volume(&mut self, volume: f32) {
+        if let Some(current) = self.music.last() {
+            let id = current.get_id();
+            if let Some(voice) = self.mixer().music_mut(id) {
+                voice.set_volume(volume);
+            }
+        }
+    }
+
+    /// Follows positioned sounds as they and the listener move, decodes music
+    /// ahead of the audio thread, and moves on to queued tracks as the
+    /// current one runs out.  Called once a frame.
+    pub fn update(&mut self) {
+        self.update_positioned();
+
+        let sample_rate = self.get_sample_rate();
+        let crossfade = self.music_queue.front().map_or(0.0, |next| next.crossfade);
+        let ahead = self.seconds_to_frames(MUSIC_BUFFER_SECONDS.max(crossfade));
+
+        // streams whose voices have finished or faded out are done with
+        let queued: Vec<Option<usize>> = {
+            let mut mixer = self.mixer();
+            self.music
+                .iter()
+                .map(|stream| mixer.music_mut(stream.get_id()).map(|v| v.queued_frames()))
+                .collect()
+        };
+        let mut index = 0;
+        self.music.retain(|_| {
+            index += 1;
+            queued[index - 1].is_some()
+        });
+        let queued: Vec<usize> = queued.into_iter().flatten().collect();
+
+        for (stream, queued) in self.music.iter_mut().zip(queued.iter()) {
+            if stream.is_ended() || *queued >= ahead {
+                continue;
+            }
+            let samples = stream
+                .read(ahead - queued, sample_rate)
+                .unwrap_or_else(|_| {
+                    // already logged; let what's been decoded play out
+                    stream.end();
+                    Vec::new()
+                });
+            let mut mixer = lock_mixer(&self.mixer);
+            if let Some(voice) = mixer.music_mut(stream.get_id()) {
+                voice.push(&samples);
+                if stream.is_ended() {
+                    voice.end();
+                }
+            }
+        }
+
+        // start the next track as the current one's last crossfade begins
+        let remaining = match self.music.last() {
+            Some(current) if current.is_ended() => {
+                let id = current.get_id();
+                self.mixer().music(id).map(|v| v.queued_frames())
+            }
+            Some(_) => None,
+            None => Some(0),
+        };
+        if let Some(remaining) = remaining
+            && remaining <= self.seconds_to_frames(crossfade)
+            && let Some(queued) = self.music_queue.pop_front()
+        {
+            match queued.open() {
+                Ok((track, decoder, crossfade)) => {
+                    let fade = remaining as f32 / sample_rate as f32;
+                    self.start_music(track, decoder, fade.min(crossfade));
+                }
+                // already logged; skip to the one after
+                Err(_) => self.update(),
+            }
+        }
+    }
+
+    // the current track stops looping so it can end
+    fn enqueue_music(&mut self, queued: QueuedMusic) {
+        if let Some(current) = self.music.last_mut() {
+            current.set_looping(false);
+        }
+        self.music_queue.push_back(queued);
+    }
+
+    fn start_music(&mut self, track: MusicTrack, decoder: Box<dyn MusicDecoder>, crossfade: f32) {
+        let frames = self.seconds_to_frames(crossfade);
+        let id = self.next_music_id;
+        self.next_music_id += 1;
+        {
+            let mut mixer = self.mixer();
+            for stream in self.music.iter() {
+                if let Some(voice) = mixer.music_mut(stream.get_id()) {
+                    voice.fade_out(frames);
+                }
+            }
+            mixer.add_music(MusicVoice::new(id, track.get_volume(), frames));
+        }
+        info!("Playing music {}", track.get_path());
+        self.music.push(MusicStream::new(id, track, decoder));
+        self.update();
+    }
+
+    fn seconds_to_frames(&self, seconds: f32) -> usize {
+        (seconds.max(0.0) * self.get_sample_rate() as f32) as usize
+    }
+
+    fn change_voice(&mut self, voice: VoiceHandle, change: impl FnOnce(&mut PlayParams)) {
+        if let Some((params, emitter)) = self.positioned.get_mut(&voice) {
+            change(params);
+            let spatialized = spatialize_params(&self.listener, &self.doppler, params, emitter);
+            self.mixer().set_params(voice, spatialized);
+            return;
+        }
+        let mut mixer = self.mixer();
+        if let Some(mut params) = mixer.get_params(voice) {
+            change(&mut params);
+            mixer.set_params(voice, params);
+        }
+    }
+
+    fn spatialized(&self, params: &PlayParams, emitter: &Emitter) -> PlayParams {
+        spatialize_params(&self.listener, &self.doppler, params, emitter)
+    }
+
+    // follows positioned sounds and the listener as they move
+    fn update_positioned(&mut self) {
+        let mut mixer = lock_mixer(&self.mixer);
+        self.positioned.retain(|voice, _| mixer.is_playing(*voice));
+        for (voice, (params, emitter)) in self.positioned.iter() {
+            let spatialized = spatialize_params(&self.listener, &self.doppler, params, emitter);
+            mixer.set_params(*voice, spatialized);
+        }
+    }
+
+    fn mixer(&self) -> MutexGuard<'_, Mixer> {
+        lock_mixer(&self.mixer)
+    }
+}
+
+fn spatialize_params(
+    listener: &Listener,
+    doppler: &Doppler,
+    params: &PlayParams,
+    emitter: &Emitter,
+) -> PlayParams {
+    let heard = spatialize(listener, emitter, doppler);
+    PlayParams {
+        volume: params.volume * heard.gain,
+        pan: (params.pan + heard.pan).clamp(-1.0, 1.0),
+        pitch: params.pitch * heard.pitch,
+        ..*params
+    }
+}
+
+fn lock_mixer(mixer: &Mutex<Mixer>) -> MutexGuard<'_, Mixer> {
+    mixer.lock().expect("unable to lock sound mixer")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bus::{MASTER_BUS, MUSIC_BUS},
+        effects::FilterKind,
+        music::BufferDecoder,
+    };
+
+    #[test]
+    fn music_crossfades() {
+        let mut sound = SoundSystem::new();
+        let left = Arc::new(SoundBuffer::new(48000, 2, [0.5, 0.0].repeat(48000)));
+        let right = Arc::new(SoundBuffer::new(48000, 2, [0.0, 0.5].repeat(48000)));
+        let mut out = vec![0.0; 960];
+
+        sound.play_music_from(
+            MusicTrack::new("left").looping(),
+            Box::new(BufferDecoder::new(left)),
+            0.0,
+        );
+        sound.mixer().mix(&mut out);
+        assert_eq!(&out[958..], &[0.5, 0.0]);
+
+        // halfway through a 480 frame crossfade, both are at half volume
+        sound.play_music_from(
+            MusicTrack::new("right"),
+            Box::new(BufferDecoder::new(right)),
+            0.01,
+        );
+        sound.mixer().mix(&mut out[..480]);
+        assert!((out[478] - 0.25).abs() < 0.01 && (out[479] - 0.25).abs() < 0.01);
+        sound.mixer().mix(&mut out);
+        assert_eq!(&out[958..], &[0.0, 0.5]);
+        assert_eq!(sound.get_current_music().unwrap().get_path(), "right");
+
+        sound.set_music_volume(0.5);
+        sound.mixer().mix(&mut out);
+        assert!((out[959] - 0.25).abs() < 1e-5);
+
+        sound.stop_music(0.0);
+        assert!(!sound.is_music_playing());
+        sound.mixer().mix(&mut out);
+        sound.update();
+        assert!(sound.music.is_empty());
+    }
+
+    #[test]
+    fn offline_rendering() {
+        let render = || {
+            let mut sound = SoundSystem::new();
+            sound.set_sample_rate(1000);
+            let left = Arc::new(SoundBuffer::new(1000, 2, [0.5, 0.0].repeat(2000)));
+            let right = Arc::new(SoundBuffer::new(1000, 2, [0.0, 0.5].repeat(2000)));
+            sound.play_music_from(
+                MusicTrack::new("left"),
+                Box::new(BufferDecoder::new(left)),
+                0.0,
+            );
+            sound.queue_music_from(
+                MusicTrack::new("right"),
+                Box::new(BufferDecoder::new(right)),
+                0.5,
+            );
+            let music = sound.render(4.0);
+
+            // effects at half volume, on top of the music bus's own
+            sound.set_effects_volume(0.5);
+            let click = Arc::new(SoundBuffer::new(1000, 1, vec![1.0; 100]));
+            sound.play(
+                &click,
+                PlayParams {
+                    pan: 1.0,
+                    ..Default::default()
+                },
+            );
+            (music, sound.render(0.05))
+        };
+
+        let (music, effects) = render();
+        assert_eq!(music.frame_count(), 4000);
+        assert_eq!(music.frame(1000), (0.5, 0.0));
+        let (left, right) = music.frame(1750);
+        assert!((left - 0.25).abs() < 0.03 && (right - 0.25).abs() < 0.03);
+        assert_eq!(music.frame(2500), (0.0, 0.5));
+        assert_eq!(music.frame(3900), (0.0, 0.0));
+        assert!((effects.frame(40).1 - 0.5).abs() < 1e-5);
+        assert_eq!(render(), (music.clone(), effects));
+
+        let path = std::env::temp_dir().join(format!("vs_render_{}.wav", std::process::id()));
+        let path = path.to_str().unwrap();
+        music.save_wav(path).unwrap();
+        let loaded = SoundBuffer::load(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(loaded, music);
+    }
+
+    #[test]
+    fn bus_effects() {
+        let mut sound = SoundSystem::new();
+        sound.set_sample_rate(1000);
+        let loud = Arc::new(SoundBuffer::new(1000, 2, vec![0.8; 200]));
+        let play = |sound: &mut SoundSystem| {
+            for _ in 0..2 {
+                sound.play(&loud, PlayParams::default());
+            }
+            sound.render(0.1)
+        };
+
+        // the master limiter holds two loud sounds under full scale...
+        let limited = play(&mut sound);
+        assert!(limited.frame(50).0 < 1.0 && limited.frame(50).0 > 0.9);
+        // ...where without it they'd clip
+        let limiter = sound.get_bus_effects(MASTER_BUS)[0];
+        assert!(sound.remove_bus_effect(MASTER_BUS, limiter));
+        assert_eq!(play(&mut sound).frame(50).0, 1.0);
+
+        let open = Effect::Filter {
+            kind: FilterKind::LowPass,
+            cutoff: 20000.0,
+            q: 0.707,
+        };
+        let closed = Effect::Filter {
+            kind: FilterKind::LowPass,
+            cutoff: 200.0,
+            q: 0.707,
+        };
+        let filter = sound.add_bus_effect(MUSIC_BUS, open).unwrap();
+        assert!(sound.set_bus_effect(MUSIC_BUS, filter, closed, 1.0));
+        assert_eq!(sound.get_bus_effect(MUSIC_BUS, filter), Some(closed));
+        assert!(!sound.set_bus_effect(MASTER_BUS, filter, closed, 1.0));
+        sound.clear_bus_effects(MUSIC_BUS);
+        assert!(sound.get_bus_effects(MUSIC_BUS).is_empty());
+    }
+
+    #[test]
+    fn automated_filter_changes_the_sound() {
+        let mut sound = SoundSystem::new();
+        sound.set_sample_rate(8000);
+        // a 2kHz tone, a quarter of the sample rate
+        let tone = Arc::new(SoundBuffer::new(
+            8000,
+            1,
+            [0.0, 0.5, 0.0, -0.5].repeat(2000),
+        ));
+        let open = Effect::Filter {
+            kind: FilterKind::LowPass,
+            cutoff: 3500.0,
+            q: 0.707,
+        };
+        let closed = Effect::Filter {
+            kind: FilterKind::LowPass,
+            cutoff: 100.0,
+            q: 0.707,
+        };
+        let filter = sound.add_bus_effect(EFFECTS_BUS, open).unwrap();
+        sound.play(
+            &tone,
+            PlayParams {
+                looping: true,
+                ..Default::default()
+            },
+        );
+        let before = sound.render(0.25);
+        assert!(sound.set_bus_effect(EFFECTS_BUS, filter, closed, 0.5));
+        let sweep = sound.render(0.75);
 
-}
\ No newline at end of file
+        let peak = |sound: &SoundBuffer, from: usize, to: usize| {
+            (from..to).fold(0.0f32, |peak, frame| peak.max(sound.frame(frame).0.abs()))
+        };
+        // panned to the centre, so at -3dB
+        assert!(peak(&before, 1000, 2000) > 0.3);
+        // the tone fades as the cutoff falls past it, and stays gone
+        let early = peak(&sweep, 0, 1000);
+        let middle = peak(&sweep, 1500, 2500);
+        let late = peak(&sweep, 5000, 6000);
+        assert!(
+            early > middle && middle > late,
+            "{} {} {}",
+            early,
+            middle,
+            late
+        );
+        assert!(late < 0.01);
+    }
+}
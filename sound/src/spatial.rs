@@ -0,0 +1,235 @@
+//! Sounds at positions in the world.  Each frame the sound system works out
+//! how loud each positioned sound is from the listener, which side it's on,
+//! and how its pitch is shifted by how fast the two are closing.
+
+use common::{Transform2D, Vector2D, Vector3D};
+
+/// How a sound fades with distance
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Attenuation {
+    /// The same volume at any distance
+    None,
+    /// Full volume within `min`, fading evenly to silence at `max`
+    Linear { min: f32, max: f32 },
+    /// Full volume within `min`, then falling off with the inverse of the
+    /// distance, as real sounds do; `rolloff` exaggerates or softens it
+    Inverse { min: f32, rolloff: f32 },
+    /// Full volume within `min`, then `(distance / min) ^ -rolloff`
+    Exponential { min: f32, rolloff: f32 },
+}
+
+impl Default for Attenuation {
+    fn default() -> Self {
+        Attenuation::Inverse {
+            min: 1.0,
+            rolloff: 1.0,
+        }
+    }
+}
+
+impl Attenuation {
+    pub fn gain(self, distance: f32) -> f32 {
+        match self {
+            Attenuation::None => 1.0,
+            Attenuation::Linear { min, max } => {
+                if distance <= min {
+                    1.0
+                } else if distance >= max {
+                    0.0
+                } else {
+                    1.0 - (distance - min) / (max - min)
+                }
+            }
+            Attenuation::Inverse { min, rolloff } => {
+                if distance <= min {
+                    1.0
+                } else {
+                    min / (min + rolloff * (distance - min))
+                }
+            }
+            Attenuation::Exponential { min, rolloff } => {
+                if distance <= min {
+                    1.0
+                } else {
+                    (distance / min.max(f32::EPSILON)).powf(-rolloff)
+                }
+            }
+        }
+    }
+}
+
+/// Where sounds are heard from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Listener {
+    pub position: Vector3D,
+    pub velocity: Vector3D,
+    /// The listener's right ear points this way; a unit vector
+    pub right: Vector3D,
+}
+
+impl Default for Listener {
+    fn default() -> Self {
+        Listener {
+            position: Vector3D::ZERO,
+            velocity: Vector3D::ZERO,
+            right: Vector3D::X,
+        }
+    }
+}
+
+impl Listener {
+    pub fn new_2d(position: Vector2D, velocity: Vector2D) -> Self {
+        Listener {
+            position: position.extend(0.0).into(),
+            velocity: velocity.extend(0.0).into(),
+            right: Vector3D::X,
+        }
+    }
+
+    /// At the centre of the screen, facing the way the camera does.  The
+    /// camera maps world positions to logical screen coordinates, as given
+    /// to `InputSystem::set_world_transform`.
+    pub fn from_camera(camera: Transform2D, screen_size: Vector2D, velocity: Vector2D) -> Self {
+        let centre = camera.inverse_apply_to(screen_size * 0.5);
+        let right = Vector2D::from_angle(-camera.angle)
+            .rotate(Vector2D::X)
+            .normalize_or(Vector2D::X);
+        Listener {
+            position: centre.extend(0.0).into(),
+            velocity: velocity.extend(0.0).into(),
+            right: right.extend(0.0).into(),
+        }
+    }
+}
+
+/// A sound's place in the world
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Emitter {
+    pub position: Vector3D,
+    pub velocity: Vector3D,
+    pub attenuation: Attenuation,
+}
+
+impl Emitter {
+    pub fn new(position: Vector3D) -> Self {
+        Emitter {
+            position,
+            velocity: Vector3D::ZERO,
+            attenuation: Attenuation::default(),
+        }
+    }
+
+    pub fn new_2d(position: Vector2D) -> Self {
+        Emitter::new(position.extend(0.0).into())
+    }
+
+    pub fn with_velocity(mut self, velocity: Vector3D) -> Self {
+        self.velocity = velocity;
+        self
+    }
+
+    pub fn with_attenuation(mut self, attenuation: Attenuation) -> Self {
+        self.attenuation = attenuation;
+        self
+    }
+}
+
+/// How the world's motion shifts pitch.  `speed_of_sound` is in world units
+/// per second; `factor` scales the effect, 0 turns it off.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Doppler {
+    pub speed_of_sound: f32,
+    pub factor: f32,
+}
+
+impl Default for Doppler {
+    fn default() -> Self {
+        Doppler {
+            speed_of_sound: 343.0,
+            factor: 1.0,
+        }
+    }
+}
+
+/// Volume, pan and pitch multiplier for an emitter as heard by a listener
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Spatialized {
+    pub gain: f32,
+    pub pan: f32,
+    pub pitch: f32,
+}
+
+pub fn spatialize(listener: &Listener, emitter: &Emitter, doppler: &Doppler) -> Spatialized {
+    let offset = emitter.position - listener.position;
+    let distance = offset.length();
+    let direction = if distance > f32::EPSILON {
+        offset / distance
+    } else {
+        Vector3D::ZERO
+    };
+
+    // from the source towards the listener, as the doppler equation wants it
+    let pitch = if doppler.factor > 0.0 && distance > f32::EPSILON {
+        let limit = doppler.speed_of_sound / doppler.factor * 0.99;
+        let listener_speed = (-direction).dot(listener.velocity).min(limit);
+        let emitter_speed = (-direction).dot(emitter.velocity).min(limit);
+        (doppler.speed_of_sound - doppler.factor * listener_speed)
+            / (doppler.speed_of_sound - doppler.factor * emitter_speed)
+    } else {
+        1.0
+    };
+
+    Spatialized {
+        gain: emitter.attenuation.gain(distance),
+        pan: direction.dot(listener.right).clamp(-1.0, 1.0),
+        pitch,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn position_sets_gain_pan_and_pitch() {
+        let linear = Attenuation::Linear {
+            min: 10.0,
+            max: 30.0,
+        };
+        assert_eq!(linear.gain(5.0), 1.0);
+        assert_eq!(linear.gain(20.0), 0.5);
+        assert_eq!(linear.gain(40.0), 0.0);
+        let inverse = Attenuation::Inverse {
+            min: 10.0,
+            rolloff: 1.0,
+        };
+        assert_eq!(inverse.gain(20.0), 0.5);
+
+        // a camera turned half way round hears things flipped
+        let camera = Transform2D::new(
+            Vector2D::new(-100.0, 0.0),
+            std::f32::consts::PI,
+            Vector2D::ONE,
+        );
+        let listener = Listener::from_camera(camera, Vector2D::new(200.0, 100.0), Vector2D::ZERO);
+        assert!(
+            listener
+                .position
+                .distance(Vector3D::new(-200.0, -50.0, 0.0))
+                < 1e-3
+        );
+        let left = Emitter::new_2d(Vector2D::new(100.0, -50.0)).with_attenuation(Attenuation::None);
+        let heard = spatialize(&listener, &left, &Doppler::default());
+        assert!((heard.pan + 1.0).abs() < 1e-5);
+        assert_eq!(heard.gain, 1.0);
+
+        // approaching sounds are higher
+        let listener = Listener::default();
+        let approaching = Emitter::new_2d(Vector2D::new(100.0, 0.0))
+            .with_velocity(Vector3D::new(-34.3, 0.0, 0.0));
+        let heard = spatialize(&listener, &approaching, &Doppler::default());
+        assert!((heard.pitch - 1.0 / 0.9).abs() < 1e-4);
+        let receding = approaching.with_velocity(Vector3D::new(34.3, 0.0, 0.0));
+        assert!(spatialize(&listener, &receding, &Doppler::default()).pitch < 1.0);
+    }
+}
@@ -0,0 +1,550 @@
+//! Sound effects made from a handful of numbers rather than recorded, in the
+//! style of sfxr.  Parameters can be written as text, one per line:
+//!
+//! ```text
+//! # a coin pickup
+//! waveform = square
+//! frequency = 880
+//! sustain = 0.05
+//! release = 0.2
+//! arpeggio = 1.5
+//! arpeggio_time = 0.06
+//! ```
+//!
+//! Anything left out keeps its default.
+
+use std::{f32::consts::TAU, fs, path::Path};
+
+use common::{math::random::Random, vs_error::VSError};
+use log::{error, info, warn};
+
+use crate::sound_buffer::SoundBuffer;
+
+// values held in the noise waveform for each cycle
+const NOISE_STEPS: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Waveform {
+    #[default]
+    Square,
+    Saw,
+    Sine,
+    Noise,
+}
+
+impl Waveform {
+    pub const ALL: &[Waveform] = &[
+        Waveform::Square,
+        Waveform::Saw,
+        Waveform::Sine,
+        Waveform::Noise,
+    ];
+
+    pub fn id(self) -> &'static str {
+        match self {
+            Waveform::Square => "square",
+            Waveform::Saw => "saw",
+            Waveform::Sine => "sine",
+            Waveform::Noise => "noise",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Waveform::ALL.iter().find(|w| w.id() == id).copied()
+    }
+}
+
+/// Starting points for common game sounds, varied by a seed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SynthPreset {
+    Pickup,
+    Laser,
+    Explosion,
+    PowerUp,
+    Hit,
+    Jump,
+    Blip,
+}
+
+/// Everything that shapes a synthesized sound.  Times are in seconds,
+/// frequencies in Hz, and slides and sweeps in octaves per second.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SynthParams {
+    pub waveform: Waveform,
+    pub volume: f32,
+
+    // envelope
+    pub attack: f32,
+    pub decay: f32,
+    pub sustain_level: f32,
+    pub sustain: f32,
+    pub release: f32,
+
+    // pitch
+    pub frequency: f32,
+    /// The sound stops if it slides below this
+    pub min_frequency: f32,
+    pub slide: f32,
+    pub slide_acceleration: f32,
+    /// A fraction of the frequency
+    pub vibrato_depth: f32,
+    pub vibrato_speed: f32,
+    /// Multiplies the frequency once, after `arpeggio_time`
+    pub arpeggio: f32,
+    pub arpeggio_time: f32,
+
+    // square wave
+    pub duty: f32,
+    /// Change in duty per second
+    pub duty_sweep: f32,
+
+    // filters; a cutoff of 0 is off
+    pub lowpass: f32,
+    /// From 0 to just under 1
+    pub lowpass_resonance: f32,
+    pub lowpass_sweep: f32,
+    pub highpass: f32,
+    pub highpass_sweep: f32,
+
+    // bit crush; 0 is off
+    pub bit_depth: u32,
+    pub crush_rate: f32,
+
+    /// For the noise waveform
+    pub seed: u64,
+}
+
+impl Default for SynthParams {
+    fn default() -> Self {
+        SynthParams {
+            waveform: Waveform::Square,
+            volume: 0.5,
+            attack: 0.0,
+            decay: 0.0,
+            sustain_level: 1.0,
+            sustain: 0.1,
+            release: 0.2,
+            frequency: 440.0,
+            min_frequency: 0.0,
+            slide: 0.0,
+            slide_acceleration: 0.0,
+            vibrato_depth: 0.0,
+            vibrato_speed: 0.0,
+            arpeggio: 1.0,
+            arpeggio_time: 0.0,
+            duty: 0.5,
+            duty_sweep: 0.0,
+            lowpass: 0.0,
+            lowpass_resonance: 0.0,
+            lowpass_sweep: 0.0,
+            highpass: 0.0,
+            highpass_sweep: 0.0,
+            bit_depth: 0,
+            crush_rate: 0.0,
+            seed: 0,
+        }
+    }
+}
+
+impl SynthParams {
+    pub fn preset(preset: SynthPreset, seed: u64) -> Self {
+        let random = Random::new_from_seed64(seed);
+        let defaults = SynthParams {
+            seed,
+            ..Default::default()
+        };
+        match preset {
+            SynthPreset::Pickup => SynthParams {
+                frequency: random.get_float(700.0, 1400.0),
+                sustain: random.get_float(0.03, 0.08),
+                release: random.get_float(0.1, 0.25),
+                arpeggio: random.get_float(1.3, 1.6),
+                arpeggio_time: random.get_float(0.04, 0.08),
+                ..defaults
+            },
+            SynthPreset::Laser => SynthParams {
+                waveform: if random.get_bool() {
+                    Waveform::Saw
+                } else {
+                    Waveform::Square
+                },
+                frequency: random.get_float(800.0, 2000.0),
+                min_frequency: 100.0,
+                slide: random.get_float(-12.0, -5.0),
+                duty: random.get_float(0.2, 0.5),
+                duty_sweep: random.get_float(0.0, 1.0),
+                sustain: random.get_float(0.05, 0.15),
+                release: random.get_float(0.05, 0.2),
+                ..defaults
+            },
+            SynthPreset::Explosion => SynthParams {
+                waveform: Waveform::Noise,
+                frequency: random.get_float(400.0, 1200.0),
+                slide: random.get_float(-3.0, -1.0),
+                sustain: random.get_float(0.1, 0.3),
+                release: random.get_float(0.3, 0.6),
+                lowpass: random.get_float(2000.0, 6000.0),
+                lowpass_sweep: random.get_float(-3.0, -1.0),
+                ..defaults
+            },
+            SynthPreset::PowerUp => SynthParams {
+                waveform: if random.get_bool() {
+                    Waveform::Saw
+                } else {
+                    Waveform::Square
+                },
+                frequency: random.get_float(200.0, 500.0),
+                slide: random.get_float(2.0, 5.0),
+                vibrato_depth: random.get_float(0.0, 0.1),
+                vibrato_speed: random.get_float(10.0, 20.0),
+                sustain: random.get_float(0.2, 0.4),
+                release: random.get_float(0.1, 0.3),
+                ..defaults
+            },
+            SynthPreset::Hit => SynthParams {
+                waveform: if random.get_bool() {
+                    Waveform::Noise
+                } else {
+                    Waveform::Saw
+                },
+                frequency: random.get_float(300.0, 900.0),
+                slide: random.get_float(-8.0, -3.0),
+                sustain: random.get_float(0.01, 0.05),
+                release: random.get_float(0.05, 0.15),
+                highpass: random.get_float(0.0, 300.0),
+                ..defaults
+            },
+            SynthPreset::Jump => SynthParams {
+                frequency: random.get_float(250.0, 500.0),
+                slide: random.get_float(2.0, 4.0),
+                duty: random.get_float(0.3, 0.5),
+                sustain: random.get_float(0.05, 0.15),
+                release: random.get_float(0.05, 0.15),
+                highpass: random.get_float(0.0, 200.0),
+                ..defaults
+            },
+            SynthPreset::Blip => SynthParams {
+                waveform: if random.get_bool() {
+                    Waveform::Sine
+                } else {
+                    Waveform::Square
+                },
+                frequency: random.get_float(600.0, 1500.0),
+                sustain: random.get_float(0.02, 0.06),
+                release: random.get_float(0.01, 0.05),
+                ..defaults
+            },
+        }
+    }
+
+    /// In seconds, unless the pitch slides below `min_frequency` first
+    pub fn duration(&self) -> f32 {
+        self.attack.max(0.0) + self.decay.max(0.0) + self.sustain.max(0.0) + self.release.max(0.0)
+    }
+
+    /// A mono sound, ready for the mixer
+    pub fn render(&self, sample_rate: u32) -> SoundBuffer {
+        let rate = sample_rate.max(1) as f32;
+        let dt = 1.0 / rate;
+        let frames = (self.duration() * rate).ceil() as usize;
+        let random = Random::new_from_seed64(self.seed);
+        let mut noise = [0.0; NOISE_STEPS];
+        noise
+            .iter_mut()
+            .for_each(|n| *n = random.get_float(-1.0, 1.0));
+
+        let mut samples = Vec::with_capacity(frames);
+        let mut phase = 0.0f32;
+        let mut octaves = 0.0f32;
+        let mut slide = self.slide;
+        let mut base = self.frequency;
+        let mut arpeggiated = self.arpeggio_time <= 0.0;
+        let mut lowpass = Lowpass::default();
+        let mut highpass = Highpass::default();
+        let mut crush_held = 0.0;
+        let mut crush_phase = 1.0f32;
+
+        for frame in 0..frames {
+            let time = frame as f32 * dt;
+            if !arpeggiated && time >= self.arpeggio_time {
+                base *= self.arpeggio;
+                arpeggiated = true;
+            }
+            slide += self.slide_acceleration * dt;
+            octaves += slide * dt;
+            let frequency = base * octaves.exp2();
+            if frequency < self.min_frequency {
+                break;
+            }
+            let vibrato = 1.0 + self.vibrato_depth * (TAU * self.vibrato_speed * time).sin();
+            let frequency = (frequency * vibrato).clamp(0.0, rate * 0.5);
+
+            phase += frequency * dt;
+            if phase >= 1.0 {
+                phase = phase.fract();
+                if self.waveform == Waveform::Noise {
+                    noise
+                        .iter_mut()
+                        .for_each(|n| *n = random.get_float(-1.0, 1.0));
+                }
+            }
+            let duty = (self.duty + self.duty_sweep * time).clamp(0.0, 1.0);
+            let mut sample = match self.waveform {
+                Waveform::Square => {
+                    if phase < duty {
+                        1.0
+                    } else {
+                        -1.0
+                    }
+                }
+                Waveform::Saw => 1.0 - 2.0 * phase,
+                Waveform::Sine => (TAU * phase).sin(),
+                Waveform::Noise => noise[(phase * NOISE_STEPS as f32) as usize % NOISE_STEPS],
+            };
+
+            if self.lowpass > 0.0 {
+                let cutoff = self.lowpass * (self.lowpass_sweep * time).exp2();
+                sample = lowpass.process(sample, cutoff, self.lowpass_resonance, rate);
+            }
+            if self.highpass > 0.0 {
+                let cutoff = self.highpass * (self.highpass_sweep * time).exp2();
+                sample = highpass.process(sample, cutoff, rate);
+            }
+            if self.crush_rate > 0.0 {
+                crush_phase += self.crush_rate / rate;
+                if crush_phase >= 1.0 {
+                    crush_phase = crush_phase.fract();
+                    crush_held = sample;
+                }
+                sample = crush_held;
+            }
+            if self.bit_depth > 0 {
+                let levels = (1u64 << (self.bit_depth.min(24) - 1)) as f32;
+                sample = (sample * levels).round() / levels;
+            }
+
+            samples.push((sample * self.envelope(time) * self.volume).clamp(-1.0, 1.0));
+        }
+        SoundBuffer::new(sample_rate, 1, samples)
+    }
+
+    fn envelope(&self, time: f32) -> f32 {
+        let attack = self.attack.max(0.0);
+        let decay = self.decay.max(0.0);
+        let sustain = self.sustain.max(0.0);
+        let release = self.release.max(f32::EPSILON);
+        if time < attack {
+            time / attack
+        } else if time < attack + decay {
+            1.0 - (1.0 - self.sustain_level) * (time - attack) / decay
+        } else if time < attack + decay + sustain {
+            self.sustain_level
+        } else {
+            let t = (time - attack - decay - sustain) / release;
+            self.sustain_level * (1.0 - t).max(0.0)
+        }
+    }
+
+    /// In the text format, with every parameter written out
+    pub fn to_text(&self) -> String {
+        let mut text = format!("waveform = {}\n", self.waveform.id());
+        let numbers = [
+            ("volume", self.volume),
+            ("attack", self.attack),
+            ("decay", self.decay),
+            ("sustain_level", self.sustain_level),
+            ("sustain", self.sustain),
+            ("release", self.release),
+            ("frequency", self.frequency),
+            ("min_frequency", self.min_frequency),
+            ("slide", self.slide),
+            ("slide_acceleration", self.slide_acceleration),
+            ("vibrato_depth", self.vibrato_depth),
+            ("vibrato_speed", self.vibrato_speed),
+            ("arpeggio", self.arpeggio),
+            ("arpeggio_time", self.arpeggio_time),
+            ("duty", self.duty),
+            ("duty_sweep", self.duty_sweep),
+            ("lowpass", self.lowpass),
+            ("lowpass_resonance", self.lowpass_resonance),
+            ("lowpass_sweep", self.lowpass_sweep),
+            ("highpass", self.highpass),
+            ("highpass_sweep", self.highpass_sweep),
+            ("crush_rate", self.crush_rate),
+        ];
+        for (name, value) in numbers {
+            text.push_str(&format!("{} = {}\n", name, value));
+        }
+        text.push_str(&format!("bit_depth = {}\n", self.bit_depth));
+        text.push_str(&format!("seed = {}\n", self.seed));
+        text
+    }
+
+    /// Lines which can't be read are skipped
+    pub fn from_text(text: &str) -> Self {
+        let mut params = SynthParams::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((name, value)) = line.split_once('=') else {
+                warn!("skipping synth line {}: no '='", number + 1);
+                continue;
+            };
+            if !params.set(name.trim(), value.trim()) {
+                warn!("skipping synth line {}: {}", number + 1, line);
+            }
+        }
+        params
+    }
+
+    // false if the name is unknown or the value doesn't parse
+    fn set(&mut self, name: &str, value: &str) -> bool {
+        match name {
+            "waveform" => Waveform::from_id(value)
+                .map(|w| self.waveform = w)
+                .is_some(),
+            "bit_depth" => value.parse().map(|v| self.bit_depth = v).is_ok(),
+            "seed" => value.parse().map(|v| self.seed = v).is_ok(),
+            _ => {
+                let Ok(value) = value.parse::<f32>() else {
+                    return false;
+                };
+                let field = match name {
+                    "volume" => &mut self.volume,
+                    "attack" => &mut self.attack,
+                    "decay" => &mut self.decay,
+                    "sustain_level" => &mut self.sustain_level,
+                    "sustain" => &mut self.sustain,
+                    "release" => &mut self.release,
+                    "frequency" => &mut self.frequency,
+                    "min_frequency" => &mut self.min_frequency,
+                    "slide" => &mut self.slide,
+                    "slide_acceleration" => &mut self.slide_acceleration,
+                    "vibrato_depth" => &mut self.vibrato_depth,
+                    "vibrato_speed" => &mut self.vibrato_speed,
+                    "arpeggio" => &mut self.arpeggio,
+                    "arpeggio_time" => &mut self.arpeggio_time,
+                    "duty" => &mut self.duty,
+                    "duty_sweep" => &mut self.duty_sweep,
+                    "lowpass" => &mut self.lowpass,
+                    "lowpass_resonance" => &mut self.lowpass_resonance,
+                    "lowpass_sweep" => &mut self.lowpass_sweep,
+                    "highpass" => &mut self.highpass,
+                    "highpass_sweep" => &mut self.highpass_sweep,
+                    "crush_rate" => &mut self.crush_rate,
+                    _ => return false,
+                };
+                *field = value;
+                true
+            }
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, VSError> {
+        let text = fs::read_to_string(path).map_err(|e| {
+            error!(
+                "unable to read synth parameters from {}: {}",
+                path.display(),
+                e
+            );
+            VSError::Sound_FileNotFound
+        })?;
+        info!("Loaded synth parameters from {}", path.display());
+        Ok(SynthParams::from_text(&text))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), VSError> {
+        fs::write(path, self.to_text()).map_err(|e| {
+            error!(
+                "unable to write synth parameters to {}: {}",
+                path.display(),
+                e
+            );
+            VSError::Sound_WriteFailed
+        })?;
+        info!("Saved synth parameters to {}", path.display());
+        Ok(())
+    }
+}
+
+// a resonant two pole filter, as a trapezoidal state variable filter, which
+// stays stable however fast the cutoff sweeps
+#[derive(Debug, Default)]
+struct Lowpass {
+    ic1: f32,
+    ic2: f32,
+}
+
+impl Lowpass {
+    fn process(&mut self, input: f32, cutoff: f32, resonance: f32, rate: f32) -> f32 {
+        let g = (std::f32::consts::PI * cutoff.clamp(1.0, rate * 0.45) / rate).tan();
+        let k = 2.0 - 2.0 * resonance.clamp(0.0, 0.95);
+        let a1 = 1.0 / (1.0 + g * (g + k));
+        let a2 = g * a1;
+        let a3 = g * a2;
+        let v3 = input - self.ic2;
+        let v1 = a1 * self.ic1 + a2 * v3;
+        let v2 = self.ic2 + a2 * self.ic1 + a3 * v3;
+        self.ic1 = 2.0 * v1 - self.ic1;
+        self.ic2 = 2.0 * v2 - self.ic2;
+        v2
+    }
+}
+
+#[derive(Debug, Default)]
+struct Highpass {
+    input: f32,
+    output: f32,
+}
+
+impl Highpass {
+    fn process(&mut self, input: f32, cutoff: f32, rate: f32) -> f32 {
+        let rc = 1.0 / (TAU * cutoff.max(f32::EPSILON));
+        let a = rc / (rc + 1.0 / rate);
+        self.output = a * (self.output + input - self.input);
+        self.input = input;
+        self.output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_from_text() {
+        let text = "# laser\nwaveform = saw\nfrequency = 1000\nslide = -4\n\
+                    min_frequency = 250\nsustain = 1\nrelease = 0\nbit_depth = 2\n\
+                    nonsense = 3\n";
+        let params = SynthParams::from_text(text);
+        assert_eq!(params.waveform, Waveform::Saw);
+        assert_eq!(SynthParams::from_text(&params.to_text()), params);
+
+        // two octaves down at four octaves a second cuts out after half a second
+        let sound = params.render(1000);
+        assert!((sound.frame_count() as i32 - 500).abs() <= 1);
+        // two bits rounds to halves, then it's at half volume
+        assert!(
+            sound
+                .samples()
+                .iter()
+                .all(|s| [-0.5, -0.25, 0.0, 0.25, 0.5].contains(s))
+        );
+
+        let explosion = SynthParams::preset(SynthPreset::Explosion, 7);
+        assert!(
+            explosion
+                .render(8000)
+                .samples()
+                .iter()
+                .all(|s| s.is_finite())
+        );
+        assert_eq!(explosion.render(8000), explosion.render(8000));
+        assert_ne!(
+            explosion.render(8000),
+            SynthParams::preset(SynthPreset::Explosion, 8).render(8000)
+        );
+    }
+}
@@ -1,15 +1,16 @@
-use engine::core::GameCode;
+use engine::core::{GameCode, game_systems::GameSystems};
 use log::info;
+use render::display_list::DisplayList;
 
 #[allow(dead_code)]
 pub struct SimpleOneFrameGame {}
 
 impl GameCode for SimpleOneFrameGame {
-    fn update(&mut self, _delta: f32) {
+    fn update(&mut self, _delta: f32, _systems: &mut GameSystems) {
         info!(target: "SimpleOneFrameGame", "in game code update");
     }
 
-    fn draw_frame(&mut self) {}
+    fn draw_frame(&mut self, _display_list: &mut DisplayList) {}
 
     fn should_exit(&self) -> bool {
         true